@@ -15,12 +15,12 @@ use rand::Rng;
 use rand_distr::{Distribution, Normal};
 use std::io::{self, stdout, Write};
 use std::net::{TcpListener, TcpStream};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::mpsc::{channel, Receiver};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::{
-    collections::HashMap,
-    fs::File,
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{File, OpenOptions},
     io::{BufRead, BufReader},
     thread,
     time::Duration,
@@ -33,6 +33,524 @@ struct Args {
     /// ATC command mode or simulation mode
     #[arg(short, long, action = ArgAction::SetTrue)]
     sim: bool,
+
+    /// Rows of empty space above and below the airport map
+    #[arg(long, default_value_t = 2)]
+    spacing_tb: usize,
+
+    /// Columns of empty space left and right of the airport map
+    #[arg(long, default_value_t = 20)]
+    spacing_lr: usize,
+
+    /// Automatically send landed aircraft to the nearest free gate
+    #[arg(long, action = ArgAction::SetTrue)]
+    auto_gate: bool,
+
+    /// Let the simulator issue clearances itself, so that the airport runs on its own
+    #[arg(long, action = ArgAction::SetTrue)]
+    autopilot: bool,
+
+    /// Wind speed in clear weather as "mean,std_dev,min,max" knots
+    #[arg(long, default_value = "10,1,0,20")]
+    wind_clear: WindDistribution,
+
+    /// Wind speed in rain as "mean,std_dev,min,max" knots
+    #[arg(long, default_value = "30,5,20,40")]
+    wind_rain: WindDistribution,
+
+    /// Wind speed in inclement weather as "mean,std_dev,min,max" knots
+    #[arg(long, default_value = "50,10,50,60")]
+    wind_inclement: WindDistribution,
+
+    /// Wind speed in a thunderstorm as "mean,std_dev,min,max" knots
+    #[arg(long, default_value = "40,10,25,60")]
+    wind_thunderstorm: WindDistribution,
+
+    /// Wind speed in snow as "mean,std_dev,min,max" knots
+    #[arg(long, default_value = "20,5,10,35")]
+    wind_snow: WindDistribution,
+
+    /// Tick ranges during which noise sensitive runways are closed for departures, e.g. "100-200,500-600"
+    #[arg(long, value_delimiter = ',')]
+    quiet_hours: Vec<TickRange>,
+
+    /// Chance of a scheduled arrival showing up in clear weather
+    #[arg(long, default_value_t = 1.0, value_parser = parse_probability)]
+    arrivals_clear: f64,
+
+    /// Chance of a scheduled arrival showing up in rain
+    #[arg(long, default_value_t = 0.7, value_parser = parse_probability)]
+    arrivals_rain: f64,
+
+    /// Chance of a scheduled arrival showing up in inclement weather
+    #[arg(long, default_value_t = 0.3, value_parser = parse_probability)]
+    arrivals_inclement: f64,
+
+    /// Chance of a scheduled arrival showing up in a thunderstorm
+    #[arg(long, default_value_t = 0.0, value_parser = parse_probability)]
+    arrivals_thunderstorm: f64,
+
+    /// Chance of a scheduled arrival showing up in snow
+    #[arg(long, default_value_t = 0.5, value_parser = parse_probability)]
+    arrivals_snow: f64,
+
+    /// Chance of a new plane being an arrival rather than a departure from a free gate
+    #[arg(long, default_value_t = 1.0, value_parser = parse_probability)]
+    arr_dep_ratio: f64,
+
+    /// Wind speed in knots above which the dashboard highlights the wind
+    #[arg(long, default_value_t = 35.0)]
+    wind_limit: f64,
+
+    /// Fraction of gates that start with a departure on them, part way through its turnaround
+    #[arg(long, default_value_t = 0.0, value_parser = parse_probability)]
+    prefill_gates: f64,
+
+    /// Chance of a new plane being a heavy, which takes up two tiles
+    #[arg(long, default_value_t = 0.0, value_parser = parse_probability)]
+    heavy_ratio: f64,
+
+    /// Chance of a new plane being a medevac flight, which lands and departs ahead of others
+    #[arg(long, default_value_t = 0.0, value_parser = parse_probability)]
+    medevac_ratio: f64,
+
+    /// Chance of a new plane being a VIP flight, which lands and departs ahead of all but
+    /// medevac flights
+    #[arg(long, default_value_t = 0.0, value_parser = parse_probability)]
+    vip_ratio: f64,
+
+    /// Most crosswind in knots a category of aircraft may take off or land in, as
+    /// "category=knots" with a category of light or heavy, e.g. "light=20"; can be repeated
+    #[arg(long, value_parser = parse_wind_limit)]
+    crosswind_limit: Vec<(String, f64)>,
+
+    /// Most tailwind in knots a category of aircraft may take off or land in, as
+    /// "category=knots" with a category of light or heavy, e.g. "heavy=10"; can be repeated
+    #[arg(long, value_parser = parse_wind_limit)]
+    tailwind_limit: Vec<(String, f64)>,
+
+    /// Ticks a taxiing plane may stand still before it is reported as stuck
+    #[arg(long, default_value_t = 10)]
+    stuck_after: usize,
+
+    /// Ticks a plane holds short of its runway before asking again for departure, or with
+    /// --autopilot before it is lined up once the runway is free
+    #[arg(long)]
+    hold_short_timeout: Option<usize>,
+
+    /// Put planes that are reported as stuck on hold
+    #[arg(long, action = ArgAction::SetTrue)]
+    hold_stuck: bool,
+
+    /// Make taxiing planes hold short of runways until cleared to cross them
+    #[arg(long, action = ArgAction::SetTrue)]
+    crossing_clearance: bool,
+
+    /// Also count two planes swapping tiles in one tick as a collision
+    #[arg(long, action = ArgAction::SetTrue)]
+    detect_swaps: bool,
+
+    /// Ring the terminal bell on collisions and loss of separation
+    #[arg(long, action = ArgAction::SetTrue)]
+    alerts: bool,
+
+    /// Report to show when two planes collide, with {first} and {second} standing for their
+    /// call signs and {location} for where it happened
+    #[arg(long)]
+    collision_message: Option<String>,
+
+    /// File of "tick: command" lines to issue at the given ticks. All the commands due at
+    /// a tick are carried out in that tick.
+    #[arg(long)]
+    script: Option<String>,
+
+    /// File to append every ATC clearance to, with its tick and aircraft
+    #[arg(long)]
+    transcript: Option<String>,
+
+    /// Also speak the reason a command was rejected, not just clearances
+    #[arg(long, action = ArgAction::SetTrue)]
+    speak_errors: bool,
+
+    /// Allow debug commands, such as wx to set the weather
+    #[arg(long, action = ArgAction::SetTrue)]
+    debug: bool,
+
+    /// Tiles before the runway threshold where an arrival must be flying a stable approach
+    #[arg(long, default_value_t = 5)]
+    stable_gate: usize,
+
+    /// Tiles before the runway threshold where arrivals call established on final
+    #[arg(long)]
+    final_fix: Option<usize>,
+
+    /// Extra name for a command keyword as "alias=keyword", e.g. "land=l"; can be repeated
+    #[arg(long, value_parser = parse_alias)]
+    alias: Vec<(String, String)>,
+
+    /// Most ticks an arrival may come early or late relative to the landing interval
+    #[arg(long, default_value_t = 0)]
+    arrival_jitter: usize,
+
+    /// Preferred departure runway for a gate as "gate=runway", e.g. "4=2"; can be repeated
+    #[arg(long, value_parser = parse_gate_runway)]
+    gate_runway: Vec<(String, String)>,
+
+    /// Runway departures file for when their gate has none of its own: gate (runway 1),
+    /// random (any open runway) or favoured (the runway in use)
+    #[arg(long, default_value = "gate")]
+    departure_runways: DepartureRunways,
+
+    /// Flight numbers for an airline as "code=first-last", e.g. "BA=100-299"; can be repeated
+    #[arg(long, value_parser = parse_flight_numbers)]
+    flight_numbers: Vec<(String, (usize, usize))>,
+
+    /// Flight never given to a new plane, e.g. "AA100" for a scripted flight; can be repeated
+    #[arg(long)]
+    reserve_name: Vec<String>,
+
+    /// Number of tiles side by side that arrivals may enter the approach on
+    #[arg(long, default_value_t = 1)]
+    corridor_width: usize,
+
+    /// File to draw each frame to instead of the terminal, which then holds the latest frame
+    #[arg(long)]
+    render_to: Option<String>,
+
+    /// Check the map file at the given path for layout problems, print a report and exit
+    #[arg(long)]
+    validate_map: Option<String>,
+
+    /// Token command consoles must send on connecting to the simulator
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Redraw the screen only every this many ticks
+    #[arg(long, default_value_t = 1, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    render_interval: usize,
+
+    /// File of "from -> to: chance" lines, each giving the chance per second of the weather
+    /// changing from one condition to another, to use instead of the built-in changes
+    #[arg(long)]
+    weather_table: Option<String>,
+
+    /// File of "id = text" lines rewording the clearances and messages with those ids, e.g.
+    /// to give them in another language
+    #[arg(long)]
+    lang: Option<String>,
+
+    /// Update the weather only every this many ticks
+    #[arg(long, default_value_t = 1, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    weather_interval: usize,
+
+    /// Length of a tick in milliseconds. Weather changes at the same real-world pace whatever it is.
+    #[arg(long, default_value_t = 1000, value_parser = clap::value_parser!(u64).range(1..))]
+    tick_ms: u64,
+
+    /// Kinds of flight a new plane is picked from: mixed, passenger, cargo or international.
+    /// Passenger and cargo flights skip the other's turnaround steps, and international
+    /// flights add customs and security steps.
+    #[arg(long, value_delimiter = ',', default_value = "mixed")]
+    flight_types: Vec<FlightType>,
+
+    /// Units to show speeds and altitudes in: imperial (knots and feet) or metric (km/h and metres)
+    #[arg(long, default_value = "imperial")]
+    units: Units,
+
+    /// Wording of clearances: terse (call sign and instruction only), standard, or verbose
+    /// (adding the wind and a readback request)
+    #[arg(long, default_value = "standard")]
+    phraseology: Phraseology,
+
+    /// Order of the plane table: id, airline, action or runway
+    #[arg(long, default_value = "id")]
+    sort: PlaneSort,
+
+    /// Screen layout: stacked (the plane table and messages below the map) or side (beside it)
+    #[arg(long, default_value = "stacked")]
+    layout: Layout,
+
+    /// Most planes that may hold in the air before the longest holding one diverts
+    #[arg(long)]
+    holding_capacity: Option<usize>,
+
+    /// Ticks an arrival may fly without a landing clearance before it diverts
+    #[arg(long)]
+    max_airborne: Option<usize>,
+
+    /// Warn about collisions the planes are heading for within this many ticks
+    #[arg(long)]
+    predict_conflicts: Option<usize>,
+
+    /// Chance of a clearance only being carried out once read back with "rb <command>"
+    #[arg(long, default_value_t = 0.0, value_parser = parse_probability)]
+    readback_ratio: f64,
+
+    /// Fewest ticks between landing clearances on the same runway, which are also refused
+    /// until the previous arrival has vacated it. Takeoff clearances wait as long after a
+    /// landing clearance, unless given with "it" for an immediate takeoff.
+    #[arg(long)]
+    arrival_spacing: Option<usize>,
+
+    /// End the simulation after this many ticks
+    #[arg(long)]
+    max_ticks: Option<usize>,
+}
+
+fn parse_alias(s: &str) -> Result<(String, String), String> {
+    let (alias, keyword) = s
+        .split_once('=')
+        .ok_or(format!("Invalid alias {}, expected alias=keyword", s))?;
+    let (alias, keyword) = (alias.trim(), keyword.trim());
+    if alias.is_empty() || keyword.is_empty() || alias.contains(char::is_whitespace) {
+        return Err(format!("Invalid alias {}, expected alias=keyword", s));
+    }
+    Ok((alias.to_lowercase(), keyword.to_lowercase()))
+}
+
+fn parse_gate_runway(s: &str) -> Result<(String, String), String> {
+    let (gate, runway) = s
+        .split_once('=')
+        .ok_or(format!("Invalid gate runway {}, expected gate=runway", s))?;
+    let (gate, runway) = (gate.trim(), runway.trim());
+    if gate.is_empty() || runway.parse::<usize>().is_err() {
+        return Err(format!("Invalid gate runway {}, expected gate=runway", s));
+    }
+    Ok((gate.to_string(), runway.to_string()))
+}
+
+fn parse_wind_limit(s: &str) -> Result<(String, f64), String> {
+    let invalid = || format!("Invalid wind limit {}, expected category=knots", s);
+    let (category, knots) = s.split_once('=').ok_or_else(invalid)?;
+    let category = category.trim().to_lowercase();
+    if category != "light" && category != "heavy" {
+        return Err(format!(
+            "Unknown aircraft category {}, expected light or heavy",
+            category
+        ));
+    }
+    let knots = knots.trim().parse::<f64>().map_err(|_| invalid())?;
+    if knots < 0.0 {
+        return Err(invalid());
+    }
+    Ok((category, knots))
+}
+
+fn parse_flight_numbers(s: &str) -> Result<(String, (usize, usize)), String> {
+    let invalid = || format!("Invalid flight numbers {}, expected code=first-last", s);
+    let (code, range) = s.split_once('=').ok_or_else(invalid)?;
+    let (first, last) = range.split_once('-').ok_or_else(invalid)?;
+    let first = first.trim().parse::<usize>().map_err(|_| invalid())?;
+    let last = last.trim().parse::<usize>().map_err(|_| invalid())?;
+    let code = code.trim().to_uppercase();
+    if !AIRWAY_IDS.contains_key(code.as_str()) {
+        return Err(format!("Unknown airline {}", code));
+    }
+    if first > last {
+        return Err(format!(
+            "Flight numbers {} must not start after {}",
+            first, last
+        ));
+    }
+    Ok((code, (first, last)))
+}
+
+fn parse_probability(s: &str) -> Result<f64, String> {
+    let p = s
+        .parse::<f64>()
+        .map_err(|e| format!("Invalid probability {}: {}", s, e))?;
+    if !(0.0..=1.0).contains(&p) {
+        return Err(format!("Probability must be between 0 and 1, got {}", p));
+    }
+    Ok(p)
+}
+
+// Half-open range of game ticks written as "start-end"
+#[derive(Debug, Clone)]
+struct TickRange {
+    start: usize,
+    end: usize,
+}
+
+impl TickRange {
+    pub fn contains(&self, tick: usize) -> bool {
+        self.start <= tick && tick < self.end
+    }
+}
+
+impl std::str::FromStr for TickRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or(format!("Invalid tick range {}, expected start-end", s))?;
+        let start = start
+            .trim()
+            .parse::<usize>()
+            .map_err(|e| format!("Invalid tick range {}: {}", s, e))?;
+        let end = end
+            .trim()
+            .parse::<usize>()
+            .map_err(|e| format!("Invalid tick range {}: {}", s, e))?;
+        if start >= end {
+            return Err(format!(
+                "Invalid tick range {}: start must be before end",
+                s
+            ));
+        }
+        Ok(TickRange { start, end })
+    }
+}
+
+// Column the plane table is ordered by
+#[derive(Debug, Clone, PartialEq)]
+enum PlaneSort {
+    Id,
+    Airline,
+    Action,
+    Runway,
+}
+
+impl std::str::FromStr for PlaneSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(PlaneSort::Id),
+            "airline" => Ok(PlaneSort::Airline),
+            "action" => Ok(PlaneSort::Action),
+            "runway" => Ok(PlaneSort::Runway),
+            _ => Err(format!(
+                "Invalid sort order {}, expected id, airline, action or runway",
+                s
+            )),
+        }
+    }
+}
+
+// Where the plane table and messages are drawn relative to the map
+#[derive(Debug, Clone, PartialEq)]
+enum Layout {
+    Stacked,
+    SidePanel,
+}
+
+impl std::str::FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stacked" => Ok(Layout::Stacked),
+            "side" => Ok(Layout::SidePanel),
+            _ => Err(format!("Invalid layout {}, expected stacked or side", s)),
+        }
+    }
+}
+
+// How departures from the gate are given their runway
+#[derive(Debug, Clone, PartialEq)]
+enum DepartureRunways {
+    Gate,
+    Random,
+    Favoured,
+}
+
+impl std::str::FromStr for DepartureRunways {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gate" => Ok(DepartureRunways::Gate),
+            "random" => Ok(DepartureRunways::Random),
+            "favoured" => Ok(DepartureRunways::Favoured),
+            _ => Err(format!(
+                "Invalid departure runways {}, expected gate, random or favoured",
+                s
+            )),
+        }
+    }
+}
+
+// How much is said in a clearance
+#[derive(Debug, Clone, PartialEq)]
+enum Phraseology {
+    Terse,
+    Standard,
+    Verbose,
+}
+
+impl std::str::FromStr for Phraseology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "terse" => Ok(Phraseology::Terse),
+            "standard" => Ok(Phraseology::Standard),
+            "verbose" => Ok(Phraseology::Verbose),
+            _ => Err(format!(
+                "Invalid phraseology {}, expected terse, standard or verbose",
+                s
+            )),
+        }
+    }
+}
+
+// Units speeds and altitudes are shown in. They are always kept in knots and feet.
+#[derive(Debug, Clone, PartialEq)]
+enum Units {
+    Imperial,
+    Metric,
+}
+
+impl Units {
+    pub fn speed(&self, knots: f64) -> f64 {
+        match self {
+            Units::Imperial => knots,
+            Units::Metric => knots * 1.852,
+        }
+    }
+
+    pub fn speed_unit(&self) -> &'static str {
+        match self {
+            Units::Imperial => "kn",
+            Units::Metric => "km/h",
+        }
+    }
+
+    // Speed unit as read out in clearances
+    pub fn spoken_speed_unit(&self) -> &'static str {
+        match self {
+            Units::Imperial => "knots",
+            Units::Metric => "kilometres per hour",
+        }
+    }
+
+    pub fn altitude(&self, feet: f64) -> f64 {
+        match self {
+            Units::Imperial => feet,
+            Units::Metric => feet * 0.3048,
+        }
+    }
+
+    pub fn altitude_unit(&self) -> &'static str {
+        match self {
+            Units::Imperial => "ft",
+            Units::Metric => "m",
+        }
+    }
+}
+
+impl std::str::FromStr for Units {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "imperial" => Ok(Units::Imperial),
+            "metric" => Ok(Units::Metric),
+            _ => Err(format!("Invalid units {}, expected imperial or metric", s)),
+        }
+    }
 }
 
 // Stores the latest error message
@@ -59,6 +577,17 @@ lazy_static! {
         timer: AtomicUsize::new(0),
     });
 }
+// Multi-line status requested by the controller, e.g. the plane list
+lazy_static! {
+    static ref STATUS: Mutex<Message> = Mutex::new(Message {
+        message: String::new(),
+        timer: AtomicUsize::new(0),
+    });
+}
+// Consoles connected over TCP by address, to send the results of their commands back to
+lazy_static! {
+    static ref CONSOLES: Mutex<HashMap<String, TcpStream>> = Mutex::new(HashMap::new());
+}
 
 #[derive(Clone, PartialEq, Debug)]
 enum Direction {
@@ -69,20 +598,69 @@ enum Direction {
     StayPut,
 }
 
+// Signed map coordinates for working out moves, so that a step off any side of the map
+// gives a coordinate beyond it instead of wrapping around or underflowing
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Coord {
+    row: isize,
+    col: isize,
+}
+
+impl Coord {
+    pub fn from_position(position: (usize, usize)) -> Self {
+        Coord {
+            row: position.0 as isize,
+            col: position.1 as isize,
+        }
+    }
+}
+
 impl Direction {
-    pub fn go(self, position: (usize, usize)) -> (usize, usize) {
-        match self {
-            Direction::North => (position.0 - 1, position.1),
-            Direction::South => (position.0 + 1, position.1),
-            Direction::East => (position.0, position.1 + 1),
-            Direction::West => (position.0, position.1 - 1),
-            Direction::StayPut => (position.0, position.1),
+    // Coordinate one step away in this direction, which may be off the map
+    pub fn step(&self, position: (usize, usize)) -> Coord {
+        let coord = Coord::from_position(position);
+        let (rows, cols) = match self {
+            Direction::North => (-1, 0),
+            Direction::South => (1, 0),
+            Direction::East => (0, 1),
+            Direction::West => (0, -1),
+            Direction::StayPut => (0, 0),
+        };
+        Coord {
+            row: coord.row + rows,
+            col: coord.col + cols,
         }
     }
 
+    // Position one step away in this direction, or None if that is off the map
+    pub fn go(self, map: &Map, position: (usize, usize)) -> Option<(usize, usize)> {
+        map.position(self.step(position))
+    }
+
     pub fn fetch_mappoint(self, map: &Map, position: (usize, usize)) -> MapPoint {
-        let (x, y) = self.go(position);
-        map.map[x][y].clone()
+        map.point(self.step(position))
+    }
+
+    // Arrow used to draw a plane facing this direction
+    pub fn plane_glyph(&self) -> &'static str {
+        match self {
+            Direction::North => "▲",
+            Direction::South => "▼",
+            Direction::East => "▶",
+            Direction::West => "◀",
+            Direction::StayPut => "",
+        }
+    }
+
+    // Magnetic heading of this direction in degrees
+    pub fn heading(&self) -> u16 {
+        match self {
+            Direction::North => 360,
+            Direction::East => 90,
+            Direction::South => 180,
+            Direction::West => 270,
+            Direction::StayPut => 0,
+        }
     }
 
     pub fn get_opposite_dir(self) -> Self {
@@ -95,6 +673,21 @@ impl Direction {
         }
     }
 
+    // Direction of a single step from one position to a neighbouring one
+    pub fn between(from: (usize, usize), to: (usize, usize)) -> Option<Self> {
+        if to.0 + 1 == from.0 && to.1 == from.1 {
+            Some(Direction::North)
+        } else if to.0 == from.0 + 1 && to.1 == from.1 {
+            Some(Direction::South)
+        } else if to.0 == from.0 && to.1 == from.1 + 1 {
+            Some(Direction::East)
+        } else if to.0 == from.0 && to.1 + 1 == from.1 {
+            Some(Direction::West)
+        } else {
+            None
+        }
+    }
+
     pub fn parse(dir: &char) -> Result<Self, String> {
         match dir {
             'N' => Ok(Direction::North),
@@ -111,6 +704,8 @@ impl Direction {
 struct Runway {
     name: usize,
     side: Direction,
+    noise_sensitive: bool,
+    heading: u16, // Magnetic heading in degrees
 }
 
 impl Runway {
@@ -129,6 +724,12 @@ impl Runway {
                             Runway {
                                 name: name.clone(),
                                 side: side.clone(),
+                                noise_sensitive: map.noise_sensitive.contains(name),
+                                heading: map
+                                    .runway_headings
+                                    .get(name)
+                                    .copied()
+                                    .unwrap_or(side.heading()),
                             },
                         );
                     }
@@ -137,6 +738,63 @@ impl Runway {
         }
         runways
     }
+
+    // Whether planes can be given the runway. Runway 0 marks runway ends on the map.
+    pub fn is_usable(&self) -> bool {
+        self.name != 0 && self.side != Direction::StayPut
+    }
+
+    // Runway designator as read out in clearances, e.g. "two seven" for a heading of 270
+    pub fn spoken_name(&self) -> String {
+        let number = match (self.heading as usize + 5) / 10 % 36 {
+            0 => 36,
+            number => number,
+        };
+        spoken_digits(&format!("{:02}", number))
+    }
+
+    // Crosswind and tailwind in knots for a plane using the runway
+    pub fn wind_components(&self, weather: &Weather) -> (f64, f64) {
+        let angle = (weather.wind_direction as f64 - self.heading as f64).to_radians();
+        let crosswind = (weather.wind_speed * angle.sin()).abs();
+        let tailwind = (-self.headwind(weather)).max(0.0);
+        (crosswind, tailwind)
+    }
+
+    // Wind in knots along the runway into the face of a plane using it, negative for a tailwind
+    pub fn headwind(&self, weather: &Weather) -> f64 {
+        let angle = (weather.wind_direction as f64 - self.heading as f64).to_radians();
+        weather.wind_speed * angle.cos()
+    }
+}
+
+// Reads a number out digit by digit, e.g. "one niner" for 19
+fn spoken_digits(number: &str) -> String {
+    number
+        .chars()
+        .map(|digit| match digit {
+            '0' => "zero",
+            '1' => "one",
+            '2' => "two",
+            '3' => "three",
+            '4' => "four",
+            '5' => "five",
+            '6' => "six",
+            '7' => "seven",
+            '8' => "eight",
+            _ => "niner",
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// Taxiway name as typed in a command, with or without a leading "t", e.g. "t7" or "a"
+fn taxiway_name(input: &str) -> String {
+    input
+        .strip_prefix(['t', 'T'])
+        .filter(|name| !name.is_empty())
+        .unwrap_or(input)
+        .to_uppercase()
 }
 
 #[derive(Clone, Debug)]
@@ -173,13 +831,48 @@ impl Gate {
 #[derive(Clone, PartialEq, Debug)]
 enum MapPoint {
     Runway((usize, Direction)),
-    Taxiway((usize, Direction)),
+    Taxiway((String, Direction)),
     Gate(String),
     GateTaxiLine((String, Direction)),
+    DeicePad(String),
     Empty,
 }
 
+// Drawn over runways and taxiways that are closed
+const CLOSED_GLYPH: &str = "X";
+
 impl MapPoint {
+    // Character used to draw this point on the map
+    fn glyph(&self) -> &str {
+        match self {
+            MapPoint::Empty => " ",
+            MapPoint::Runway((usize, dir)) => match usize {
+                0 => "∥",
+                _ => match dir {
+                    Direction::North | Direction::South => "∥",
+                    Direction::East | Direction::West => "=",
+                    _ => " ",
+                },
+            },
+            MapPoint::Taxiway((_, dir)) => match dir {
+                Direction::North => "^",
+                Direction::South => "v",
+                Direction::East => ">",
+                Direction::West => "<",
+                _ => " ",
+            },
+            MapPoint::Gate(name) => name,
+            MapPoint::DeicePad(_) => "*",
+            MapPoint::GateTaxiLine((_, dir)) => match dir {
+                Direction::North => "↑",
+                Direction::South => "↓",
+                Direction::East => "→",
+                Direction::West => "←",
+                _ => " ",
+            },
+        }
+    }
+
     fn check_if_runway(self) -> bool {
         match self {
             MapPoint::Runway(_) => true,
@@ -227,16 +920,28 @@ impl MapPoint {
         gate: &str,
         direction: Direction,
     ) -> bool {
-        // Search all directions for a gate taxi line
-        if direction
-            .to_owned()
-            .fetch_mappoint(map, position)
-            .check_if_gate_taxi_line()
-        {
-            let new_pos = direction.to_owned().go(position);
-            return self.check_for_gate_taxi_line(map, new_pos, gate, direction);
-        } else if direction.fetch_mappoint(map, position).check_if_gate(gate) {
-            return true;
+        // Step onto the gate taxi line, then follow the direction of each of its tiles,
+        // so that the line may bend on its way to the gate
+        let (rows, cols) = map.bounds();
+        let (mut position, mut direction) = (position, direction);
+        for _ in 0..rows * cols {
+            let point = direction.to_owned().fetch_mappoint(map, position);
+            if point.clone().check_if_gate(gate) {
+                return true;
+            }
+            match point {
+                // A line pointing back the way we came leads away from the gate
+                MapPoint::GateTaxiLine((_, next))
+                    if next != direction.clone().get_opposite_dir() =>
+                {
+                    position = match direction.go(map, position) {
+                        Some(position) => position,
+                        None => return false,
+                    };
+                    direction = next;
+                }
+                _ => return false,
+            }
         }
         false
     }
@@ -288,33 +993,310 @@ struct Spacing {
     left_right: usize,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Map {
-    _length: usize,
-    _width: usize,
+    length: usize,
+    width: usize,
     spacing: Spacing,
     map: Vec<Vec<MapPoint>>,
+    noise_sensitive: Vec<usize>, // Runways with departure restrictions during quiet hours
+    runway_headings: HashMap<usize, u16>, // Headings annotated in the map file
+}
+
+impl Map {
+    // Number of rows and columns of the map, including the spacing around it
+    pub fn bounds(&self) -> (usize, usize) {
+        (
+            self.length + 2 * self.spacing.top_bottom,
+            self.width + 2 * self.spacing.left_right,
+        )
+    }
+
+    // Whether any tile belongs to the taxiway with the given name
+    pub fn has_taxiway(&self, name: &str) -> bool {
+        self.map.iter().flatten().any(|point| match point {
+            MapPoint::Taxiway((n, _)) => n == name,
+            _ => false,
+        })
+    }
+
+    pub fn has_deice_pad(&self, name: &str) -> bool {
+        self.map
+            .iter()
+            .flatten()
+            .any(|point| *point == MapPoint::DeicePad(name.to_string()))
+    }
+
+    // Position of the coordinate, or None if it lies off the map
+    pub fn position(&self, coord: Coord) -> Option<(usize, usize)> {
+        let (rows, cols) = self.bounds();
+        match (usize::try_from(coord.row), usize::try_from(coord.col)) {
+            (Ok(row), Ok(col)) if row < rows && col < cols => Some((row, col)),
+            _ => None,
+        }
+    }
+
+    // Point at the coordinate, where everything off the map is empty
+    pub fn point(&self, coord: Coord) -> MapPoint {
+        match self.position(coord) {
+            Some((row, col)) => self.map[row][col].clone(),
+            None => MapPoint::Empty,
+        }
+    }
+
+    // Whether moving one step in the given direction would leave the map
+    pub fn is_at_edge(&self, position: (usize, usize), direction: &Direction) -> bool {
+        let (rows, cols) = self.bounds();
+        match direction {
+            Direction::North => position.0 == 0,
+            Direction::South => position.0 + 1 >= rows,
+            Direction::West => position.1 == 0,
+            Direction::East => position.1 + 1 >= cols,
+            Direction::StayPut => false,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 enum WeatherCondition {
     Clear,
     Rain,
     InclementWeather,
+    Thunderstorm, // Ground stop, arrivals divert
+    Snow,         // Reduced braking, departures need de-icing
+}
+
+impl WeatherCondition {
+    // Colour the condition is shown in, by how much it gets in the way of operations
+    pub fn color(&self) -> &'static str {
+        match self {
+            WeatherCondition::Clear => GREEN_COLOR,
+            WeatherCondition::Rain => YELLOW_COLOR,
+            WeatherCondition::Snow => CYAN_COLOR,
+            WeatherCondition::InclementWeather | WeatherCondition::Thunderstorm => RED_COLOR,
+        }
+    }
+
+    // Visibility in metres and weather group as given in a METAR
+    pub fn metar(&self) -> (usize, &'static str) {
+        match self {
+            WeatherCondition::Clear => (9999, "NSW"),
+            WeatherCondition::Rain => (6000, "RA"),
+            WeatherCondition::InclementWeather => (2000, "+RA"),
+            WeatherCondition::Thunderstorm => (3000, "TSRA"),
+            WeatherCondition::Snow => (1500, "SN"),
+        }
+    }
+}
+
+impl std::str::FromStr for WeatherCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clear" => Ok(WeatherCondition::Clear),
+            "rain" => Ok(WeatherCondition::Rain),
+            "inclement" => Ok(WeatherCondition::InclementWeather),
+            "thunderstorm" => Ok(WeatherCondition::Thunderstorm),
+            "snow" => Ok(WeatherCondition::Snow),
+            _ => Err(format!(
+                "Invalid weather {}, expected clear, rain, inclement, thunderstorm or snow",
+                s
+            )),
+        }
+    }
+}
+
+// Chance per second of the weather changing from one condition to another
+#[derive(Debug, Clone)]
+struct WeatherTransition {
+    from: WeatherCondition,
+    to: WeatherCondition,
+    chance_per_second: f64,
+}
+
+impl std::str::FromStr for WeatherTransition {
+    type Err = String;
+
+    // Parses "from -> to: chance", e.g. "clear -> rain: 0.01"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid weather change {}, expected from -> to: chance", s);
+        let (conditions, chance) = s.split_once(':').ok_or_else(invalid)?;
+        let (from, to) = conditions.split_once("->").ok_or_else(invalid)?;
+        Ok(WeatherTransition {
+            from: from.trim().parse()?,
+            to: to.trim().parse()?,
+            chance_per_second: parse_probability(chance.trim())?,
+        })
+    }
 }
 
-#[derive(Debug)]
+// Built-in weather changes, tried in order until one happens
+const WEATHER_TRANSITIONS: [WeatherTransition; 8] = [
+    WeatherTransition {
+        from: WeatherCondition::Clear,
+        to: WeatherCondition::Rain,
+        chance_per_second: 2.0 / 300.0,
+    },
+    WeatherTransition {
+        from: WeatherCondition::Clear,
+        to: WeatherCondition::InclementWeather,
+        chance_per_second: 2.0 / 1000.0,
+    },
+    WeatherTransition {
+        from: WeatherCondition::Clear,
+        to: WeatherCondition::Snow,
+        chance_per_second: 2.0 / 1000.0,
+    },
+    WeatherTransition {
+        from: WeatherCondition::Rain,
+        to: WeatherCondition::Thunderstorm,
+        chance_per_second: 0.01,
+    },
+    WeatherTransition {
+        from: WeatherCondition::Rain,
+        to: WeatherCondition::Clear,
+        chance_per_second: 0.04,
+    },
+    WeatherTransition {
+        from: WeatherCondition::Thunderstorm,
+        to: WeatherCondition::Rain,
+        chance_per_second: 0.10,
+    },
+    WeatherTransition {
+        from: WeatherCondition::Snow,
+        to: WeatherCondition::Clear,
+        chance_per_second: 0.03,
+    },
+    WeatherTransition {
+        from: WeatherCondition::InclementWeather,
+        to: WeatherCondition::Clear,
+        chance_per_second: 0.02,
+    },
+];
+
+#[derive(Debug, Clone)]
 struct Weather {
     condition: WeatherCondition,
-    wind_direction: usize, // 0-360 degrees
-    wind_speed: f64,       // 0-60 knots
+    wind_direction: usize,  // 0-360 degrees
+    wind_speed: f64,        // 0-MAX_WIND_SPEED knots
+    wind_gust: Option<f64>, // Peak knots, when gusting well above the mean
+    wind: WindProfile,
 }
 
-#[derive(Debug, Clone, Sequence, PartialEq)]
-enum AtGateAction {
-    ShutdownProcedure,
-    DeboardPassengers,
-    DeboardCargo,
+const MAX_WIND_SPEED: f64 = 60.0;
+
+// Knots a peak has to exceed the mean wind by to be reported as a gust
+const GUST_SPREAD_KT: f64 = 10.0;
+
+// Normal distribution of wind speeds, clamped to [min, max] knots
+#[derive(Debug, Clone)]
+struct WindDistribution {
+    mean: f64,
+    std_dev: f64,
+    min: f64,
+    max: f64,
+}
+
+impl WindDistribution {
+    pub fn sample(&self) -> f64 {
+        let normal = Normal::new(self.mean, self.std_dev).unwrap();
+        normal
+            .sample(&mut rand::thread_rng())
+            .clamp(self.min, self.max)
+    }
+}
+
+impl std::str::FromStr for WindDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| format!("Invalid wind distribution {}: {}", s, e))?;
+        if values.len() != 4 {
+            return Err("Wind distribution must be mean,std_dev,min,max".to_string());
+        }
+        if values.iter().any(|v| !v.is_finite()) {
+            return Err(format!("Wind distribution values must be finite: {}", s));
+        }
+        let (mean, std_dev, min, max) = (values[0], values[1], values[2], values[3]);
+        if std_dev < 0.0 {
+            return Err(format!(
+                "Wind speed standard deviation must not be negative: {}",
+                s
+            ));
+        }
+        if min > max {
+            return Err(format!("Invalid wind distribution: {}", s));
+        }
+        if min < 0.0 || max > MAX_WIND_SPEED {
+            return Err(format!(
+                "Wind speed bounds must lie within 0-{} knots",
+                MAX_WIND_SPEED
+            ));
+        }
+        Ok(WindDistribution {
+            mean,
+            std_dev,
+            min,
+            max,
+        })
+    }
+}
+
+// Wind speed distribution for each weather condition
+#[derive(Debug, Clone)]
+struct WindProfile {
+    clear: WindDistribution,
+    rain: WindDistribution,
+    inclement: WindDistribution,
+    thunderstorm: WindDistribution,
+    snow: WindDistribution,
+}
+
+impl WindProfile {
+    pub fn for_condition(&self, condition: &WeatherCondition) -> &WindDistribution {
+        match condition {
+            WeatherCondition::Clear => &self.clear,
+            WeatherCondition::Rain => &self.rain,
+            WeatherCondition::InclementWeather => &self.inclement,
+            WeatherCondition::Thunderstorm => &self.thunderstorm,
+            WeatherCondition::Snow => &self.snow,
+        }
+    }
+}
+
+// Chance of a scheduled arrival actually spawning, as bad weather causes diversions
+#[derive(Debug, Clone)]
+struct ArrivalRates {
+    clear: f64,
+    rain: f64,
+    inclement: f64,
+    thunderstorm: f64,
+    snow: f64,
+}
+
+impl ArrivalRates {
+    pub fn for_condition(&self, condition: &WeatherCondition) -> f64 {
+        match condition {
+            WeatherCondition::Clear => self.clear,
+            WeatherCondition::Rain => self.rain,
+            WeatherCondition::InclementWeather => self.inclement,
+            WeatherCondition::Thunderstorm => self.thunderstorm,
+            WeatherCondition::Snow => self.snow,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Sequence, PartialEq)]
+enum AtGateAction {
+    ShutdownProcedure,
+    DeboardPassengers,
+    DeboardCargo,
+    CustomsInspection, // International flights only
     UnloadBaggage,
     UnloadCargo,
     Refuel,
@@ -325,15 +1307,78 @@ enum AtGateAction {
     MaintenanceCheck,
     LoadBaggage,
     LoadPassengers,
+    SecurityScreening, // International flights only
     BoardPassengers,
     LoadAdditionalCargo,
+    DeIce, // Only while it snows
     Standby,
 }
 
-#[derive(Debug, Clone)]
+// Kind of flight, which decides the steps of its turnaround at the gate
+#[derive(Debug, Clone, PartialEq)]
+enum FlightType {
+    Mixed,
+    Passenger,
+    Cargo,
+    International,
+}
+
+impl FlightType {
+    // Steps the plane goes through at the gate, in order, ending with standby
+    pub fn turnaround(&self) -> Vec<AtGateAction> {
+        let cargo_steps = [
+            AtGateAction::DeboardCargo,
+            AtGateAction::UnloadCargo,
+            AtGateAction::LoadCargo,
+            AtGateAction::LoadAdditionalCargo,
+        ];
+        let passenger_steps = [
+            AtGateAction::DeboardPassengers,
+            AtGateAction::UnloadBaggage,
+            AtGateAction::LoadBaggage,
+            AtGateAction::LoadPassengers,
+            AtGateAction::BoardPassengers,
+        ];
+        let international_steps = [
+            AtGateAction::CustomsInspection,
+            AtGateAction::SecurityScreening,
+        ];
+        all::<AtGateAction>()
+            .filter(|step| match self {
+                FlightType::Mixed => !international_steps.contains(step),
+                FlightType::Passenger => {
+                    !international_steps.contains(step) && !cargo_steps.contains(step)
+                }
+                FlightType::Cargo => {
+                    !international_steps.contains(step) && !passenger_steps.contains(step)
+                }
+                FlightType::International => true,
+            })
+            .collect()
+    }
+}
+
+impl std::str::FromStr for FlightType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mixed" => Ok(FlightType::Mixed),
+            "passenger" => Ok(FlightType::Passenger),
+            "cargo" => Ok(FlightType::Cargo),
+            "international" => Ok(FlightType::International),
+            _ => Err(format!(
+                "Invalid flight type {}, expected mixed, passenger, cargo or international",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 enum Action {
     InAir,
-    Land,
+    Land(Option<String>), // Taxiway to exit the runway by, if assigned
     Takeoff,
     HoldPosition,
     TaxiOntoRunway(usize),
@@ -341,6 +1386,42 @@ enum Action {
     TaxiToGate(String),
     Pushback,
     AtGate((String, AtGateAction)), // Gate number, wait time
+    GoAround,
+    Deice(String), // De-icing pad
+}
+
+// How urgently a plane is to be handled, most urgent first, so that planes sort by it
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    Medevac,
+    Vip,
+    Normal,
+}
+
+impl Priority {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::Medevac => "medevac",
+            Priority::Vip => "vip",
+            Priority::Normal => "normal",
+        }
+    }
+}
+
+impl std::str::FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Priority::Normal),
+            "medevac" => Ok(Priority::Medevac),
+            "vip" => Ok(Priority::Vip),
+            _ => Err(format!(
+                "Invalid priority {}, expected normal, medevac or vip",
+                s
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -351,1051 +1432,8364 @@ struct Plane {
     position: (usize, usize),
     runway: Runway,
     out_of_map: bool,
+    diverted: bool, // Left for another airport instead of landing
+    altitude_ft: f64,
+    speed_kt: f64,
+    heading: Direction,
+    stationary_ticks: usize,
+    pushback_held: bool,          // Told to hold pushback for traffic on the lane
+    taxi_route: Vec<String>,      // Taxiways named in the last "t2g ... via" clearance
+    landing_exit: Option<String>, // Taxiway named in the landing clearance
+    cleared_to_land: bool,
+    on_final: bool,                   // Called established on final on this approach
+    go_around_reason: Option<String>, // Why the last approach was abandoned
+    intention: String, // Where the last ground clearance sends the plane, e.g. "to gate 3"
+    on_ground_frequency: bool, // Handed over from Tower to Ground
+    runway_entry_tick: Option<usize>, // Tick the plane touched down or lined up
+    held_short_tick: Option<usize>, // Tick the plane stopped short of its runway
+    de_iced: bool,
+    gate_arrival_tick: Option<usize>,
+    cleared_to_cross: Option<usize>, // Runway the plane may taxi across
+    heavy: bool,                     // Takes up the tile behind it as well
+    fuel_ticks: usize,               // Ticks of flying left
+    airborne_since: Option<usize>,   // Tick an arrival entered the map
+    turning_around: bool, // An arrival that goes on to depart, rather than one spawned at the gate
+    flight_type: FlightType,
+    priority: Priority,
+    immediate_takeoff: bool, // Cleared with "it", accepting closer spacing to arrivals
 }
 
-lazy_static! {
-    static ref AIRWAY_IDS: HashMap<&'static str, &'static str> = {
-        let mut map = HashMap::new();
-        map.insert("AA", "American Airlines");
-        map.insert("DL", "Delta Air Lines");
-        map.insert("UA", "United Airlines");
-        map.insert("BA", "British Airways");
-        map.insert("AF", "Air France");
-        map.insert("LH", "Lufthansa");
-        map.insert("EK", "Emirates");
-        map.insert("QF", "Qantas");
-        map.insert("AS", "Alaska Airlines");
-        map.insert("WN", "Southwest Airlines");
-        map.insert("AI", "Air India");
-        map
-    };
-}
+impl Plane {
+    // Whether the plane is supposed to be moving along the ground
+    pub fn is_moving_on_ground(&self) -> bool {
+        match self.current_action {
+            Action::Land(_)
+            | Action::Takeoff
+            | Action::TaxiToGate(_)
+            | Action::HoldShort
+            | Action::Pushback => true,
+            // Runway 0 marks the point where planes line up and wait
+            Action::TaxiOntoRunway(runway) => runway != 0,
+            // Taxiing to the pad, until de-iced on it
+            Action::Deice(_) => !self.de_iced,
+            Action::InAir | Action::GoAround | Action::HoldPosition | Action::AtGate(_) => false,
+        }
+    }
 
-#[derive(Debug)]
-struct Airport {
-    runways: HashMap<String, Runway>,
-    gates: HashMap<String, Gate>,
-    map: Map,
-    weather: Weather,
-    planes: Vec<Plane>,
-}
+    // Tiles the plane takes up: its position, and for a heavy the tile behind it
+    pub fn footprint(&self) -> Vec<(usize, usize)> {
+        let (row, col) = self.position;
+        let tail = match self.heading {
+            Direction::North => Some((row + 1, col)),
+            Direction::South => row.checked_sub(1).map(|row| (row, col)),
+            Direction::East => col.checked_sub(1).map(|col| (row, col)),
+            Direction::West => Some((row, col + 1)),
+            Direction::StayPut => None,
+        };
+        match (self.heavy, tail) {
+            (true, Some(tail)) => vec![self.position, tail],
+            _ => vec![self.position],
+        }
+    }
 
-struct Time {
-    step_duration: usize, // Duration in seconds for each game step
-}
+    // Category the wind limits are looked up by
+    pub fn category(&self) -> &'static str {
+        match self.heavy {
+            true => "heavy",
+            false => "light",
+        }
+    }
 
-struct _GroundAlert {
-    message: String,
+    pub fn overlaps(&self, another: &Plane) -> bool {
+        let footprint = another.footprint();
+        self.footprint().iter().any(|tile| footprint.contains(tile))
+    }
 }
 
-struct Score {
-    takeoff: usize,
-    crash: usize,
+#[derive(Debug, Clone, PartialEq)]
+enum GroundVehicleKind {
+    Tug,
 }
 
-impl Score {
-    pub fn _score(self) -> i32 {
-        (self.takeoff - (100 * self.crash)) as i32
+impl GroundVehicleKind {
+    pub fn glyph(&self) -> &'static str {
+        match self {
+            GroundVehicleKind::Tug => "■",
+        }
     }
 }
 
-fn construct_airport() -> Airport {
-    let spacing = Spacing {
-        top_bottom: 2,
-        left_right: 20,
-    };
-    let map_path = "./src/airport.map";
-    let map = build_airport_map(map_path, spacing.clone());
+// Vehicle on the ramp that takes up a tile just like a plane does
+#[derive(Debug, Clone)]
+struct GroundVehicle {
+    id: usize,
+    kind: GroundVehicleKind,
+    position: (usize, usize),
+    assisting: Option<usize>, // Plane the vehicle is hooked up to
+}
 
-    let runways = Runway::new(&map);
-    let gates = Gate::new(&map);
-    let mut weather = Weather {
-        condition: WeatherCondition::Clear,
-        wind_direction: 360,
-        wind_speed: 0.0,
-    };
-    simulate_wind_direction_and_speed(&mut weather, 100);
+// Height lost per tile flown on the glide path
+const GLIDE_SLOPE_FT_PER_TILE: f64 = 150.0;
+// Highest an arrival may be when crossing the runway threshold
+const THRESHOLD_CROSSING_FT: f64 = 50.0;
+// Height gained per tile flown after takeoff or during a go-around
+const CLIMB_FT_PER_TILE: f64 = 300.0;
 
-    Airport {
-        runways,
-        gates,
-        map,
-        weather,
-        planes: vec![],
+// First runway tile a plane landing on it touches down on, if the map has the runway
+fn runway_threshold(map: &Map, runway: &Runway) -> Option<(usize, usize)> {
+    let (rows, cols) = map.bounds();
+    let threshold = MapPoint::Runway((runway.name, runway.side.clone()));
+    let mut tiles = (0..rows)
+        .flat_map(|row| (0..cols).map(move |col| (row, col)))
+        .filter(|&(row, col)| map.map[row][col] == threshold);
+    match runway.side {
+        Direction::East => tiles.min_by_key(|&(_, col)| col),
+        Direction::West => tiles.max_by_key(|&(_, col)| col),
+        Direction::South => tiles.min_by_key(|&(row, _)| row),
+        Direction::North => tiles.max_by_key(|&(row, _)| row),
+        Direction::StayPut => tiles.next(),
     }
 }
 
-fn build_airport_map(map_path: &str, spacing: Spacing) -> Map {
-    // open the map file
-    let map_file = File::open(map_path).expect("Failed to open map file");
-
-    // Get the map dimensions present in the first line of the format "XxY"
-    let mut map_dimensions = String::new();
-    let mut map_file = BufReader::new(map_file);
-    map_file
-        .read_line(&mut map_dimensions)
-        .expect("Failed to read map dimensions");
-    let width = map_dimensions
-        .split('x')
-        .next()
-        .expect("Failed to parse map width")
-        .parse::<usize>()
-        .expect("Failed to parse map width");
-    let length = map_dimensions
-        .split('x')
-        .nth(1)
-        .expect("Failed to parse map length")
-        .replace("\n", "")
-        .parse::<usize>()
-        .expect("Failed to parse map length");
-
-    let mut map: Vec<Vec<MapPoint>> = vec![vec![MapPoint::Empty; width]; length];
+// Where arrivals enter the map to start their approach, on the edge lined up with the
+// runway threshold
+fn approach_entry(map: &Map, runway: &Runway) -> Option<(usize, usize)> {
+    let (rows, cols) = map.bounds();
+    let (row, col) = runway_threshold(map, runway)?;
+    Some(match runway.side {
+        Direction::East => (row, 0),
+        Direction::West => (row, cols - 1),
+        Direction::South => (0, col),
+        Direction::North => (rows - 1, col),
+        Direction::StayPut => (row, col),
+    })
+}
 
-    // Read the map file line by line and populate the map
-    for (y, line) in map_file.lines().enumerate() {
-        let line = line.expect("Failed to read line in map");
-        for (x, block) in line.split(",").enumerate() {
-            if block == "..." {
+// Pairs of runways that cross each other, found where both run through the same tile
+fn runway_intersections(map: &Map) -> Vec<(usize, usize)> {
+    let (rows, cols) = map.bounds();
+    let horizontal = |dir: &Direction| matches!(dir, Direction::East | Direction::West);
+    let runway = |(row, col): (usize, usize), across: bool| match map
+        .map
+        .get(row)
+        .and_then(|points| points.get(col))
+    {
+        Some(MapPoint::Runway((name, side)))
+            if *name != 0 && *side != Direction::StayPut && horizontal(side) == across =>
+        {
+            Some(*name)
+        }
+        _ => None,
+    };
+    // The runway running one way through a tile, either on it or passing over it, as
+    // runway 0 tiles mark
+    let through = |(row, col): (usize, usize), across: bool| {
+        let (before, after) = match across {
+            true => ((row, col.wrapping_sub(1)), (row, col + 1)),
+            false => ((row.wrapping_sub(1), col), (row + 1, col)),
+        };
+        runway((row, col), across).or(match (runway(before, across), runway(after, across)) {
+            (Some(a), Some(b)) if a == b => Some(a),
+            _ => None,
+        })
+    };
+    let mut intersections = vec![];
+    for row in 0..rows {
+        for col in 0..cols {
+            if !matches!(map.map[row][col], MapPoint::Runway(_)) {
                 continue;
             }
-            let point = block.chars().nth(0).expect("Failed to parse MapPoint");
-            let name = block.chars().nth(1).expect("Failed to parse Name");
-            let dir_info = block.chars().nth(2).expect("Failed to parse Direction");
-            let direction = Direction::parse(&dir_info).expect("Failed to parse Direction");
-
-            let map_point = match point {
-                'R' => {
-                    let name = name.to_digit(10).expect("Failed to parse Runway Name");
-                    MapPoint::Runway((name as usize, direction))
-                }
-                'T' => {
-                    let name = name.to_digit(10).expect("Failed to parse Taxiway Name");
-                    MapPoint::Taxiway((name as usize, direction))
+            if let (Some(a), Some(b)) = (through((row, col), true), through((row, col), false)) {
+                let pair = (a.min(b), a.max(b));
+                if a != b && !intersections.contains(&pair) {
+                    intersections.push(pair);
                 }
-                'M' => MapPoint::GateTaxiLine((name.to_string(), direction)),
-                'G' => MapPoint::Gate(name.to_string()),
-                _ => MapPoint::Empty,
-            };
-            map[y][x] = map_point;
+            }
         }
     }
+    intersections
+}
 
-    // Add spacing of MapPoint::Empty on left/right sides of map rows
-    let mut map = map
-        .iter()
-        .map(|row| {
-            let mut row = row.clone();
-            for _ in 0..spacing.left_right {
-                row.insert(0, MapPoint::Empty);
-                row.push(MapPoint::Empty);
+// Tiles reachable from the starting tiles by stepping onto neighbours that are kept
+fn connected_tiles(
+    map: &Map,
+    start: Vec<(usize, usize)>,
+    keep: impl Fn(&MapPoint) -> bool,
+) -> HashSet<(usize, usize)> {
+    let (rows, cols) = map.bounds();
+    let mut seen: HashSet<(usize, usize)> = start.iter().cloned().collect();
+    let mut queue: VecDeque<(usize, usize)> = start.into_iter().collect();
+    while let Some((row, col)) = queue.pop_front() {
+        let neighbours = [
+            (row.wrapping_sub(1), col),
+            (row + 1, col),
+            (row, col.wrapping_sub(1)),
+            (row, col + 1),
+        ];
+        for (row, col) in neighbours {
+            if row < rows && col < cols && keep(&map.map[row][col]) && seen.insert((row, col)) {
+                queue.push_back((row, col));
             }
-            row
-        })
-        .collect::<Vec<Vec<MapPoint>>>();
-    // Add spacing num of columns on top and bottom
-    for _ in 0..spacing.top_bottom {
-        let row = vec![MapPoint::Empty; width + (spacing.left_right * 2)];
-        map.insert(0, row.clone());
-        map.push(row);
-    }
-
-    Map {
-        _length: length,
-        _width: width,
-        spacing,
-        map,
+        }
     }
+    seen
 }
 
-// Function to update the game state for each time step
-fn update_game_state(
-    airport: &mut Airport,
-    spawn_plane: bool,
-    score: &mut Score,
-    receiver: &Receiver<String>,
-    tts: &mut Tts,
-) {
-    update_aircraft_position(airport);
-    update_aircraft_from_user_input(airport, receiver, tts);
-    // Signal alerts
-    update_score(airport, score);
-    simulate_weather(airport);
-    if spawn_plane {
-        spawn_landing_aircraft(airport, false);
+// Problems with the layout of a map, such as gates that planes cannot taxi to
+fn validate_map(map: &Map) -> Vec<String> {
+    let tiles = map
+        .map
+        .iter()
+        .enumerate()
+        .flat_map(|(row, points)| {
+            points
+                .iter()
+                .enumerate()
+                .map(move |(col, p)| ((row, col), p))
+        })
+        .collect::<Vec<_>>();
+    let runway_tiles = |name: Option<usize>| {
+        tiles
+            .iter()
+            .filter(|(_, point)| match point {
+                MapPoint::Runway((n, _)) => name.is_none() || name == Some(*n),
+                _ => false,
+            })
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>()
+    };
+    let mut problems = vec![];
+    if runway_tiles(None).is_empty() {
+        problems.push("The map has no runways".to_string());
     }
-    render(airport, score);
-    detect_and_handle_collisions(airport, score);
-}
-
-fn render(airport: &Airport, score: &Score) {
-    // Draw the airport map to the screen
-    let mut stdout = stdout();
-    // Clear the screen
-    stdout.write_all(b"\x1B[2J").unwrap();
-    // Move the cursor to the beginning of the terminal
-    stdout.write_all(b"\x1B[1;1H").unwrap();
 
-    // Print the dashboard
-    let weather = format!("{:?}", airport.weather.condition);
-    stdout
-        .write_all(
-            format!(
-                "Takeoffs: {:<5} Weather: {:<20} Wind Direction: {}'   Wind Speed: {:.2} kn\n",
-                score.takeoff, weather, airport.weather.wind_direction, airport.weather.wind_speed
-            )
-            .as_bytes(),
-        )
-        .unwrap();
-
-    for (col_index, col) in airport.map.map.iter().enumerate() {
-        for (row_index, row) in col.iter().enumerate() {
-            // check if plane is at this point
-            let mut plane_rendered = false;
-            for plane in airport.planes.iter() {
-                if plane.position.0 == col_index
-                    && plane.position.1 == row_index
-                    && !plane.out_of_map
-                {
-                    let dir: Direction = match row {
-                        MapPoint::GateTaxiLine((_, dir))
-                        | MapPoint::Runway((_, dir))
-                        | MapPoint::Taxiway((_, dir)) => dir.clone(),
-                        MapPoint::Gate(gate) => {
-                            let point = row.clone();
-                            point
-                                .check_for_gate_taxi_line_all_directions(
-                                    &airport.map,
-                                    (col_index, row_index),
-                                    gate.to_string(),
-                                    true,
-                                )
-                                .1
-                                .get_opposite_dir()
-                        }
-                        MapPoint::Empty => plane.runway.side.clone(),
-                    };
-                    match dir {
-                        Direction::North => stdout.write_all("▲".as_bytes()).unwrap(),
-                        Direction::South => stdout.write_all("▼".as_bytes()).unwrap(),
-                        Direction::East => stdout.write_all("▶".as_bytes()).unwrap(),
-                        Direction::West => stdout.write_all("◀".as_bytes()).unwrap(),
-                        _ => (),
-                    }
-                    plane_rendered = true;
-                }
-            }
-            if plane_rendered {
-                continue;
-            }
-            let pixel = match row {
-                MapPoint::Empty => " ",
-                MapPoint::Runway((usize, dir)) => match usize {
-                    0 => "∥",
-                    _ => match dir {
-                        Direction::North | Direction::South => "∥",
-                        Direction::East | Direction::West => "=",
-                        _ => " ",
-                    },
-                },
-                MapPoint::Taxiway((_, dir)) => match dir {
-                    Direction::North => "^",
-                    Direction::South => "v",
-                    Direction::East => ">",
-                    Direction::West => "<",
-                    _ => " ",
-                },
-                MapPoint::Gate(name) => name,
-                MapPoint::GateTaxiLine((_, dir)) => match dir {
-                    Direction::North => "↑",
-                    Direction::South => "↓",
-                    Direction::East => "→",
-                    Direction::West => "←",
-                    _ => " ",
-                },
-            };
-            stdout.write_all(pixel.as_bytes()).unwrap();
+    // Each runway is a single strip, so all its tiles should join up, if need be across
+    // the tiles of runways that cross it
+    let mut runways = tiles
+        .iter()
+        .filter_map(|(_, point)| match point {
+            MapPoint::Runway((name, _)) => Some(*name),
+            _ => None,
+        })
+        .collect::<Vec<usize>>();
+    runways.sort();
+    runways.dedup();
+    for name in runways {
+        let strip = runway_tiles(Some(name));
+        let joined = connected_tiles(map, vec![strip[0]], |point| {
+            matches!(point, MapPoint::Runway(_))
+        });
+        if strip.iter().any(|position| !joined.contains(position)) {
+            problems.push(format!("Runway {} is used for more than one strip", name));
         }
-        stdout.write_all(b"\r\n").unwrap();
-    }
-    // Print out the plane information in a table format on the terminal
-    stdout.write_all(b"Planes\r\n").unwrap();
-    let header = format!(
-        "{}\t{}\t{}\t{:<30}{}\n",
-        "ID", "Name", "Runway", "Airlines", "Status"
-    );
-    stdout.write_all(header.as_bytes()).unwrap();
-    for plane in airport.planes.iter().filter(|p| !p.out_of_map) {
-        let airline = AIRWAY_IDS.get(plane.name.get(..2).unwrap()).unwrap();
-        let info = format!(
-            "{}\t{}\t{}\t{:<30}{:?}\n",
-            plane.id, plane.name, plane.runway.name, airline, plane.current_action
-        );
-        stdout.write_all(info.as_bytes()).unwrap();
     }
-    stdout.write_all(b"\r\n\n").unwrap();
 
-    // Print out the latest error message
-    if let Ok(error) = ERROR.lock() {
-        if error.timer.load(Ordering::SeqCst) > 0 {
-            stdout
-                .write_all(format!("‼  {}", error.message).as_bytes())
-                .unwrap();
-            error.timer.fetch_sub(1, Ordering::SeqCst);
-            stdout.write_all(b"\r\n").unwrap();
+    let mut gates: Vec<(&String, (usize, usize))> = tiles
+        .iter()
+        .filter_map(|(position, point)| match point {
+            MapPoint::Gate(number) => Some((number, *position)),
+            _ => None,
+        })
+        .collect();
+    gates.sort();
+    for pair in gates.windows(2) {
+        if pair[0].0 == pair[1].0 {
+            problems.push(format!("Gate {} appears more than once", pair[0].0));
         }
     }
 
-    // Print out the latest clearance message
-    if let Ok(clearance) = ATC.lock() {
-        if clearance.timer.load(Ordering::SeqCst) > 0 {
-            stdout
-                .write_all(format!("🎙  {}", clearance.message).as_bytes())
-                .unwrap();
-            clearance.timer.fetch_sub(1, Ordering::SeqCst);
-            stdout.write_all(b"\r\n").unwrap();
+    // Everything a plane can reach by taxiing off a runway
+    let reachable = connected_tiles(map, runway_tiles(None), |point| *point != MapPoint::Empty);
+    for (number, position) in gates.iter() {
+        if !reachable.contains(position) {
+            problems.push(format!("Gate {} cannot be reached from a runway", number));
+        }
+    }
+    let mut taxiways: Vec<(&String, bool)> = vec![];
+    for (position, point) in tiles.iter() {
+        if let MapPoint::Taxiway((name, _)) = point {
+            let connected = reachable.contains(position);
+            match taxiways.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, any)) => *any = *any || connected,
+                None => taxiways.push((name, connected)),
+            }
         }
     }
+    taxiways.sort();
+    for (name, _) in taxiways.iter().filter(|(_, connected)| !connected) {
+        problems.push(format!("Taxiway {} is not connected to a runway", name));
+    }
+    problems
+}
 
-    // Print out the message from Airport Operations Center (AOC) if any
-    if let Ok(aoc) = AOC.lock() {
-        if aoc.message.len() > 0 {
-            stdout
-                .write_all(format!("\n{}", aoc.message).as_bytes())
-                .unwrap();
-            stdout.write_all(b"\r\n").unwrap();
+// Mean and standard deviation of the speed arrivals enter the map at
+const ARRIVAL_SPEED_KT: (f64, f64) = (170.0, 15.0);
+// Speed arrivals slow down to on final approach
+const APPROACH_SPEED_KT: f64 = 140.0;
+// Speed window an arrival must be within at the stabilisation gate
+const STABLE_SPEED_KT: (f64, f64) = (130.0, 150.0);
+
+// Direction of the de-icing pad next to the position, if there is one
+fn adjacent_deice_pad(map: &Map, position: (usize, usize), pad: &str) -> Option<Direction> {
+    [
+        Direction::North,
+        Direction::South,
+        Direction::East,
+        Direction::West,
+    ]
+    .into_iter()
+    .find(|dir| dir.clone().fetch_mappoint(map, position) == MapPoint::DeicePad(pad.to_string()))
+}
+
+// Whether following the taxiway from the position passes the de-icing pad
+fn deice_pad_ahead(map: &Map, position: (usize, usize), pad: &str) -> bool {
+    let mut position = position;
+    while adjacent_deice_pad(map, position, pad).is_none() {
+        match &map.map[position.0][position.1] {
+            MapPoint::Taxiway((_, dir)) => match dir.clone().go(map, position) {
+                Some(next) => position = next,
+                None => return false,
+            },
+            _ => return false,
         }
     }
+    true
+}
 
-    // Flush the output buffer to ensure that the output is immediately displayed
-    stdout.flush().unwrap();
+// Speed an arrival can shed or gain per tile flown
+const SPEED_CHANGE_KT_PER_TILE: f64 = 3.0;
+// Tiles a landing plane rolls past the threshold before it can slow enough to exit in snow
+const SNOW_ROLLOUT_TILES: usize = 10;
+
+// Tiles left to fly before reaching the runway threshold
+fn distance_to_threshold(map: &Map, position: (usize, usize), runway: &Runway) -> Option<usize> {
+    // Measured along the runway, so planes still joining the centreline are counted too
+    let threshold = runway_threshold(map, runway)?;
+    match runway.side {
+        Direction::East => threshold.1.checked_sub(position.1),
+        Direction::West => position.1.checked_sub(threshold.1),
+        Direction::South => threshold.0.checked_sub(position.0),
+        Direction::North => position.0.checked_sub(threshold.0),
+        Direction::StayPut => None,
+    }
 }
 
-fn update_aircraft_from_user_input(
-    airport: &mut Airport,
-    receiver: &Receiver<String>,
-    tts: &mut Tts,
-) {
-    if let Ok(user_input) = receiver.try_recv() {
-        let plane = parse_user_input(
-            user_input,
-            &airport.planes,
-            &airport.runways,
-            &airport.weather,
+// One step from a row or column towards another
+fn step_towards(from: usize, to: usize) -> usize {
+    match from.cmp(&to) {
+        std::cmp::Ordering::Less => from + 1,
+        std::cmp::Ordering::Greater => from - 1,
+        std::cmp::Ordering::Equal => from,
+    }
+}
+
+// Altitude on the glide path for a plane flying towards the runway threshold
+fn glide_path_altitude(map: &Map, position: (usize, usize), runway: &Runway) -> f64 {
+    distance_to_threshold(map, position, runway).unwrap_or(0) as f64 * GLIDE_SLOPE_FT_PER_TILE
+}
+
+// A runway without a direction cannot be flown or rolled along, so the plane is held instead
+fn hold_for_runway_direction(plane: &mut Plane) {
+    plane.current_action = Action::HoldPosition;
+    if let Ok(mut error) = ERROR.lock() {
+        error.message = format!(
+            "{} is holding, runway {} has no direction on the map",
+            plane.name, plane.runway.name
         );
-        if plane.is_ok() {
-            let keep_aside_fleet = airport.planes.clone();
-            let plane = plane.unwrap();
-            airport.planes = vec![plane.clone()];
-            update_aircraft_position(airport);
-            // Restore the fleet but replace the plane that was changed
-            airport.planes = keep_aside_fleet
-                .iter()
-                .map(|p| {
-                    if p.id == airport.planes[0].id {
-                        airport.planes[0].to_owned()
-                    } else {
-                        p.to_owned()
-                    }
-                })
-                .collect::<Vec<Plane>>();
+        error.timer = AtomicUsize::new(5);
+    }
+}
 
-            // Get the clearance message
-            let clearance = create_atc_clearance(&airport, &plane);
-            tts.speak(clearance.clone(), false)
-                .expect("Could not speak ATC clearance");
-            #[cfg(target_os = "macos")]
-            {
-                let run_loop: id = unsafe { NSRunLoop::currentRunLoop() };
-                unsafe {
-                    let date: id = msg_send![class!(NSDate), distantFuture];
-                    let _: () = msg_send![run_loop, runMode:NSDefaultRunLoopMode beforeDate:date];
-                }
-            }
-            if let Ok(mut atc) = ATC.lock() {
-                atc.message = clearance;
-                atc.timer = AtomicUsize::new(5);
+// Abandons the approach; the plane climbs out and comes back around
+fn go_around(messages: &HashMap<String, String>, plane: &mut Plane, reason: String) {
+    plane.current_action = Action::GoAround;
+    // The next approach needs a clearance of its own
+    plane.cleared_to_land = false;
+    plane.on_final = false;
+    if let Ok(mut error) = ERROR.lock() {
+        error.message = fill_message(
+            messages,
+            "going_around",
+            &[("flight", &plane.name), ("reason", &reason)],
+        );
+        error.timer = AtomicUsize::new(5);
+    }
+    plane.go_around_reason = Some(reason);
+}
+
+lazy_static! {
+    static ref AIRWAY_IDS: HashMap<&'static str, &'static str> = {
+        let mut map = HashMap::new();
+        map.insert("AA", "American Airlines");
+        map.insert("DL", "Delta Air Lines");
+        map.insert("UA", "United Airlines");
+        map.insert("BA", "British Airways");
+        map.insert("AF", "Air France");
+        map.insert("LH", "Lufthansa");
+        map.insert("EK", "Emirates");
+        map.insert("QF", "Qantas");
+        map.insert("AS", "Alaska Airlines");
+        map.insert("WN", "Southwest Airlines");
+        map.insert("AI", "Air India");
+        map
+    };
+}
+// Flight numbers of airlines without a configured range
+const DEFAULT_FLIGHT_NUMBERS: (usize, usize) = (100, 399);
+// Random names tried for a new plane before looking through every flight number
+const NAME_ATTEMPTS: usize = 20;
+// Terminal colour used to draw each airline's planes
+lazy_static! {
+    static ref AIRLINE_COLORS: HashMap<&'static str, &'static str> = {
+        let mut map = HashMap::new();
+        map.insert("AA", "\x1B[31m");
+        map.insert("DL", "\x1B[32m");
+        map.insert("UA", "\x1B[33m");
+        map.insert("BA", "\x1B[34m");
+        map.insert("AF", "\x1B[35m");
+        map.insert("LH", "\x1B[36m");
+        map.insert("EK", "\x1B[91m");
+        map.insert("QF", "\x1B[92m");
+        map.insert("AS", "\x1B[93m");
+        map.insert("WN", "\x1B[94m");
+        map.insert("AI", "\x1B[95m");
+        map
+    };
+}
+const DEFAULT_COLOR: &str = "\x1B[39m";
+const RESET_COLOR: &str = "\x1B[0m";
+// Severity colours for the weather on the dashboard
+const GREEN_COLOR: &str = "\x1B[32m";
+const YELLOW_COLOR: &str = "\x1B[33m";
+const RED_COLOR: &str = "\x1B[31m";
+const CYAN_COLOR: &str = "\x1B[36m";
+const BOLD_RED_COLOR: &str = "\x1B[1;31m";
+
+// Colour of the airline flying the plane, from the first two letters of its name
+fn airline_color(name: &str) -> &'static str {
+    name.get(..2)
+        .and_then(|code| AIRLINE_COLORS.get(code))
+        .unwrap_or(&DEFAULT_COLOR)
+}
+
+// A plane's glyph as drawn on the map, in its airline's colour
+fn plane_cell(plane: &Plane) -> String {
+    format!(
+        "{}{}{}",
+        airline_color(&plane.name),
+        plane.heading.plane_glyph(),
+        RESET_COLOR
+    )
+}
+
+#[derive(Debug, Clone)]
+struct Airport {
+    runways: HashMap<String, Runway>,
+    gates: HashMap<String, Gate>,
+    map: Map,
+    map_path: String,
+    weather: Weather,
+    planes: Vec<Plane>,
+    vehicles: Vec<GroundVehicle>,
+    next_arrival_tick: usize,
+    landing_interval: usize,
+    arrival_jitter: usize,
+    next_render_tick: usize,
+    render_interval: usize,
+    last_weather_tick: Option<usize>,
+    weather_interval: usize,
+    weather_transitions: Vec<WeatherTransition>,
+    tick_ms: u64,
+    plane_sort: PlaneSort,
+    layout: Layout,
+    units: Units,
+    phraseology: Phraseology,
+    flight_types: Vec<FlightType>,
+    corridor_width: usize,
+    aliases: HashMap<String, String>,
+    flight_numbers: HashMap<String, (usize, usize)>,
+    reserved_names: HashSet<String>,
+    gate_runways: HashMap<String, String>,
+    departure_runways: DepartureRunways,
+    auto_gate: bool,
+    stable_gate: usize,
+    final_fix: Option<usize>,
+    stuck_after: usize,
+    hold_short_timeout: Option<usize>,
+    hold_stuck: bool,
+    crossing_clearance: bool,
+    transcript: Option<String>,
+    render_to: Option<String>,
+    speak_errors: bool,
+    debug: bool,
+    alerts: bool,
+    messages: HashMap<String, String>, // Wording of each message by id, see DEFAULT_MESSAGES
+    detect_swaps: bool,
+    last_positions: HashMap<usize, (usize, usize)>, // Plane positions at the last collision check
+    last_actions: HashMap<usize, Action>,           // Plane actions at the last request check
+    requests: Vec<PlaneRequest>,
+    readback_ratio: f64,
+    pending_readbacks: Vec<PendingReadback>, // Clearances held until read back
+    separation_losses: Vec<(usize, usize)>,  // Pairs of planes currently too close
+    conflict_horizon: Option<usize>,         // Ticks ahead to look for collisions
+    predicted_conflicts: Vec<(usize, usize)>, // Pairs of planes heading for a collision
+    arrival_rates: ArrivalRates,
+    arr_dep_ratio: f64,
+    heavy_ratio: f64,
+    medevac_ratio: f64,
+    vip_ratio: f64,
+    wind_limit: f64,
+    crosswind_limits: HashMap<String, f64>,
+    tailwind_limits: HashMap<String, f64>,
+    tick: usize,
+    quiet_hours: Vec<TickRange>,
+    closed_runways: Vec<usize>,
+    runway_intersections: Vec<(usize, usize)>, // Pairs of runways that cross each other
+    closed_taxiways: Vec<String>,
+    turnarounds: DurationStats, // Ticks from reaching the gate to pushing back
+    runway_occupancy: HashMap<usize, DurationStats>, // Ticks takeoffs and landings held each runway
+    throughput: Throughput,
+    missed_approaches: usize, // Go-arounds from the runway threshold
+    holding_capacity: Option<usize>,
+    max_airborne: Option<usize>,
+    diversions: usize,
+    arrival_spacing: Option<usize>,
+    last_arrival_ticks: HashMap<usize, usize>, // Per runway, tick of the last landing clearance
+    screen: Frame,                             // What was drawn on the terminal by the last render
+    path_preview: Vec<(usize, usize)>,         // Tiles of the latest clearance's path
+    path_preview_until: usize,                 // Tick the path stops being highlighted
+}
+
+impl Airport {
+    // Runway planes use unless told otherwise: runway 1, or on maps without it the lowest
+    // numbered runway planes can be given
+    pub fn default_runway(&self) -> Option<Runway> {
+        let mut runways = self
+            .runways
+            .values()
+            .filter(|runway| runway.is_usable())
+            .collect::<Vec<&Runway>>();
+        runways.sort_by_key(|runway| (runway.name != 1, runway.name));
+        runways.first().cloned().cloned()
+    }
+
+    // Runway departures from the gate are expected to use, the default runway unless
+    // configured otherwise
+    pub fn departure_runway(&self, gate: &str) -> Runway {
+        match self.gate_runways.get(gate) {
+            Some(runway) => self.runways[runway].clone(),
+            None => self
+                .default_runway()
+                .expect("The map has no runways planes can use"),
+        }
+    }
+
+    // Runway a departure from the gate files for, which its pushback clearance names
+    pub fn filed_runway(&self, gate: &str) -> Runway {
+        let open = self
+            .runways
+            .values()
+            .filter(|runway| runway.is_usable())
+            .filter(|runway| !self.closed_runways.contains(&runway.name))
+            .collect::<Vec<&Runway>>();
+        let filed = match (
+            &self.departure_runways,
+            self.gate_runways.contains_key(gate),
+        ) {
+            (DepartureRunways::Random, false) => {
+                open.choose(&mut rand::thread_rng()).cloned().cloned()
             }
-        } else if plane.is_err() {
-            if let Ok(mut error) = ERROR.lock() {
-                error.message = plane.err().unwrap();
-                error.timer = AtomicUsize::new(5);
+            (DepartureRunways::Favoured, false) => self.favoured_runway(),
+            _ => None,
+        };
+        filed.unwrap_or_else(|| self.departure_runway(gate))
+    }
+
+    // Whether a plane is on the runway, or taking off from or landing on it
+    pub fn runway_occupied(&self, runway: usize) -> bool {
+        self.planes.iter().filter(|p| !p.out_of_map).any(|p| {
+            let point = self
+                .map
+                .map
+                .get(p.position.0)
+                .and_then(|row| row.get(p.position.1));
+            let on_runway = p.altitude_ft == 0.0
+                && matches!(point, Some(MapPoint::Runway((name, _))) if *name == runway);
+            let using = matches!(p.current_action, Action::Land(_) | Action::Takeoff);
+            on_runway || (using && p.runway.name == runway)
+        })
+    }
+
+    // Whether a plane holding short has waited out --hold-short-timeout
+    pub fn hold_short_timed_out(&self, plane: &Plane) -> bool {
+        match (self.hold_short_timeout, plane.held_short_tick) {
+            (Some(timeout), Some(since)) => {
+                plane.current_action == Action::HoldPosition && self.tick >= since + timeout
             }
+            _ => false,
         }
     }
-}
 
-fn update_aircraft_position(airport: &mut Airport) {
-    // Update aircraft position
-    for plane in airport
-        .planes
-        .iter_mut()
-        .filter(|p| !p.out_of_map)
-        .into_iter()
-    {
-        match &mut plane.current_action {
-            Action::InAir => {
-                let plane_dir;
-                let pos = match plane.runway.side {
-                    Direction::West | Direction::East | Direction::North | Direction::South => {
-                        plane_dir = plane.runway.side.clone();
-                        plane_dir.to_owned().go(plane.position)
-                    }
-                    Direction::StayPut => todo!(),
-                };
-                plane.position = pos;
+    // Whether enough ticks have passed since the last landing clearance on the runway
+    pub fn arrival_spacing_elapsed(&self, runway: usize) -> bool {
+        match (self.arrival_spacing, self.last_arrival_ticks.get(&runway)) {
+            (Some(spacing), Some(last)) => self.tick >= last + spacing,
+            _ => true,
+        }
+    }
 
-                // Check if plane has reached the start of the runway
-                let runway_name = plane.runway.name;
-                if Direction::StayPut.fetch_mappoint(&airport.map, plane.position)
-                    == MapPoint::Runway((runway_name, plane_dir))
-                {
-                    plane.current_action = Action::Land;
-                }
+    // Open, unoccupied runway with the most headwind, which new arrivals are sent to
+    pub fn favoured_runway(&self) -> Option<Runway> {
+        let mut runways = self
+            .runways
+            .values()
+            .filter(|runway| runway.is_usable())
+            .filter(|runway| !self.closed_runways.contains(&runway.name))
+            .filter(|runway| !self.runway_occupied(runway.name))
+            .collect::<Vec<&Runway>>();
+        // Ties go to the lowest numbered runway
+        runways.sort_by_key(|runway| runway.name);
+        runways
+            .into_iter()
+            .min_by(|a, b| {
+                let (a, b) = (a.headwind(&self.weather), b.headwind(&self.weather));
+                b.partial_cmp(&a).unwrap()
+            })
+            .cloned()
+    }
+
+    // Replaces an aliased keyword at the start of a command with the keyword it stands for.
+    // Keywords are matched in any case and the command is left single spaced.
+    pub fn expand_alias(&self, command: &str) -> String {
+        let mut tokens = command
+            .split_whitespace()
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        if let Some(first) = tokens.first_mut() {
+            *first = first.to_lowercase();
+            if let Some(keyword) = self.aliases.get(first) {
+                *first = keyword.clone();
             }
-            Action::Land => {
-                let pos = match plane.runway.side {
-                    Direction::West | Direction::East | Direction::North | Direction::South => {
-                        let plane_dir = plane.runway.side.clone();
-                        // Check if plane has a nearby taxiway
-                        let (nearby_taxiway, taxiway_dir) = plane_dir
-                            .to_owned()
-                            .fetch_mappoint(&airport.map, plane.position)
-                            .to_owned()
-                            .check_for_taxiway(&airport.map, plane.position);
-                        let mut pos = plane_dir.to_owned().go(plane.position);
-                        if nearby_taxiway {
-                            // Only stop if the direction is outward facing
-                            // i.e. if we take that direction, and follow the path at that point,
-                            // we should not end up on a runway
-                            let mut outward_facing = false;
-                            let potential_map_point = taxiway_dir
-                                .to_owned()
-                                .fetch_mappoint(&airport.map, plane.position);
-                            let potential_point = taxiway_dir.go(plane.position);
-                            if let MapPoint::Taxiway((_, dir)) = potential_map_point {
-                                if let MapPoint::Runway(_) =
-                                    dir.fetch_mappoint(&airport.map, potential_point)
-                                {
-                                    outward_facing = true;
-                                }
-                            }
-                            if !outward_facing {
-                                pos = potential_point;
-                                plane.current_action = Action::HoldPosition;
-                            }
-                        }
-                        // Check if plane has reached the end of the runway
-                        if plane_dir.fetch_mappoint(&airport.map, pos) == MapPoint::Empty {
-                            plane.current_action = Action::HoldPosition;
-                        }
-                        pos
-                    }
-                    Direction::StayPut => todo!(),
-                };
-                plane.position = pos;
+        }
+        tokens.join(" ")
+    }
+
+    // Name for a new plane of a random airline, within that airline's flight numbers, not
+    // used by any active plane and not reserved
+    pub fn unique_plane_name(&self) -> Option<String> {
+        let mut rng = rand::thread_rng();
+        let taken = self
+            .planes
+            .iter()
+            .filter(|plane| !plane.out_of_map)
+            .map(|plane| plane.name.as_str())
+            .collect::<HashSet<&str>>();
+        let is_free =
+            |name: &String| !self.reserved_names.contains(name) && !taken.contains(name.as_str());
+        let numbers = |airline: &str| {
+            self.flight_numbers
+                .get(airline)
+                .copied()
+                .unwrap_or(DEFAULT_FLIGHT_NUMBERS)
+        };
+        let mut airlines = AIRWAY_IDS.keys().cloned().collect::<Vec<&str>>();
+        // With most names free, a few random picks find one
+        for _ in 0..NAME_ATTEMPTS {
+            let airline = airlines.choose(&mut rng).unwrap();
+            let (first, last) = numbers(airline);
+            let name = format!("{}{}", airline, rng.gen_range(first..=last));
+            if is_free(&name) {
+                return Some(name);
             }
-            Action::TaxiToGate(gate) => {
-                // Check if the plane is standing at the end of the runway
-                if airport.map.map[plane.position.0][plane.position.1]
-                    .clone()
-                    .check_if_runway()
-                    && plane
-                        .runway
-                        .side
-                        .clone()
-                        .fetch_mappoint(&airport.map, plane.position)
-                        == MapPoint::Empty
-                {
-                    // Change position from runway to taxiway
-                    let point = airport.map.map[plane.position.0][plane.position.1].clone();
-                    let taxiway_dir = match point {
-                        MapPoint::Runway((_, dir)) => dir,
-                        _ => panic!("Plane is not standing on a runway"),
-                    };
-                    plane.position = taxiway_dir.go(plane.position);
-                    continue;
-                }
-                // Check if there is a GateTaxiLine in any direction surrounding the current direction
-                let (is_nearby_gate, gate_dir) = airport.map.map[plane.position.0]
-                    [plane.position.1]
-                    .clone()
-                    .check_for_gate_taxi_line_all_directions(
-                        &airport.map,
-                        plane.position,
-                        gate.to_string(),
-                        false,
-                    );
+        }
+        // Otherwise look through every name there is
+        airlines.shuffle(&mut rng);
+        airlines.iter().find_map(|airline| {
+            let (first, last) = numbers(airline);
+            let free = (first..=last)
+                .map(|number| format!("{}{}", airline, number))
+                .filter(is_free)
+                .collect::<Vec<String>>();
+            free.choose(&mut rng).cloned()
+        })
+    }
 
-                if is_nearby_gate {
-                    plane.position = gate_dir.go(plane.position);
-                }
-                // Traverse along the taxiway/gate line
-                else {
-                    let point = airport.map.map[plane.position.0][plane.position.1].clone();
-                    let dir = match point {
-                        MapPoint::Taxiway((_, dir)) => dir,
-                        MapPoint::GateTaxiLine((_, dir)) => dir,
-                        MapPoint::Gate(_) => {
-                            // Gate is now occupied
-                            let at = airport.gates.get_mut(gate).expect("Gate not found");
-                            at.is_occupied = true;
-                            // Change action to AtGate with wait time 0
-                            plane.current_action =
-                                Action::AtGate((gate.clone(), AtGateAction::ShutdownProcedure));
-                            Direction::StayPut
-                        }
-                        MapPoint::Runway((_, dir)) => dir,
-                        _ => panic!("Plane is not standing on a taxiway or correct gate"),
-                    };
-                    plane.position = dir.go(plane.position);
-                }
+    // Sends a tug to push back a plane waiting at its gate
+    pub fn dispatch_tug(&mut self, aircraft: &str) -> Result<String, Rejection> {
+        let plane = self
+            .planes
+            .iter()
+            .find(|plane| !plane.out_of_map && plane.name.to_lowercase() == aircraft.to_lowercase())
+            .ok_or_else(|| {
+                Rejection::new(
+                    RejectCode::UnknownAircraft,
+                    self.message("plane_not_found", &[]),
+                )
+            })?;
+        if !matches!(plane.current_action, Action::AtGate(_)) {
+            return Err("A tug can only be sent to a plane at its gate".into());
+        }
+        if self.vehicles.iter().any(|v| v.assisting == Some(plane.id)) {
+            return Err(format!("{} already has a tug", plane.name).into());
+        }
+        let notice = format!("Tug dispatched to {}.", plane.name);
+        let vehicle = GroundVehicle {
+            id: self.vehicles.iter().map(|v| v.id).max().unwrap_or(0) + 1,
+            kind: GroundVehicleKind::Tug,
+            position: plane.position,
+            assisting: Some(plane.id),
+        };
+        self.vehicles.push(vehicle);
+        Ok(notice)
+    }
+
+    // Checks a command for a plane and holds it back until read back, replacing any clearance
+    // the plane is already waiting on. Returns the clearance as given.
+    pub fn hold_for_readback(&mut self, command: &str) -> Result<String, Rejection> {
+        let plane = parse_user_input(command.to_string(), self)?;
+        let command = command.split_whitespace().collect::<Vec<&str>>().join(" ");
+        self.pending_readbacks.retain(|p| p.plane != plane.id);
+        self.requests.retain(|r| r.plane != plane.id);
+        self.requests.push(PlaneRequest {
+            plane: plane.id,
+            action: self
+                .planes
+                .iter()
+                .find(|p| p.id == plane.id)
+                .map_or(Action::HoldPosition, |p| p.current_action.clone()),
+            message: format!("{}, awaiting readback of \"{}\".", plane.name, command),
+        });
+        self.pending_readbacks.push(PendingReadback {
+            plane: plane.id,
+            command,
+            tick: self.tick,
+        });
+        Ok(create_atc_clearance(self, &plane))
+    }
+
+    // Takes the clearance read back back off hold, if it matches one word for word
+    pub fn read_back(&mut self, readback: &str) -> Result<String, Rejection> {
+        let words = |command: &str| {
+            command
+                .split_whitespace()
+                .map(|word| word.to_lowercase())
+                .collect::<Vec<String>>()
+        };
+        let index = self
+            .pending_readbacks
+            .iter()
+            .position(|p| words(&p.command) == words(readback))
+            .ok_or("Readback does not match a clearance awaiting one")?;
+        let readback = self.pending_readbacks.remove(index);
+        self.requests.retain(|r| r.plane != readback.plane);
+        Ok(readback.command)
+    }
+
+    // Changes how urgently a plane is sequenced for landing and takeoff
+    pub fn set_priority(&mut self, aircraft: &str, level: &str) -> Result<String, Rejection> {
+        let priority = level
+            .parse::<Priority>()
+            .map_err(|e| Rejection::new(RejectCode::Syntax, e))?;
+        let not_found = Rejection::new(
+            RejectCode::UnknownAircraft,
+            self.message("plane_not_found", &[]),
+        );
+        let plane = self
+            .planes
+            .iter_mut()
+            .find(|plane| !plane.out_of_map && plane.name.to_lowercase() == aircraft.to_lowercase())
+            .ok_or(not_found)?;
+        plane.priority = priority;
+        Ok(format!(
+            "{} now has {} priority.",
+            plane.name,
+            plane.priority.name()
+        ))
+    }
+
+    // A message in the wording of the airport's bundle, with its placeholders filled in
+    pub fn message(&self, id: &str, values: &[(&str, &str)]) -> String {
+        fill_message(&self.messages, id, values)
+    }
+
+    // Debug command setting the weather at once, rather than waiting for it to change
+    pub fn force_weather(&mut self, condition: &str) -> Result<String, Rejection> {
+        if !self.debug {
+            return Err("Debug commands are off, start with --debug".into());
+        }
+        let condition = condition
+            .parse::<WeatherCondition>()
+            .map_err(|e| Rejection::new(RejectCode::Syntax, e))?;
+        change_weather(self, condition);
+        Ok(format!("Weather set to {:?}.", self.weather.condition))
+    }
+
+    // Debug command reading the map file again, so changes to it show without a restart.
+    // Planes on the ground stay if they still stand where their clearance needs them, and
+    // planes in the air if their runway is still there. A map that could not be played, or
+    // that --gate-runway no longer fits, is turned down and the running one kept.
+    pub fn reload_map(&mut self) -> Result<String, Rejection> {
+        if !self.debug {
+            return Err("Debug commands are off, start with --debug".into());
+        }
+        let refuse = |reason: String| format!("Could not reload {}: {}", self.map_path, reason);
+        let map = build_airport_map(&self.map_path, self.map.spacing.clone()).map_err(refuse)?;
+        let problems = validate_map(&map);
+        if !problems.is_empty() {
+            return Err(refuse(problems.join(", ")).into());
+        }
+        let runways = Runway::new(&map);
+        if !runways.values().any(Runway::is_usable) {
+            return Err(refuse("the map has no runways planes can use".to_string()).into());
+        }
+        let gates = Gate::new(&map);
+        for (gate, runway) in self.gate_runways.iter() {
+            if !gates.contains_key(gate) || !runways.get(runway).is_some_and(Runway::is_usable) {
+                return Err(refuse(format!(
+                    "--gate-runway {}={} no longer fits the map",
+                    gate, runway
+                ))
+                .into());
             }
-            Action::Takeoff => {
-                // Check if the plane is out of the map
-                if plane.position.0 <= 1
-                    || plane.position.0 >= airport.map.map.len() - 1 as usize
-                    || plane.position.1 <= 1
-                    || plane.position.1 >= airport.map.map[0].len() - 1 as usize
+        }
+        self.map = map;
+        self.runways = runways;
+        self.gates = gates;
+        self.runway_intersections = runway_intersections(&self.map);
+
+        let fallback = self.default_runway();
+        let (map, runways, gates) = (&self.map, &self.runways, &mut self.gates);
+        let point_at = |(row, col): (usize, usize)| {
+            map.map
+                .get(row)
+                .and_then(|points| points.get(col))
+                .cloned()
+                .unwrap_or(MapPoint::Empty)
+        };
+        let before = self.planes.len();
+        self.planes.retain_mut(|plane| {
+            let runway = runways.get(&plane.runway.name.to_string()).cloned();
+            let point = point_at(plane.position);
+            // Planes that have left are kept, so that they still add up in the summary
+            let runway = match &plane.current_action {
+                _ if plane.out_of_map => runway.or(fallback.clone()),
+                Action::InAir | Action::GoAround => runway,
+                Action::AtGate((gate, _)) if point != MapPoint::Gate(gate.clone()) => None,
+                Action::TaxiToGate(gate) if !gates.contains_key(gate) => None,
+                Action::Pushback
+                    if !matches!(point, MapPoint::Gate(_) | MapPoint::GateTaxiLine(_)) =>
                 {
-                    plane.out_of_map = true;
-                    continue;
+                    None
                 }
-
-                let point = airport.map.map[plane.position.0][plane.position.1].clone();
-                match point {
-                    MapPoint::Runway((_, _)) | MapPoint::Empty => {
-                        plane.position = plane.runway.side.clone().go(plane.position)
+                _ if point == MapPoint::Empty => None,
+                _ => runway.or(fallback.clone()),
+            };
+            match runway {
+                Some(runway) => {
+                    plane.runway = runway;
+                    if plane
+                        .cleared_to_cross
+                        .is_some_and(|name| !runways.contains_key(&name.to_string()))
+                    {
+                        plane.cleared_to_cross = None;
                     }
-                    _ => panic!("Plane is not standing on a runway"),
+                    true
                 }
+                None => false,
             }
-            Action::HoldPosition => {}
-            Action::TaxiOntoRunway(_) => {
-                let point = airport.map.map[plane.position.0][plane.position.1].clone();
-                match point {
-                    MapPoint::Taxiway((_, dir)) => plane.position = dir.go(plane.position),
-                    MapPoint::Runway((name, dir)) => match name {
-                        0 => plane.current_action = Action::TaxiOntoRunway(name),
-                        _ => plane.position = dir.go(plane.position),
-                    },
-                    _ => panic!("Plane is not standing on a taxiway or runway"),
+        });
+        for plane in self.planes.iter().filter(|plane| !plane.out_of_map) {
+            if let Action::AtGate((gate, _)) = &plane.current_action {
+                if let Some(gate) = gates.get_mut(gate) {
+                    gate.is_occupied = true;
                 }
             }
-            Action::HoldShort => {
-                let point = airport.map.map[plane.position.0][plane.position.1].clone();
-                match point {
-                    MapPoint::Taxiway((_, dir)) => {
-                        match dir
-                            .to_owned()
-                            .fetch_mappoint(&airport.map, plane.position)
-                            .check_if_runway()
-                        {
-                            true => plane.current_action = Action::HoldPosition,
-                            false => plane.position = dir.go(plane.position),
-                        }
-                    }
-                    _ => panic!("Plane is not standing on a taxiway"),
+        }
+        self.closed_runways
+            .retain(|name| runways.contains_key(&name.to_string()));
+        self.closed_taxiways
+            .retain(|taxiway| map.has_taxiway(taxiway));
+        self.last_arrival_ticks
+            .retain(|name, _| runways.contains_key(&name.to_string()));
+        let planes = &self.planes;
+        self.vehicles.retain(|vehicle| {
+            point_at(vehicle.position) != MapPoint::Empty
+                && vehicle
+                    .assisting
+                    .is_none_or(|id| planes.iter().any(|plane| plane.id == id))
+        });
+        self.requests
+            .retain(|request| planes.iter().any(|plane| plane.id == request.plane));
+        self.pending_readbacks
+            .retain(|readback| planes.iter().any(|plane| plane.id == readback.plane));
+        // The map may have changed shape, so draw all of it again
+        self.screen = vec![];
+        Ok(format!(
+            "Reloaded {}, {} plane(s) removed.",
+            self.map_path,
+            before - self.planes.len()
+        ))
+    }
+
+    // Clears a taxiing plane to cross a runway
+    pub fn clear_to_cross(&mut self, aircraft: &str, runway: &str) -> Result<String, Rejection> {
+        let runway = self
+            .runways
+            .get(runway)
+            .ok_or_else(|| {
+                Rejection::new(
+                    RejectCode::UnknownTarget,
+                    self.message("runway_not_found", &[]),
+                )
+            })?
+            .clone();
+        let not_found = Rejection::new(
+            RejectCode::UnknownAircraft,
+            self.message("plane_not_found", &[]),
+        );
+        let plane = self
+            .planes
+            .iter_mut()
+            .find(|plane| !plane.out_of_map && plane.name.to_lowercase() == aircraft.to_lowercase())
+            .ok_or(not_found)?;
+        plane.cleared_to_cross = Some(runway.name);
+        Ok(format!(
+            "{}, cross runway {}.",
+            plane.name,
+            runway.spoken_name()
+        ))
+    }
+
+    // Changes the ticks between arrivals, with 0 pausing them
+    pub fn set_landing_interval(&mut self, interval: &str) -> Result<String, Rejection> {
+        let interval = interval.parse::<usize>().map_err(|_| {
+            Rejection::new(
+                RejectCode::Syntax,
+                format!("Invalid landing interval: {}", interval),
+            )
+        })?;
+        self.landing_interval = interval;
+        self.next_arrival_tick = self.tick + interval;
+        match interval {
+            0 => Ok("Arrivals paused.".to_string()),
+            _ => Ok(format!("Arrivals every {} ticks.", interval)),
+        }
+    }
+
+    // Closes or reopens a runway ("1") or a taxiway ("t7" or "tA")
+    pub fn set_closed(&mut self, target: &str, closed: bool) -> Result<String, Rejection> {
+        fn update<T: PartialEq>(closures: &mut Vec<T>, name: T, closed: bool) {
+            closures.retain(|n| *n != name);
+            if closed {
+                closures.push(name);
+            }
+        }
+        let (kind, name) = match target.strip_prefix(['t', 'T']) {
+            Some(_) => {
+                let name = taxiway_name(target);
+                if !self.map.has_taxiway(&name) {
+                    return Err(Rejection::new(
+                        RejectCode::UnknownTarget,
+                        self.message("taxiway_not_found", &[]),
+                    ));
                 }
+                update(&mut self.closed_taxiways, name.clone(), closed);
+                ("Taxiway", name)
             }
-            Action::Pushback => {
-                let mut point = airport.map.map[plane.position.0][plane.position.1].clone();
-                match point {
-                    MapPoint::GateTaxiLine((_, dir)) => {
-                        plane.position = dir.get_opposite_dir().go(plane.position);
-                        point = airport.map.map[plane.position.0][plane.position.1].clone();
-                        if point.check_if_taxiway() {
-                            plane.current_action = Action::HoldPosition;
-                        }
-                    }
-                    MapPoint::Gate(ref gate) => {
-                        let (is_nearby_gate, gate_dir) =
-                            point.clone().check_for_gate_taxi_line_all_directions(
-                                &airport.map,
-                                plane.position,
-                                gate.to_string(),
-                                true,
-                            );
-                        match is_nearby_gate {
-                            true => plane.position = gate_dir.go(plane.position),
-                            false => panic!("Plane is not standing near a gate taxi line"),
-                        }
-                    }
-                    _ => panic!("Plane is not standing at a gate or gate taxi line"),
+            None => {
+                let not_found = || {
+                    Rejection::new(
+                        RejectCode::UnknownTarget,
+                        self.message("runway_not_found", &[]),
+                    )
                 };
+                let name = target.parse::<usize>().map_err(|_| not_found())?;
+                if !self.runways.contains_key(target) {
+                    return Err(not_found());
+                }
+                update(&mut self.closed_runways, name, closed);
+                ("Runway", name.to_string())
+            }
+        };
+        Ok(format!(
+            "{} {} is now {}.",
+            kind,
+            name,
+            if closed { "closed" } else { "open" }
+        ))
+    }
+
+    pub fn is_quiet_hours(&self) -> bool {
+        self.quiet_hours
+            .iter()
+            .any(|range| range.contains(self.tick))
+    }
+
+    // A runway crossing this one that another plane is taking off from or landing on
+    pub fn crossing_runway_in_use(&self, runway: usize, except: usize) -> Option<usize> {
+        self.planes
+            .iter()
+            .filter(|p| !p.out_of_map && p.id != except)
+            .filter(|p| {
+                matches!(p.current_action, Action::Takeoff | Action::Land(_))
+                    || (p.cleared_to_land && p.current_action == Action::InAir)
+            })
+            .map(|p| p.runway.name)
+            .find(|&other| {
+                self.runway_intersections
+                    .contains(&(runway.min(other), runway.max(other)))
+            })
+    }
+
+    // Gates that are neither occupied nor claimed by a plane taxiing to them
+    pub fn free_gates(&self) -> Vec<&Gate> {
+        let claimed = self
+            .planes
+            .iter()
+            .filter(|p| !p.out_of_map)
+            .filter_map(|p| match &p.current_action {
+                Action::TaxiToGate(gate) | Action::AtGate((gate, _)) => Some(gate.clone()),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+        self.gates
+            .values()
+            .filter(|gate| !gate.is_occupied && !claimed.contains(&gate.number))
+            .collect()
+    }
+
+    // Finds the closest gate that is neither occupied nor claimed by another plane
+    pub fn nearest_free_gate(&self, position: (usize, usize)) -> Option<String> {
+        self.free_gates()
+            .into_iter()
+            .min_by_key(|gate| {
+                (
+                    gate.position.0.abs_diff(position.0) + gate.position.1.abs_diff(position.1),
+                    gate.number.clone(),
+                )
+            })
+            .map(|gate| gate.number.clone())
+    }
+}
+
+struct Time {
+    step_duration: u64, // Duration in milliseconds for each game step
+}
+
+struct _GroundAlert {
+    message: String,
+}
+
+// Count and spread of durations in ticks, such as how long planes spent at the gate or
+// kept a runway occupied
+#[derive(Debug, Clone)]
+struct DurationStats {
+    count: usize,
+    total: usize,
+    min: usize,
+    max: usize,
+}
+
+impl DurationStats {
+    pub fn new() -> Self {
+        DurationStats {
+            count: 0,
+            total: 0,
+            min: usize::MAX,
+            max: 0,
+        }
+    }
+
+    pub fn record(&mut self, ticks: usize) {
+        self.count += 1;
+        self.total += ticks;
+        self.min = self.min.min(ticks);
+        self.max = self.max.max(ticks);
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            count => Some(self.total as f64 / count as f64),
+        }
+    }
+}
+
+// Ticks the departure rate on the dashboard is averaged over, five minutes of play
+const THROUGHPUT_WINDOW_TICKS: usize = 300;
+
+// Ticks of the departures within the throughput window, oldest first
+#[derive(Debug, Clone)]
+struct Throughput {
+    departures: VecDeque<usize>,
+}
+
+impl Throughput {
+    pub fn new() -> Self {
+        Throughput {
+            departures: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: usize) {
+        self.departures.push_back(tick);
+        while let Some(&oldest) = self.departures.front() {
+            match oldest + THROUGHPUT_WINDOW_TICKS <= tick {
+                true => self.departures.pop_front(),
+                false => break,
+            };
+        }
+    }
+
+    // Departures per minute over the window ending at this tick, with one tick a second
+    pub fn per_minute(&self, tick: usize) -> f64 {
+        let recent = self
+            .departures
+            .iter()
+            .filter(|&&departed| departed + THROUGHPUT_WINDOW_TICKS > tick)
+            .count();
+        recent as f64 * 60.0 / THROUGHPUT_WINDOW_TICKS as f64
+    }
+}
+
+struct Score {
+    takeoff: usize,
+    crash: usize,
+    missed_approach: usize,
+    diversion: usize,
+}
+
+impl Score {
+    pub fn _score(self) -> i32 {
+        self.takeoff as i32 - (100 * self.crash) as i32 - (10 * self.missed_approach) as i32
+    }
+}
+
+// Builds the airport from the given map, or says why the map cannot be used
+fn construct_airport_from(args: &Args, map_path: &str) -> Result<Airport, String> {
+    let spacing = Spacing {
+        top_bottom: args.spacing_tb,
+        left_right: args.spacing_lr,
+    };
+    let map = build_airport_map(map_path, spacing.clone())?;
+
+    let runways = Runway::new(&map);
+    if !runways.values().any(Runway::is_usable) {
+        return Err("The airport map has no runways planes can use".to_string());
+    }
+    let intersections = runway_intersections(&map);
+    let gates = Gate::new(&map);
+    let mut weather = Weather {
+        condition: WeatherCondition::Clear,
+        wind_direction: 360,
+        wind_speed: 0.0,
+        wind_gust: None,
+        wind: WindProfile {
+            clear: args.wind_clear.clone(),
+            rain: args.wind_rain.clone(),
+            inclement: args.wind_inclement.clone(),
+            thunderstorm: args.wind_thunderstorm.clone(),
+            snow: args.wind_snow.clone(),
+        },
+    };
+    simulate_wind_direction_and_speed(&mut weather, 100);
+
+    let weather_transitions = match &args.weather_table {
+        Some(path) => load_weather_table(path)?,
+        None => WEATHER_TRANSITIONS.to_vec(),
+    };
+
+    for (gate, runway) in &args.gate_runway {
+        if !gates.contains_key(gate) {
+            return Err(format!("Gate {} in --gate-runway not found", gate));
+        }
+        if !runways.get(runway).is_some_and(Runway::is_usable) {
+            return Err(format!("Runway {} in --gate-runway not found", runway));
+        }
+    }
+
+    let mut messages = match &args.lang {
+        Some(path) => load_messages(path)?,
+        None => default_messages(),
+    };
+    // --collision-message words the collision report, whatever the bundle says
+    if let Some(template) = &args.collision_message {
+        messages.insert("collision".to_string(), template.clone());
+    }
+
+    Ok(Airport {
+        runways,
+        gates,
+        map,
+        map_path: map_path.to_string(),
+        weather,
+        planes: vec![],
+        vehicles: vec![],
+        next_arrival_tick: 0,
+        landing_interval: LANDING_INTERVAL,
+        arrival_jitter: args.arrival_jitter,
+        next_render_tick: 0,
+        render_interval: args.render_interval,
+        last_weather_tick: None,
+        weather_interval: args.weather_interval,
+        weather_transitions,
+        tick_ms: args.tick_ms,
+        plane_sort: args.sort.clone(),
+        layout: args.layout.clone(),
+        phraseology: args.phraseology.clone(),
+        units: args.units.clone(),
+        flight_types: args.flight_types.clone(),
+        corridor_width: args.corridor_width,
+        aliases: args.alias.iter().cloned().collect(),
+        flight_numbers: args.flight_numbers.iter().cloned().collect(),
+        reserved_names: args
+            .reserve_name
+            .iter()
+            .map(|name| name.to_uppercase())
+            .collect(),
+        gate_runways: args.gate_runway.iter().cloned().collect(),
+        departure_runways: args.departure_runways.clone(),
+        auto_gate: args.auto_gate,
+        stable_gate: args.stable_gate,
+        final_fix: args.final_fix,
+        stuck_after: args.stuck_after,
+        hold_short_timeout: args.hold_short_timeout,
+        hold_stuck: args.hold_stuck,
+        crossing_clearance: args.crossing_clearance,
+        transcript: args.transcript.clone(),
+        render_to: args.render_to.clone(),
+        speak_errors: args.speak_errors,
+        debug: args.debug,
+        alerts: args.alerts,
+        messages,
+        detect_swaps: args.detect_swaps,
+        last_positions: HashMap::new(),
+        last_actions: HashMap::new(),
+        requests: vec![],
+        readback_ratio: args.readback_ratio,
+        pending_readbacks: vec![],
+        separation_losses: vec![],
+        conflict_horizon: args.predict_conflicts,
+        predicted_conflicts: vec![],
+        screen: vec![],
+        path_preview: vec![],
+        path_preview_until: 0,
+        arrival_rates: ArrivalRates {
+            clear: args.arrivals_clear,
+            rain: args.arrivals_rain,
+            inclement: args.arrivals_inclement,
+            thunderstorm: args.arrivals_thunderstorm,
+            snow: args.arrivals_snow,
+        },
+        arr_dep_ratio: args.arr_dep_ratio,
+        heavy_ratio: args.heavy_ratio,
+        medevac_ratio: args.medevac_ratio,
+        vip_ratio: args.vip_ratio,
+        wind_limit: args.wind_limit,
+        crosswind_limits: args.crosswind_limit.iter().cloned().collect(),
+        tailwind_limits: args.tailwind_limit.iter().cloned().collect(),
+        tick: 0,
+        quiet_hours: args.quiet_hours.clone(),
+        closed_runways: vec![],
+        runway_intersections: intersections,
+        closed_taxiways: vec![],
+        turnarounds: DurationStats::new(),
+        runway_occupancy: HashMap::new(),
+        throughput: Throughput::new(),
+        missed_approaches: 0,
+        holding_capacity: args.holding_capacity,
+        max_airborne: args.max_airborne,
+        diversions: 0,
+        arrival_spacing: args.arrival_spacing,
+        last_arrival_ticks: HashMap::new(),
+    })
+}
+
+fn build_airport_map(map_path: &str, spacing: Spacing) -> Result<Map, String> {
+    // open the map file
+    let map_file =
+        File::open(map_path).map_err(|e| format!("Failed to open map file {}: {}", map_path, e))?;
+
+    // Get the map dimensions present in the first line of the format "XxY"
+    let mut map_dimensions = String::new();
+    let mut map_file = BufReader::new(map_file);
+    map_file
+        .read_line(&mut map_dimensions)
+        .map_err(|e| format!("Failed to read map dimensions: {}", e))?;
+    let (width, length) = map_dimensions
+        .trim()
+        .split_once('x')
+        .and_then(|(width, length)| Some((width.parse::<usize>().ok()?, length.parse().ok()?)))
+        .ok_or(format!(
+            "Failed to parse map dimensions \"{}\", expected e.g. \"40x10\"",
+            map_dimensions.trim()
+        ))?;
+
+    let mut map: Vec<Vec<MapPoint>> = vec![vec![MapPoint::Empty; width]; length];
+    let mut noise_sensitive = vec![];
+    let mut runway_headings = HashMap::new();
+
+    // Read the map file line by line and populate the map
+    for (y, line) in map_file.lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read line in map: {}", e))?;
+        for (x, block) in line.split(",").enumerate() {
+            if block == "..." {
+                continue;
+            }
+            let bad_tile = || format!("Failed to parse tile \"{}\" on line {}", block, y + 2);
+            let mut chars = block.chars();
+            let (point, name, dir_info) = match (chars.next(), chars.next(), chars.next()) {
+                (Some(point), Some(name), Some(dir_info)) => (point, name, dir_info),
+                _ => return Err(bad_tile()),
+            };
+            let direction = Direction::parse(&dir_info).map_err(|_| bad_tile())?;
+
+            let map_point = match point {
+                'R' => {
+                    let name = name.to_digit(10).ok_or_else(bad_tile)?;
+                    // A trailing 'Q' marks the runway as noise sensitive
+                    if block.chars().nth(3) == Some('Q')
+                        && !noise_sensitive.contains(&(name as usize))
+                    {
+                        noise_sensitive.push(name as usize);
+                    }
+                    // Trailing digits give the runway heading, e.g. "R1W270" or "R1WQ270"
+                    let heading = block.get(3..).unwrap_or("").trim_start_matches('Q');
+                    if !heading.is_empty() {
+                        let heading = heading.parse::<u16>().map_err(|_| bad_tile())?;
+                        runway_headings.insert(name as usize, heading);
+                    }
+                    MapPoint::Runway((name as usize, direction))
+                }
+                // Taxiways are named with a digit or a letter, e.g. "T7N" or "TAN"
+                'T' => MapPoint::Taxiway((name.to_ascii_uppercase().to_string(), direction)),
+                'M' => MapPoint::GateTaxiLine((name.to_string(), direction)),
+                'G' => MapPoint::Gate(name.to_string()),
+                'D' => MapPoint::DeicePad(name.to_string()),
+                _ => MapPoint::Empty,
+            };
+            *map.get_mut(y)
+                .and_then(|row| row.get_mut(x))
+                .ok_or(format!(
+                    "Tile \"{}\" on line {} lies outside the {}x{} map",
+                    block,
+                    y + 2,
+                    width,
+                    length
+                ))? = map_point;
+        }
+    }
+
+    // Add spacing of MapPoint::Empty on left/right sides of map rows
+    let mut map = map
+        .iter()
+        .map(|row| {
+            let mut row = row.clone();
+            for _ in 0..spacing.left_right {
+                row.insert(0, MapPoint::Empty);
+                row.push(MapPoint::Empty);
+            }
+            row
+        })
+        .collect::<Vec<Vec<MapPoint>>>();
+    // Add spacing num of columns on top and bottom
+    for _ in 0..spacing.top_bottom {
+        let row = vec![MapPoint::Empty; width + (spacing.left_right * 2)];
+        map.insert(0, row.clone());
+        map.push(row);
+    }
+
+    Ok(Map {
+        length,
+        width,
+        spacing,
+        map,
+        noise_sensitive,
+        runway_headings,
+    })
+}
+
+// Called with the state at the end of every tick, along with the tick number
+type Telemetry<'a> = &'a mut dyn FnMut(&Airport, &Score, usize);
+
+// Function to update the game state for each time step
+fn update_game_state(
+    airport: &mut Airport,
+    spawn_plane: bool,
+    score: &mut Score,
+    receiver: &Receiver<Command>,
+    tts: &mut dyn Speaker,
+    out: Option<&mut dyn Write>,
+    telemetry: Option<Telemetry>,
+) {
+    let render_due = is_render_due(airport);
+    // The tick is drawn on the screen, unless the caller has somewhere else for it to go
+    let mut screen_out;
+    let out: &mut dyn Write = match out {
+        Some(out) => out,
+        None => {
+            screen_out = screen(&airport.render_to, render_due);
+            &mut screen_out
+        }
+    };
+    update_aircraft_position(airport, None);
+    detect_stuck_aircraft(airport);
+    divert_from_thunderstorm(airport);
+    detect_separation_loss(airport, out);
+    warn_of_predicted_conflicts(airport, out);
+    detect_plane_requests(airport);
+    divert_excess_holders(airport);
+    divert_overdue_arrivals(airport);
+    expire_readbacks(airport);
+    update_aircraft_from_user_input(airport, receiver, tts);
+    // Signal alerts
+    update_score(airport, score);
+    if is_weather_due(airport) {
+        simulate_weather(airport, airport.tick_ms as f64 / 1000.0);
+    }
+    if spawn_plane {
+        // A departure needs a free gate to start from
+        let at_gate =
+            !airport.free_gates().is_empty() && !rand::thread_rng().gen_bool(airport.arr_dep_ratio);
+        spawn_landing_aircraft(airport, at_gate);
+    }
+    if render_due {
+        // A fresh render file needs all of the frame drawn
+        if airport.render_to.is_some() {
+            airport.screen = vec![];
+        }
+        render(airport, score, out);
+    }
+    detect_and_handle_collisions(airport, score, out);
+    if let Some(telemetry) = telemetry {
+        telemetry(airport, score, airport.tick);
+    }
+    airport.tick += 1;
+}
+
+// Where everything drawn on the screen goes: the terminal, or the render file with
+// --render-to. A new frame starts the file afresh, so that it only ever holds the latest
+// frame and what has happened since.
+fn screen(render_to: &Option<String>, new_frame: bool) -> Box<dyn Write> {
+    let path = match render_to {
+        Some(path) => path,
+        None => return Box::new(stdout()),
+    };
+    let file = match new_frame {
+        true => File::create(path),
+        false => OpenOptions::new().create(true).append(true).open(path),
+    };
+    // Checked at startup, so a file that stops being writable mid-run only loses frames
+    match file {
+        Ok(file) => Box::new(file),
+        Err(_) => Box::new(std::io::sink()),
+    }
+}
+
+// Whether the weather is to change this tick, an interval after it last did
+fn is_weather_due(airport: &mut Airport) -> bool {
+    if let Some(last) = airport.last_weather_tick {
+        if airport.tick < last + airport.weather_interval {
+            return false;
+        }
+    }
+    airport.last_weather_tick = Some(airport.tick);
+    true
+}
+
+// Whether the screen is to be redrawn this tick. Messages only count down when drawn,
+// so none expire unseen between redraws.
+fn is_render_due(airport: &mut Airport) -> bool {
+    if airport.tick < airport.next_render_tick {
+        return false;
+    }
+    airport.next_render_tick = airport.tick + airport.render_interval;
+    true
+}
+
+// One row of cells per terminal line. A map tile is a cell; a line of text is a single
+// cell that also clears whatever was left on the line.
+type Frame = Vec<Vec<String>>;
+
+// Escape sequences that turn a terminal showing `previous` into `current`, writing only the
+// cells that changed
+fn frame_diff(previous: &Frame, current: &Frame) -> String {
+    let mut output = String::new();
+    if previous.is_empty() {
+        // Nothing has been drawn yet, so start from a clean screen
+        output.push_str("\x1B[2J");
+    }
+    for (row_index, row) in current.iter().enumerate() {
+        match previous.get(row_index) {
+            Some(old_row) if old_row.len() == row.len() => {
+                for (col_index, cell) in row.iter().enumerate() {
+                    if old_row[col_index] != *cell {
+                        output.push_str(&format!(
+                            "\x1B[{};{}H{}",
+                            row_index + 1,
+                            col_index + 1,
+                            cell
+                        ));
+                    }
+                }
+            }
+            _ => {
+                // The line changed shape, so redraw all of it
+                output.push_str(&format!("\x1B[{};1H\x1B[K{}", row_index + 1, row.concat()));
+            }
+        }
+    }
+    // Blank out lines that are no longer used
+    for row_index in current.len()..previous.len() {
+        output.push_str(&format!("\x1B[{};1H\x1B[K", row_index + 1));
+    }
+    output
+}
+
+// Columns left blank between the map and the side panel
+const SIDE_PANEL_GAP: usize = 3;
+
+// Rows of the map with the panel drawn to their right, padded so the panel lines up
+fn side_by_side(map_rows: Frame, panel: Frame) -> Frame {
+    let width = map_rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    (0..map_rows.len().max(panel.len()))
+        .map(|index| {
+            let mut row = map_rows.get(index).cloned().unwrap_or_default();
+            row.resize(width + SIDE_PANEL_GAP, " ".to_string());
+            row.extend(panel.get(index).cloned().unwrap_or_else(|| text_row("")));
+            row
+        })
+        .collect()
+}
+
+// A line of text as a row of the frame
+fn text_row(line: &str) -> Vec<String> {
+    vec![format!("{}\x1B[K", line)]
+}
+
+// Planes in the plane table, in the configured order. Ties keep spawn order.
+fn table_planes(airport: &Airport) -> Vec<&Plane> {
+    let mut planes = airport
+        .planes
+        .iter()
+        .filter(|p| !p.out_of_map)
+        .collect::<Vec<&Plane>>();
+    match airport.plane_sort {
+        PlaneSort::Id => planes.sort_by_key(|p| p.id),
+        PlaneSort::Airline => planes.sort_by_key(|p| {
+            AIRWAY_IDS
+                .get(p.name.get(..2).unwrap())
+                .unwrap()
+                .to_string()
+        }),
+        PlaneSort::Action => planes.sort_by_key(|p| format!("{:?}", p.current_action)),
+        PlaneSort::Runway => planes.sort_by_key(|p| p.runway.name),
+    }
+    planes
+}
+
+// One line per gate with the flight parked at it or taxiing to it
+fn gate_panel(airport: &Airport) -> Vec<String> {
+    let mut gates = airport.gates.values().collect::<Vec<&Gate>>();
+    // Numbered gates in numeric order, so that 10 follows 9, then any others by name
+    gates.sort_by_key(|gate| {
+        let number = gate.number.parse::<usize>().unwrap_or(usize::MAX);
+        (number, gate.number.clone())
+    });
+    gates
+        .iter()
+        .map(|gate| {
+            let flight = |inbound: bool| {
+                airport.planes.iter().find(|p| {
+                    !p.out_of_map
+                        && match &p.current_action {
+                            Action::TaxiToGate(number) => inbound && *number == gate.number,
+                            Action::AtGate((number, _)) => !inbound && *number == gate.number,
+                            _ => false,
+                        }
+                })
+            };
+            let (status, plane) = match (flight(false), flight(true)) {
+                (Some(parked), _) => ("Occupied", parked.name.as_str()),
+                (None, Some(inbound)) => ("Inbound", inbound.name.as_str()),
+                (None, None) => ("Free", "-"),
+            };
+            format!("{}\t{:<10}{}", gate.number, status, plane)
+        })
+        .collect()
+}
+
+// Top line of the screen, with the weather coloured by severity
+fn dashboard(airport: &Airport, score: &Score) -> String {
+    let weather = format!(
+        "{}{:<20}{}",
+        airport.weather.condition.color(),
+        format!("{:?}", airport.weather.condition),
+        RESET_COLOR
+    );
+    let wind_speed = format!(
+        "{:.2} {}",
+        airport.units.speed(airport.weather.wind_speed),
+        airport.units.speed_unit()
+    );
+    let wind_speed = match airport.weather.wind_speed > airport.wind_limit {
+        true => format!("{}{}{}", BOLD_RED_COLOR, wind_speed, RESET_COLOR),
+        false => wind_speed,
+    };
+    let active = airport.planes.iter().filter(|p| !p.out_of_map).count();
+    let runway = match airport.favoured_runway() {
+        Some(runway) => runway.name.to_string(),
+        None => "-".to_string(),
+    };
+    format!(
+        "Planes: {:<4} Takeoffs: {:<5} ({:.1}/min) Runway in use: {:<2} Weather: {} Wind Direction: {}'   Wind Speed: {}",
+        active,
+        score.takeoff,
+        airport.throughput.per_minute(airport.tick),
+        runway,
+        weather,
+        airport.weather.wind_direction,
+        wind_speed
+    )
+}
+
+// Crosswind and tailwind on every runway, in red where they are above the lowest limit
+fn runway_winds(airport: &Airport) -> String {
+    let mut runways = airport
+        .runways
+        .values()
+        .filter(|runway| runway.is_usable())
+        .collect::<Vec<&Runway>>();
+    runways.sort_by_key(|runway| runway.name);
+    let component = |wind: f64, limits: &HashMap<String, f64>| {
+        let text = format!(
+            "{:.0} {}",
+            airport.units.speed(wind),
+            airport.units.speed_unit()
+        );
+        match limits.values().any(|limit| wind > *limit) {
+            true => format!("{}{}{}", BOLD_RED_COLOR, text, RESET_COLOR),
+            false => text,
+        }
+    };
+    let winds = runways
+        .iter()
+        .map(|runway| {
+            let (crosswind, tailwind) = runway.wind_components(&airport.weather);
+            format!(
+                "{} cross {} tail {}",
+                runway.name,
+                component(crosswind, &airport.crosswind_limits),
+                component(tailwind, &airport.tailwind_limits)
+            )
+        })
+        .collect::<Vec<String>>();
+    format!("Runway winds: {}", winds.join("   "))
+}
+
+fn render(airport: &mut Airport, score: &Score, out: &mut dyn Write) {
+    // Build the frame to be drawn, then write only what changed since the last render
+    let mut frame: Frame = vec![];
+
+    // The dashboard
+    frame.push(text_row(&dashboard(airport, score)));
+    frame.push(text_row(&runway_winds(airport)));
+
+    let mut map_rows: Frame = vec![];
+    for (col_index, col) in airport.map.map.iter().enumerate() {
+        let mut cells = vec![];
+        for (row_index, row) in col.iter().enumerate() {
+            // check if plane is at this point
+            if let Some(plane) = airport.planes.iter().rev().find(|plane| {
+                plane.footprint().contains(&(col_index, row_index)) && !plane.out_of_map
+            }) {
+                cells.push(plane_cell(plane));
+                continue;
+            }
+            if let Some(vehicle) = airport
+                .vehicles
+                .iter()
+                .find(|v| v.position == (col_index, row_index))
+            {
+                cells.push(vehicle.kind.glyph().to_string());
+                continue;
+            }
+            let pixel = match row {
+                MapPoint::Runway((name, _)) if airport.closed_runways.contains(name) => {
+                    CLOSED_GLYPH
+                }
+                MapPoint::Taxiway((name, _)) if airport.closed_taxiways.contains(name) => {
+                    CLOSED_GLYPH
+                }
+                _ => row.glyph(),
+            };
+            // The path of the latest clearance stands out for a few ticks
+            let previewed = airport.tick < airport.path_preview_until
+                && airport.path_preview.contains(&(col_index, row_index));
+            match previewed {
+                true => cells.push(format!("{}{}{}", CYAN_COLOR, pixel, RESET_COLOR)),
+                false => cells.push(pixel.to_string()),
+            }
+        }
+        map_rows.push(cells);
+    }
+
+    // Everything else goes in a panel, below the map or beside it
+    let mut panel: Frame = vec![];
+
+    // The plane information in a table format
+    panel.push(text_row("Planes"));
+    panel.push(text_row(&format!(
+        "{}\t{}\t{}\t{:<30}{:<12}{:<10}{:<24}{}",
+        "ID", "Name", "Runway", "Airlines", "Altitude", "Speed", "Status", "Intention"
+    )));
+    let sequence = landing_sequence(airport);
+    for plane in table_planes(airport) {
+        let airline = AIRWAY_IDS.get(plane.name.get(..2).unwrap()).unwrap();
+        let altitude = match plane.current_action {
+            Action::InAir | Action::GoAround | Action::Takeoff => {
+                format!(
+                    "{:.0} {}",
+                    airport.units.altitude(plane.altitude_ft),
+                    airport.units.altitude_unit()
+                )
+            }
+            _ => "-".to_string(),
+        };
+        let speed = match plane.current_action {
+            Action::InAir | Action::GoAround => format!(
+                "{:.0} {}",
+                airport.units.speed(plane.speed_kt),
+                airport.units.speed_unit()
+            ),
+            _ => "-".to_string(),
+        };
+        let mut status = format!("{:?}", plane.current_action);
+        if let Some(position) = sequence.iter().position(|id| *id == plane.id) {
+            status += &format!(" #{}", position + 1);
+        }
+        if plane.altitude_ft > 0.0 && plane.fuel_ticks < LOW_FUEL_TICKS {
+            status += " (low fuel)";
+        }
+        if plane.priority != Priority::Normal {
+            status += &format!(" ({})", plane.priority.name());
+        }
+        // Where a plane is headed is only unclear while it moves about on the ground
+        let intention = match plane.current_action {
+            Action::TaxiToGate(_)
+            | Action::TaxiOntoRunway(_)
+            | Action::HoldShort
+            | Action::HoldPosition
+            | Action::Pushback
+            | Action::Deice(_) => plane.intention.as_str(),
+            _ => "",
+        };
+        panel.push(text_row(&format!(
+            "{}{}\t{}\t{}\t{:<30}{:<12}{:<10}{:<24}{}{}",
+            airline_color(&plane.name),
+            plane.id,
+            plane.name,
+            plane.runway.name,
+            airline,
+            altitude,
+            speed,
+            status,
+            intention,
+            RESET_COLOR
+        )));
+    }
+    panel.push(text_row(""));
+
+    // Ramp status of every gate
+    panel.push(text_row("Gates"));
+    panel.push(text_row(&format!(
+        "{}\t{:<10}{}",
+        "Gate", "Status", "Flight"
+    )));
+    for line in gate_panel(airport) {
+        panel.push(text_row(&line));
+    }
+    panel.push(text_row(""));
+    panel.push(text_row(""));
+
+    // The latest error message
+    if let Ok(error) = ERROR.lock() {
+        if error.timer.load(Ordering::SeqCst) > 0 {
+            panel.push(text_row(&format!("‼  {}", error.message)));
+            error.timer.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // The latest clearance message
+    if let Ok(clearance) = ATC.lock() {
+        if clearance.timer.load(Ordering::SeqCst) > 0 {
+            panel.push(text_row(&format!("🎙  {}", clearance.message)));
+            clearance.timer.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // What planes are waiting on the controller for
+    for request in airport.requests.iter() {
+        panel.push(text_row(&format!("✋ {}", request.message)));
+    }
+
+    // The status requested by the controller
+    if let Ok(status) = STATUS.lock() {
+        if status.timer.load(Ordering::SeqCst) > 0 {
+            for line in status.message.lines() {
+                panel.push(text_row(&format!("ℹ  {}", line)));
+            }
+            status.timer.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // The message from Airport Operations Center (AOC) if any
+    if let Ok(aoc) = AOC.lock() {
+        if aoc.message.len() > 0 {
+            panel.push(text_row(""));
+            for line in aoc.message.lines() {
+                panel.push(text_row(line));
+            }
+        }
+    }
+
+    match airport.layout {
+        Layout::Stacked => {
+            frame.extend(map_rows);
+            frame.extend(panel);
+        }
+        Layout::SidePanel => frame.extend(side_by_side(map_rows, panel)),
+    }
+
+    out.write_all(frame_diff(&airport.screen, &frame).as_bytes())
+        .unwrap();
+    // Leave the cursor below the frame
+    out.write_all(format!("\x1B[{};1H", frame.len() + 1).as_bytes())
+        .unwrap();
+    airport.screen = frame;
+
+    // Flush the output buffer to ensure that the output is immediately displayed
+    out.flush().unwrap();
+}
+
+fn update_aircraft_from_user_input(
+    airport: &mut Airport,
+    receiver: &Receiver<Command>,
+    tts: &mut dyn Speaker,
+) {
+    // Every command waiting is carried out this tick, rather than one a tick, so that the
+    // commands of a script or of several consoles due at once are not put off
+    while let Ok((client, user_input)) = receiver.try_recv() {
+        let mut user_input = airport.expand_alias(&user_input);
+        // Commands that are about the whole airport rather than a single plane
+        let tokens = user_input.split_whitespace().collect::<Vec<&str>>();
+        let outcome = match tokens.as_slice() {
+            ["list"] => {
+                if let Ok(mut status) = STATUS.lock() {
+                    status.message = list_planes(airport);
+                    status.timer = AtomicUsize::new(10);
+                }
+                Some(Ok(String::new()))
+            }
+            ["help"] => {
+                if let Ok(mut status) = STATUS.lock() {
+                    status.message = help_text();
+                    status.timer = AtomicUsize::new(15);
+                }
+                Some(Ok(String::new()))
+            }
+            ["rate", interval] => Some(airport.set_landing_interval(interval)),
+            [keyword @ ("close" | "open"), target] => {
+                Some(airport.set_closed(target, *keyword == "close"))
+            }
+            ["tug", aircraft] => Some(airport.dispatch_tug(aircraft)),
+            ["wx", condition] => Some(airport.force_weather(condition)),
+            ["metar"] => {
+                reply(&client, &CommandResult::Noted(metar(airport)));
+                continue;
+            }
+            ["reload"] => Some(airport.reload_map()),
+            ["prio", aircraft, level] => Some(airport.set_priority(aircraft, level)),
+            ["cross", aircraft, runway] => {
+                let outcome = airport.clear_to_cross(aircraft, runway);
+                if let Ok(clearance) = &outcome {
+                    announce_clearance(
+                        &airport.transcript,
+                        airport.tick,
+                        &aircraft.to_uppercase(),
+                        clearance.clone(),
+                    );
+                }
+                Some(outcome)
+            }
+            // Some clearances are only carried out once read back
+            ["rb", command @ ..] => match airport.read_back(&command.join(" ")) {
+                Ok(command) => {
+                    user_input = command;
+                    None
+                }
+                Err(e) => Some(Err(e)),
+            },
+            // Neither the autopilot nor a script is there to read a clearance back
+            _ if !["autopilot", "script"].contains(&client.as_str())
+                && rand::thread_rng().gen_bool(airport.readback_ratio) =>
+            {
+                match airport.hold_for_readback(&user_input) {
+                    Ok(clearance) => {
+                        tts.say(clearance.clone());
+                        Some(Ok(clearance))
+                    }
+                    // Rejected below like any other command
+                    Err(_) => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(outcome) = outcome {
+            let result = match outcome {
+                Ok(notice) => CommandResult::Noted(notice),
+                Err(Rejection { code, reason }) => CommandResult::Rejected { reason, code },
+            };
+            reply(&client, &result);
+            notify(result);
+            continue;
+        }
+
+        let result = run_command(airport, user_input);
+        reply(&client, &result);
+        match result {
+            CommandResult::Accepted {
+                aircraft,
+                clearance,
+            } => {
+                tts.say(clearance.clone());
+                announce_clearance(&airport.transcript, airport.tick, &aircraft, clearance);
+            }
+            CommandResult::Rejected { reason, .. } => {
+                if airport.speak_errors {
+                    tts.say(reason.clone());
+                }
+                if let Ok(mut error) = ERROR.lock() {
+                    error.message = format!("{} ({})", reason, client);
+                    error.timer = AtomicUsize::new(5);
+                }
+            }
+            CommandResult::Noted(_) => {}
+        }
+    }
+}
+
+// Shows a clearance given to an aircraft, and appends it to the transcript if one is kept
+fn announce_clearance(transcript: &Option<String>, tick: usize, aircraft: &str, clearance: String) {
+    if let Some(path) = transcript {
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}\t{}\t{}", tick, aircraft, clearance));
+        if let Err(e) = written {
+            if let Ok(mut error) = ERROR.lock() {
+                error.message = format!("Could not write to the transcript: {}", e);
+                error.timer = AtomicUsize::new(5);
+            }
+        }
+    }
+    if let Ok(mut atc) = ATC.lock() {
+        atc.message = clearance;
+        atc.timer = AtomicUsize::new(5);
+    }
+}
+
+// Shows the outcome of an airport command as an ATC notice or an error
+fn notify(result: CommandResult) {
+    match result {
+        CommandResult::Noted(notice) if !notice.is_empty() => {
+            if let Ok(mut atc) = ATC.lock() {
+                atc.message = notice;
+                atc.timer = AtomicUsize::new(5);
+            }
+        }
+        CommandResult::Rejected { reason, .. } => {
+            if let Ok(mut error) = ERROR.lock() {
+                error.message = reason;
+                error.timer = AtomicUsize::new(5);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Reads messages out loud. The game uses the text-to-speech engine.
+trait Speaker {
+    fn say(&mut self, text: String);
+}
+
+impl Speaker for Tts {
+    // Queues a message on the text-to-speech engine
+    fn say(&mut self, text: String) {
+        self.speak(text, false).expect("Could not speak message");
+        #[cfg(target_os = "macos")]
+        {
+            let run_loop: id = unsafe { NSRunLoop::currentRunLoop() };
+            unsafe {
+                let date: id = msg_send![class!(NSDate), distantFuture];
+                let _: () = msg_send![run_loop, runMode:NSDefaultRunLoopMode beforeDate:date];
+            }
+        }
+    }
+}
+
+// Syntax and meaning of every controller command
+const COMMANDS: [(&str, &str); 22] = [
+    ("l <aircraft> <runway_number>", "Landing at runway X"),
+    (
+        "l <aircraft> <runway_number> <taxiway>",
+        "Landing at runway X, exit via taxiway Y",
+    ),
+    ("t <aircraft> <runway_number>", "Takeoff from runway X"),
+    (
+        "it <aircraft> <runway_number>",
+        "Immediate takeoff from runway X, closer to arrivals",
+    ),
+    ("hp <aircraft>", "Hold position"),
+    ("p <aircraft>", "Pushback"),
+    ("tor <aircraft> <runway_number>", "Taxi onto runway X"),
+    ("hs <aircraft> <runway_number>", "Hold short of runway X"),
+    ("t2g <aircraft> <gate_number>", "Taxi to gate X"),
+    (
+        "t2g <aircraft> <gate_number> via <taxiway,...>",
+        "Taxi to gate X along the given taxiways",
+    ),
+    ("deice <aircraft> <pad>", "De-ice at pad X"),
+    (
+        "tug <aircraft>",
+        "Send a tug to push back a plane at its gate",
+    ),
+    ("cross <aircraft> <runway_number>", "Cross runway X"),
+    ("rb <command>", "Read back a clearance awaiting readback"),
+    (
+        "prio <aircraft> <level>",
+        "Give a plane normal, medevac or vip priority",
+    ),
+    ("list", "List all active planes"),
+    (
+        "metar",
+        "Send the current weather report back to the console",
+    ),
+    ("help", "Show the map legend and commands"),
+    ("rate <interval>", "Ticks between arrivals, 0 to pause them"),
+    ("close <runway_number>", "Close runway X"),
+    ("open <runway_number>", "Reopen runway X"),
+    ("close|open t<taxiway>", "Close or reopen taxiway X"),
+];
+
+// Map legend built from the glyphs used by the renderer, followed by the command syntax
+fn help_text() -> String {
+    let directions = [
+        Direction::North,
+        Direction::South,
+        Direction::East,
+        Direction::West,
+    ];
+    let glyphs = |point: fn(Direction) -> MapPoint| {
+        directions
+            .iter()
+            .map(|dir| point(dir.clone()).glyph().to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    let mut lines = vec![
+        "Legend".to_string(),
+        format!(
+            "{} {}\tRunway (north/south, east/west)",
+            MapPoint::Runway((1, Direction::North)).glyph(),
+            MapPoint::Runway((1, Direction::East)).glyph()
+        ),
+        format!(
+            "{}\tTaxiway, pointing the way traffic flows",
+            glyphs(|dir| MapPoint::Taxiway(("1".to_string(), dir)))
+        ),
+        format!(
+            "{}\tGate taxi line, pointing towards the gate",
+            glyphs(|dir| MapPoint::GateTaxiLine((String::new(), dir)))
+        ),
+        "1-9\tGate".to_string(),
+        format!(
+            "{}\tDe-icing pad",
+            MapPoint::DeicePad(String::new()).glyph()
+        ),
+        format!("{}\tClosed runway or taxiway", CLOSED_GLYPH),
+        format!("{}\tTug", GroundVehicleKind::Tug.glyph()),
+        format!(
+            "{}\tPlane and its heading",
+            directions
+                .iter()
+                .map(|dir| dir.plane_glyph())
+                .collect::<Vec<&str>>()
+                .join(" ")
+        ),
+        "Commands".to_string(),
+    ];
+    for (syntax, action) in COMMANDS.iter() {
+        lines.push(format!("{:<32}{}", syntax, action));
+    }
+    lines.join("\n")
+}
+
+// Summarises every active plane, one per line
+fn list_planes(airport: &Airport) -> String {
+    let planes = airport
+        .planes
+        .iter()
+        .filter(|p| !p.out_of_map)
+        .map(|p| {
+            format!(
+                "{} {} runway {} {:?}",
+                p.id, p.name, p.runway.name, p.current_action
+            )
+        })
+        .collect::<Vec<String>>();
+    match planes.len() {
+        0 => "No active planes".to_string(),
+        _ => planes.join("\n"),
+    }
+}
+
+// Applies a command to its plane and moves that plane a step right away.
+// Returns the plane as it was when the command was accepted.
+fn issue_command(airport: &mut Airport, command: String) -> Result<Plane, Rejection> {
+    let plane = parse_user_input(command, airport)?;
+    // A plane in the air only accepts landing clearances
+    if plane.current_action == Action::InAir {
+        airport
+            .last_arrival_ticks
+            .insert(plane.runway.name, airport.tick);
+    }
+    // Replace the plane in the fleet and move only that plane
+    if let Some(p) = airport.planes.iter_mut().find(|p| p.id == plane.id) {
+        *p = plane.clone();
+    }
+    update_aircraft_position(airport, Some(plane.id));
+    Ok(plane)
+}
+
+// Why a command was turned down, in a form scripted consoles can branch on
+#[derive(Debug, Clone, PartialEq)]
+enum RejectCode {
+    Syntax,          // Malformed or unknown command
+    UnknownAircraft, // No such plane on the map
+    UnknownTarget,   // No such runway, taxiway or gate
+    Refused,         // Understood, but not allowed right now
+}
+
+impl RejectCode {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RejectCode::Syntax => "syntax",
+            RejectCode::UnknownAircraft => "unknown_aircraft",
+            RejectCode::UnknownTarget => "unknown_target",
+            RejectCode::Refused => "refused",
+        }
+    }
+}
+
+// A command that was turned down, and why
+#[derive(Debug, Clone, PartialEq)]
+struct Rejection {
+    code: RejectCode,
+    reason: String,
+}
+
+impl Rejection {
+    pub fn new(code: RejectCode, reason: String) -> Self {
+        Rejection { code, reason }
+    }
+}
+
+// A command turned down with only a reason was understood, but is not allowed right now
+impl From<String> for Rejection {
+    fn from(reason: String) -> Self {
+        Rejection::new(RejectCode::Refused, reason)
+    }
+}
+
+impl From<&str> for Rejection {
+    fn from(reason: &str) -> Self {
+        Rejection::new(RejectCode::Refused, reason.to_string())
+    }
+}
+
+// Outcome of a command
+#[derive(Debug, Clone, PartialEq)]
+enum CommandResult {
+    Accepted { aircraft: String, clearance: String },
+    Noted(String), // An airport command carried out, with its notice if it has one
+    Rejected { reason: String, code: RejectCode },
+}
+
+impl CommandResult {
+    // Line sent back to the console, e.g. "OK AA123 ..." or "ERR unknown_aircraft Plane not found"
+    pub fn response_line(&self) -> String {
+        match self {
+            CommandResult::Accepted {
+                aircraft,
+                clearance,
+            } => format!("OK {} {}", aircraft, clearance),
+            CommandResult::Noted(notice) if notice.is_empty() => "OK".to_string(),
+            CommandResult::Noted(notice) => format!("OK {}", notice),
+            CommandResult::Rejected { reason, code } => format!("ERR {} {}", code.code(), reason),
+        }
+    }
+}
+
+// Issues a clearance command and sums up how it went
+fn run_command(airport: &mut Airport, command: String) -> CommandResult {
+    match issue_command(airport, command) {
+        Ok(plane) => {
+            airport.path_preview = planned_path(airport, &plane);
+            airport.path_preview_until = airport.tick + PATH_PREVIEW_TICKS;
+            CommandResult::Accepted {
+                clearance: create_atc_clearance(airport, &plane),
+                aircraft: plane.name,
+            }
+        }
+        Err(Rejection { code, reason }) => CommandResult::Rejected { reason, code },
+    }
+}
+
+// Sends the result of a command back to the console that sent it, if it is still connected
+fn reply(client: &str, result: &CommandResult) {
+    send_to_console(client, &result.response_line());
+}
+
+// Sends a line to a console connected over TCP, dropping it if it cannot take the line
+fn send_to_console(client: &str, line: &str) {
+    if let Ok(mut consoles) = CONSOLES.lock() {
+        let sent = consoles
+            .get_mut(client)
+            .map(|stream| stream.write_all(format!("{}\n", line).as_bytes()).is_ok());
+        if sent == Some(false) {
+            consoles.remove(client);
+        }
+    }
+}
+
+// Commands the autopilot would issue this tick: landing clearances while the runway is free,
+// arrivals to the nearest free gate and departures from pushback through takeoff
+fn autopilot_commands(airport: &Airport) -> Vec<String> {
+    // Runways an arrival is cleared to land on or a departure is lined up on
+    let mut busy = airport
+        .planes
+        .iter()
+        .filter(|p| !p.out_of_map)
+        .filter(|p| {
+            (p.cleared_to_land && matches!(p.current_action, Action::InAir | Action::Land(_)))
+                || matches!(p.current_action, Action::TaxiOntoRunway(_))
+        })
+        .map(|p| p.runway.name)
+        .collect::<Vec<usize>>();
+    let mut gates = airport
+        .free_gates()
+        .iter()
+        .map(|gate| gate.number.clone())
+        .collect::<Vec<String>>();
+    // Higher priority planes get the first go at free runways and gates
+    let mut planes = airport
+        .planes
+        .iter()
+        .filter(|p| !p.out_of_map)
+        .collect::<Vec<&Plane>>();
+    planes.sort_by_key(|p| p.priority.clone());
+    let mut commands = vec![];
+    for plane in planes {
+        // A clearance awaiting readback has already been given
+        if airport
+            .pending_readbacks
+            .iter()
+            .any(|r| r.plane == plane.id)
+        {
+            continue;
+        }
+        let runway = plane.runway.name;
+        let runway_free = !busy.contains(&runway) && !airport.runway_occupied(runway);
+        let departing = plane.intention.starts_with("to runway");
+        let command = match &plane.current_action {
+            Action::InAir
+                if !plane.cleared_to_land
+                    && runway_free
+                    && airport.arrival_spacing_elapsed(runway) =>
+            {
+                busy.push(runway);
+                format!("l {} {}", plane.name, runway)
+            }
+            // Pushback is left alone while the weather would refuse it, rather than asked for
+            // again every tick
+            Action::AtGate((_, AtGateAction::Standby))
+                if parse_user_input(format!("p {}", plane.name), airport).is_ok() =>
+            {
+                format!("p {}", plane.name)
+            }
+            Action::HoldPosition if departing => {
+                let point = airport.map.map[plane.position.0][plane.position.1].clone();
+                let held_short = match point {
+                    MapPoint::Taxiway((_, dir)) => dir
+                        .fetch_mappoint(&airport.map, plane.position)
+                        .check_if_runway(),
+                    _ => false,
+                };
+                // With a hold short timeout, departures wait it out before lining up
+                let waited =
+                    airport.hold_short_timeout.is_none() || airport.hold_short_timed_out(plane);
+                match held_short {
+                    true if runway_free && waited => {
+                        busy.push(runway);
+                        format!("tor {} {}", plane.name, runway)
+                    }
+                    true => continue,
+                    false => format!("hs {} {}", plane.name, runway),
+                }
+            }
+            Action::HoldPosition if plane.altitude_ft == 0.0 && plane.intention.is_empty() => {
+                match airport.nearest_free_gate(plane.position) {
+                    Some(gate) if gates.contains(&gate) => {
+                        gates.retain(|g| *g != gate);
+                        format!("t2g {} {}", plane.name, gate)
+                    }
+                    _ => continue,
+                }
+            }
+            Action::TaxiOntoRunway(0) => format!("t {} {}", plane.name, runway),
+            _ => continue,
+        };
+        commands.push(command);
+    }
+    commands
+}
+
+// Where a plane taxiing to the gate moves next, if it can move at all
+fn next_taxi_to_gate_position(
+    map: &Map,
+    position: (usize, usize),
+    runway: &Runway,
+    gate: &str,
+) -> Option<(usize, usize)> {
+    let point = map.map[position.0][position.1].clone();
+    // Check if the plane is standing at the end of the runway
+    if point.clone().check_if_runway()
+        && runway.side.clone().fetch_mappoint(map, position) == MapPoint::Empty
+    {
+        // Change position from runway to taxiway
+        return match point {
+            MapPoint::Runway((_, dir)) => dir.go(map, position),
+            _ => None,
+        };
+    }
+    // Check if there is a GateTaxiLine in any direction surrounding the current direction
+    let (is_nearby_gate, gate_dir) = point.clone().check_for_gate_taxi_line_all_directions(
+        map,
+        position,
+        gate.to_string(),
+        false,
+    );
+    if is_nearby_gate {
+        return gate_dir.go(map, position);
+    }
+    // Traverse along the taxiway/gate line
+    let dir = match point {
+        MapPoint::Taxiway((_, dir)) => dir,
+        MapPoint::GateTaxiLine((_, dir)) => dir,
+        MapPoint::Runway((_, dir)) => dir,
+        _ => return None,
+    };
+    dir.go(map, position)
+}
+
+// Tiles, in order, that a plane at the given position crosses to reach the gate
+fn trace_taxi_tiles(
+    map: &Map,
+    position: (usize, usize),
+    runway: &Runway,
+    gate: &str,
+) -> Result<Vec<(usize, usize)>, String> {
+    let (rows, cols) = map.bounds();
+    let mut tiles = vec![];
+    let mut position = position;
+    // Any route longer than the number of tiles is going round in circles
+    for _ in 0..rows * cols {
+        tiles.push(position);
+        if matches!(&map.map[position.0][position.1], MapPoint::Gate(number) if number == gate) {
+            return Ok(tiles);
+        }
+        position = next_taxi_to_gate_position(map, position, runway, gate)
+            .ok_or(format!("No taxi route to gate {}", gate))?;
+    }
+    Err(format!("No taxi route to gate {}", gate))
+}
+
+// Taxiways, in order, that a plane at the given position follows to reach the gate
+fn trace_taxi_route(
+    map: &Map,
+    position: (usize, usize),
+    runway: &Runway,
+    gate: &str,
+) -> Result<Vec<String>, String> {
+    let mut route: Vec<String> = vec![];
+    for (row, col) in trace_taxi_tiles(map, position, runway, gate)? {
+        if let MapPoint::Taxiway((name, _)) = &map.map[row][col] {
+            if route.last() != Some(name) {
+                route.push(name.clone());
+            }
+        }
+    }
+    Ok(route)
+}
+
+// Ticks the path of a new clearance stays highlighted on the map
+const PATH_PREVIEW_TICKS: usize = 5;
+
+// Tiles a cleared plane is about to use: its runway for a landing or takeoff, or its taxi
+// route for a taxi to a gate
+fn planned_path(airport: &Airport, plane: &Plane) -> Vec<(usize, usize)> {
+    let runway_tiles = || {
+        airport
+            .map
+            .map
+            .iter()
+            .enumerate()
+            .flat_map(|(row, points)| {
+                points.iter().enumerate().filter_map(move |(col, point)| {
+                    matches!(point, MapPoint::Runway((name, _)) if *name == plane.runway.name)
+                        .then_some((row, col))
+                })
+            })
+            .collect::<Vec<(usize, usize)>>()
+    };
+    match &plane.current_action {
+        Action::InAir if plane.cleared_to_land => runway_tiles(),
+        Action::Land(_) | Action::Takeoff => runway_tiles(),
+        Action::TaxiToGate(gate) => {
+            trace_taxi_tiles(&airport.map, plane.position, &plane.runway, gate).unwrap_or_default()
+        }
+        _ => vec![],
+    }
+}
+
+// Checks that the named taxiways are the route to the gate, in order. Planes taxi where the
+// arrows lead, so a route they do not follow cannot be given.
+fn check_taxi_via(
+    map: &Map,
+    position: (usize, usize),
+    runway: &Runway,
+    gate: &str,
+    via: &[String],
+) -> Result<(), String> {
+    let route = trace_taxi_route(map, position, runway, gate)?;
+    match route == via {
+        true => Ok(()),
+        false => Err(format!(
+            "Taxiway {} is not the route to gate {}, which is via taxiway {}",
+            via.join(", "),
+            gate,
+            route.join(", ")
+        )),
+    }
+}
+
+// Moves every plane a step, or only the plane with the given id
+fn update_aircraft_position(airport: &mut Airport, only: Option<usize>) {
+    let landing = airport
+        .planes
+        .iter()
+        .filter(|p| only.is_none() || only == Some(p.id))
+        .filter(|p| matches!(p.current_action, Action::Land(_)))
+        .map(|p| p.id)
+        .collect::<Vec<usize>>();
+    let previous_positions = airport
+        .planes
+        .iter()
+        .map(|p| (p.id, p.position))
+        .collect::<HashMap<usize, (usize, usize)>>();
+    let previous_actions = airport
+        .planes
+        .iter()
+        .map(|p| (p.id, p.current_action.clone()))
+        .collect::<HashMap<usize, Action>>();
+    let occupied = airport
+        .planes
+        .iter()
+        .filter(|p| !p.out_of_map)
+        .map(|p| (p.id, p.footprint()))
+        .collect::<Vec<(usize, Vec<(usize, usize)>)>>();
+    let on_runways = airport
+        .planes
+        .iter()
+        .filter(|p| !p.out_of_map && p.altitude_ft == 0.0)
+        .filter_map(|p| match airport.map.map[p.position.0][p.position.1] {
+            MapPoint::Runway((name, _)) => Some((p.id, name)),
+            _ => None,
+        })
+        .collect::<Vec<(usize, usize)>>();
+
+    // Update aircraft position
+    for plane in airport
+        .planes
+        .iter_mut()
+        .filter(|p| !p.out_of_map && (only.is_none() || only == Some(p.id)))
+        .into_iter()
+    {
+        if plane.altitude_ft > 0.0 {
+            plane.fuel_ticks = plane.fuel_ticks.saturating_sub(1);
+        }
+        match &mut plane.current_action {
+            Action::InAir => {
+                let plane_dir;
+                let pos = match plane.runway.side {
+                    Direction::West | Direction::East | Direction::North | Direction::South => {
+                        plane_dir = plane.runway.side.clone();
+                        match plane_dir.to_owned().go(&airport.map, plane.position) {
+                            Some(pos) => pos,
+                            None => {
+                                plane.out_of_map = true;
+                                continue;
+                            }
+                        }
+                    }
+                    Direction::StayPut => {
+                        hold_for_runway_direction(plane);
+                        continue;
+                    }
+                };
+                // Arrivals spread across the approach corridor turn onto the centreline
+                let threshold = runway_threshold(&airport.map, &plane.runway).unwrap_or(pos);
+                plane.position = match plane_dir {
+                    Direction::East | Direction::West => (step_towards(pos.0, threshold.0), pos.1),
+                    _ => (pos.0, step_towards(pos.1, threshold.1)),
+                };
+                plane.altitude_ft = (plane.altitude_ft - GLIDE_SLOPE_FT_PER_TILE).max(0.0);
+                plane.speed_kt += (APPROACH_SPEED_KT - plane.speed_kt)
+                    .clamp(-SPEED_CHANGE_KT_PER_TILE, SPEED_CHANGE_KT_PER_TILE);
+
+                // The approach must be stable by the time the plane reaches the gate
+                let (min_speed, max_speed) = STABLE_SPEED_KT;
+                if distance_to_threshold(&airport.map, plane.position, &plane.runway)
+                    == Some(airport.stable_gate)
+                    && !(min_speed..=max_speed).contains(&plane.speed_kt)
+                {
+                    let reason = fill_message(
+                        &airport.messages,
+                        "go_around_unstable",
+                        &[
+                            (
+                                "speed",
+                                &format!("{:.0}", airport.units.speed(plane.speed_kt)),
+                            ),
+                            ("unit", airport.units.speed_unit()),
+                        ],
+                    );
+                    go_around(&airport.messages, plane, reason);
+                    continue;
+                }
+
+                // Past the final approach fix the plane calls the tower, once per approach
+                if let Some(fix) = airport.final_fix {
+                    let distance =
+                        distance_to_threshold(&airport.map, plane.position, &plane.runway);
+                    if !plane.on_final && distance.is_some_and(|distance| distance <= fix) {
+                        plane.on_final = true;
+                        airport.requests.push(PlaneRequest {
+                            plane: plane.id,
+                            action: plane.current_action.clone(),
+                            message: fill_message(
+                                &airport.messages,
+                                "established_on_final",
+                                &[
+                                    ("flight", &plane.name),
+                                    ("runway", &plane.runway.name.to_string()),
+                                ],
+                            ),
+                        });
+                    }
+                }
+
+                // Check if plane has reached the start of the runway
+                let runway_name = plane.runway.name;
+                if Direction::StayPut.fetch_mappoint(&airport.map, plane.position)
+                    == MapPoint::Runway((runway_name, plane_dir))
+                {
+                    let runway_occupied = on_runways
+                        .iter()
+                        .any(|(id, name)| *id != plane.id && *name == runway_name);
+                    if !plane.cleared_to_land {
+                        let reason = fill_message(&airport.messages, "go_around_no_clearance", &[]);
+                        go_around(&airport.messages, plane, reason);
+                        airport.missed_approaches += 1;
+                    } else if plane.altitude_ft > THRESHOLD_CROSSING_FT {
+                        let reason = fill_message(
+                            &airport.messages,
+                            "go_around_high",
+                            &[
+                                (
+                                    "altitude",
+                                    &format!("{:.0}", airport.units.altitude(plane.altitude_ft)),
+                                ),
+                                ("unit", airport.units.altitude_unit()),
+                            ],
+                        );
+                        go_around(&airport.messages, plane, reason);
+                        airport.missed_approaches += 1;
+                    } else if airport.closed_runways.contains(&runway_name) {
+                        let reason = fill_message(
+                            &airport.messages,
+                            "go_around_runway_closed",
+                            &[("runway", &runway_name.to_string())],
+                        );
+                        go_around(&airport.messages, plane, reason);
+                        airport.missed_approaches += 1;
+                    } else if runway_occupied {
+                        let reason = fill_message(
+                            &airport.messages,
+                            "go_around_runway_occupied",
+                            &[("runway", &runway_name.to_string())],
+                        );
+                        go_around(&airport.messages, plane, reason);
+                        airport.missed_approaches += 1;
+                    } else {
+                        plane.altitude_ft = 0.0;
+                        plane.current_action = Action::Land(plane.landing_exit.clone());
+                    }
+                }
+            }
+            Action::Land(exit) => {
+                let exit = exit.clone();
+                let pos = match plane.runway.side {
+                    Direction::West | Direction::East | Direction::North | Direction::South => {
+                        let threshold =
+                            runway_threshold(&airport.map, &plane.runway).unwrap_or(plane.position);
+                        let rolled = threshold.0.abs_diff(plane.position.0)
+                            + threshold.1.abs_diff(plane.position.1);
+                        let braked = airport.weather.condition != WeatherCondition::Snow
+                            || rolled >= SNOW_ROLLOUT_TILES;
+                        let plane_dir = plane.runway.side.clone();
+                        // Check if plane has a nearby taxiway
+                        let (nearby_taxiway, taxiway_dir) = plane_dir
+                            .to_owned()
+                            .fetch_mappoint(&airport.map, plane.position)
+                            .to_owned()
+                            .check_for_taxiway(&airport.map, plane.position);
+                        let mut pos = match plane_dir.to_owned().go(&airport.map, plane.position) {
+                            Some(pos) => pos,
+                            None => {
+                                plane.out_of_map = true;
+                                continue;
+                            }
+                        };
+                        if nearby_taxiway {
+                            // Only stop if the direction is outward facing
+                            // i.e. if we take that direction, and follow the path at that point,
+                            // we should not end up on a runway
+                            let mut outward_facing = false;
+                            let potential_map_point = taxiway_dir
+                                .to_owned()
+                                .fetch_mappoint(&airport.map, plane.position);
+                            let potential_point = taxiway_dir
+                                .go(&airport.map, plane.position)
+                                .unwrap_or(plane.position);
+                            let mut assigned_exit = true;
+                            if let MapPoint::Taxiway((name, dir)) = potential_map_point {
+                                if let MapPoint::Runway(_) =
+                                    dir.fetch_mappoint(&airport.map, potential_point)
+                                {
+                                    outward_facing = true;
+                                }
+                                // Roll past other exits when a specific one was assigned
+                                assigned_exit = exit.is_none() || exit == Some(name);
+                            }
+                            if !outward_facing && assigned_exit && braked {
+                                pos = potential_point;
+                                plane.current_action = Action::HoldPosition;
+                            }
+                        }
+                        // Check if plane has reached the end of the runway
+                        if plane_dir.fetch_mappoint(&airport.map, pos) == MapPoint::Empty {
+                            plane.current_action = Action::HoldPosition;
+                        }
+                        pos
+                    }
+                    Direction::StayPut => {
+                        hold_for_runway_direction(plane);
+                        continue;
+                    }
+                };
+                plane.position = pos;
+            }
+            Action::TaxiToGate(gate) => {
+                if let MapPoint::Gate(_) = airport.map.map[plane.position.0][plane.position.1] {
+                    // Gate is now occupied
+                    let at = airport.gates.get_mut(gate).expect("Gate not found");
+                    at.is_occupied = true;
+                    // Change action to AtGate with wait time 0
+                    plane.current_action =
+                        Action::AtGate((gate.clone(), AtGateAction::ShutdownProcedure));
+                    plane.gate_arrival_tick = Some(airport.tick);
+                    continue;
+                }
+                // A plane with nowhere to go stays put, for the stuck detector to report
+                if let Some(position) =
+                    next_taxi_to_gate_position(&airport.map, plane.position, &plane.runway, gate)
+                {
+                    plane.position = position;
+                }
+            }
+            Action::Takeoff => {
+                // The plane leaves the map once it flies off the edge
+                let point = airport.map.map[plane.position.0][plane.position.1].clone();
+                let side = plane.runway.side.clone();
+                match point {
+                    MapPoint::Runway((_, _)) => move_plane(plane, &airport.map, side),
+                    MapPoint::Empty => {
+                        move_plane(plane, &airport.map, side);
+                        plane.altitude_ft += CLIMB_FT_PER_TILE;
+                    }
+                    _ => panic!("Plane is not standing on a runway"),
+                }
+            }
+            Action::GoAround => {
+                // Climb out along the runway, then rejoin the approach from the start
+                if airport.map.is_at_edge(plane.position, &plane.runway.side) {
+                    plane.position =
+                        approach_entry(&airport.map, &plane.runway).unwrap_or(plane.position);
+                    plane.altitude_ft =
+                        glide_path_altitude(&airport.map, plane.position, &plane.runway);
+                    plane.speed_kt = APPROACH_SPEED_KT;
+                    plane.current_action = Action::InAir;
+                    continue;
+                }
+                let side = plane.runway.side.clone();
+                move_plane(plane, &airport.map, side);
+                plane.altitude_ft += CLIMB_FT_PER_TILE;
+            }
+            Action::HoldPosition => {}
+            Action::Deice(pad) => {
+                let point = airport.map.map[plane.position.0][plane.position.1].clone();
+                match point {
+                    // De-iced in one step, like the de-icing step at the gate
+                    MapPoint::DeicePad(_) if !plane.de_iced => plane.de_iced = true,
+                    // Then back out onto the taxiway the plane came in from
+                    MapPoint::DeicePad(_) => {
+                        let (_, taxiway_dir) =
+                            point.check_for_taxiway(&airport.map, plane.position);
+                        move_plane(plane, &airport.map, taxiway_dir);
+                        plane.current_action = Action::HoldPosition;
+                    }
+                    MapPoint::Taxiway((_, dir)) => {
+                        let dir =
+                            adjacent_deice_pad(&airport.map, plane.position, pad).unwrap_or(dir);
+                        move_plane(plane, &airport.map, dir)
+                    }
+                    _ => panic!("Plane is not standing on a taxiway or de-icing pad"),
+                }
+            }
+            Action::TaxiOntoRunway(_) => {
+                let point = airport.map.map[plane.position.0][plane.position.1].clone();
+                match point {
+                    MapPoint::Taxiway((_, dir)) => move_plane(plane, &airport.map, dir),
+                    MapPoint::Runway((name, dir)) => match name {
+                        0 => plane.current_action = Action::TaxiOntoRunway(name),
+                        _ => move_plane(plane, &airport.map, dir),
+                    },
+                    _ => panic!("Plane is not standing on a taxiway or runway"),
+                }
+            }
+            Action::HoldShort => {
+                let point = airport.map.map[plane.position.0][plane.position.1].clone();
+                match point {
+                    MapPoint::Taxiway((_, dir)) => {
+                        match dir
+                            .to_owned()
+                            .fetch_mappoint(&airport.map, plane.position)
+                            .check_if_runway()
+                        {
+                            true => {
+                                plane.current_action = Action::HoldPosition;
+                                plane.held_short_tick = Some(airport.tick);
+                            }
+                            false => move_plane(plane, &airport.map, dir),
+                        }
+                    }
+                    _ => panic!("Plane is not standing on a taxiway"),
+                }
+            }
+            Action::Pushback => {
+                if let Some(arrival) = plane.gate_arrival_tick.take() {
+                    airport.turnarounds.record(airport.tick - arrival);
+                }
+                let point = airport.map.map[plane.position.0][plane.position.1].clone();
+                let target = match point {
+                    MapPoint::GateTaxiLine((_, ref dir)) => {
+                        gate_taxi_line_behind(&airport.map, plane.position, dir)
+                    }
+                    MapPoint::Gate(ref gate) => {
+                        let (is_nearby_gate, gate_dir) =
+                            point.clone().check_for_gate_taxi_line_all_directions(
+                                &airport.map,
+                                plane.position,
+                                gate.to_string(),
+                                true,
+                            );
+                        match is_nearby_gate {
+                            true => gate_dir
+                                .go(&airport.map, plane.position)
+                                .unwrap_or(plane.position),
+                            false => panic!("Plane is not standing near a gate taxi line"),
+                        }
+                    }
+                    _ => panic!("Plane is not standing at a gate or gate taxi line"),
+                };
+
+                // Wait until the lane behind the plane is clear
+                if occupied
+                    .iter()
+                    .any(|(id, footprint)| *id != plane.id && footprint.contains(&target))
+                    || airport
+                        .vehicles
+                        .iter()
+                        .any(|v| v.position == target && v.assisting != Some(plane.id))
+                {
+                    // Said once, when the plane is first held
+                    if !plane.pushback_held {
+                        plane.pushback_held = true;
+                        announce_clearance(
+                            &airport.transcript,
+                            airport.tick,
+                            &plane.name,
+                            format!("{}, hold pushback, traffic on the lane.", plane.name),
+                        );
+                    }
+                    continue;
+                }
+                plane.pushback_held = false;
+                plane.position = target;
+                if point.check_if_gate_taxi_line()
+                    && airport.map.map[target.0][target.1]
+                        .clone()
+                        .check_if_taxiway()
+                {
+                    plane.current_action = Action::HoldPosition;
+                }
+            }
+            Action::AtGate((_, ref mut atgate_action)) => {
+                if *atgate_action == AtGateAction::DeIce {
+                    plane.de_iced = true;
+                }
+                let actions = plane.flight_type.turnaround();
+                let mut iter = actions.iter();
+                while let Some(action) = iter.next() {
+                    if action.to_owned() == atgate_action.to_owned() {
+                        match iter.next() {
+                            Some(next_action) => *atgate_action = next_action.to_owned(),
+                            None => *atgate_action = AtGateAction::Standby,
+                        }
+                    }
+                }
+                // De-icing is only needed while it snows, but then it must be done before pushback
+                let snowing = airport.weather.condition == WeatherCondition::Snow;
+                match atgate_action {
+                    AtGateAction::DeIce if !snowing => *atgate_action = AtGateAction::Standby,
+                    AtGateAction::Standby if snowing && !plane.de_iced => {
+                        *atgate_action = AtGateAction::DeIce
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    resolve_taxi_conflicts(airport, &previous_positions, &previous_actions);
+
+    // Face each plane the way it moved, or away from it while being pushed back
+    for plane in airport
+        .planes
+        .iter_mut()
+        .filter(|p| only.is_none() || only == Some(p.id))
+    {
+        let previous = previous_positions[&plane.id];
+        // Taxiing planes wait for ground vehicles in their way to move
+        if plane.is_moving_on_ground()
+            && airport
+                .vehicles
+                .iter()
+                .any(|v| v.position == plane.position && v.assisting != Some(plane.id))
+        {
+            plane.position = previous;
+        }
+
+        // Taxiing planes hold short of a runway in their way until cleared to cross it
+        let runway_at = |position: (usize, usize)| match airport.map.map[position.0][position.1] {
+            MapPoint::Runway((name, _)) => Some(name),
+            _ => None,
+        };
+        let taxiing = matches!(
+            plane.current_action,
+            Action::TaxiToGate(_) | Action::Pushback | Action::Deice(_)
+        );
+        match (runway_at(previous), runway_at(plane.position)) {
+            (None, Some(runway))
+                if airport.crossing_clearance
+                    && taxiing
+                    && plane.cleared_to_cross != Some(runway) =>
+            {
+                plane.position = previous;
+                if let Ok(mut error) = ERROR.lock() {
+                    error.message = format!(
+                        "{} is holding short of runway {}, awaiting clearance to cross",
+                        plane.name, runway
+                    );
+                    error.timer = AtomicUsize::new(5);
+                }
+            }
+            // The crossing clearance ends once the plane is off the runway
+            (Some(_), None) => plane.cleared_to_cross = None,
+            _ => {}
+        }
+
+        plane.stationary_ticks = match plane.position == previous {
+            true => plane.stationary_ticks + 1,
+            false => 0,
+        };
+
+        // Taxiing planes stop short of closed taxiways
+        let is_closed_taxiway =
+            |position: (usize, usize)| match &airport.map.map[position.0][position.1] {
+                MapPoint::Taxiway((name, _)) => airport.closed_taxiways.contains(name),
+                _ => false,
+            };
+        if is_closed_taxiway(plane.position) && !is_closed_taxiway(previous) {
+            plane.position = previous;
+            plane.current_action = Action::HoldPosition;
+            if let Ok(mut error) = ERROR.lock() {
+                error.message = format!("{} is holding short of a closed taxiway", plane.name);
+                error.timer = AtomicUsize::new(5);
+            }
+            continue;
+        }
+
+        if let Some(dir) = Direction::between(previous, plane.position) {
+            plane.heading = match plane.current_action {
+                Action::Pushback => dir.get_opposite_dir(),
+                _ => dir,
+            };
+        }
+    }
+
+    update_ground_vehicles(airport);
+    hand_off_to_ground(airport, &previous_positions);
+    track_runway_occupancy(airport);
+
+    if airport.auto_gate {
+        assign_gates_to_landed_aircraft(airport, &landing);
+    }
+}
+
+// Right of way between planes on the ground that would end up on the same tile. A plane
+// already there stays put, one carrying on along its taxiway goes before one merging onto it,
+// and arrivals give way to departures. Everyone else holds where they were, still doing what
+// they were doing, and tries again next tick.
+fn resolve_taxi_conflicts(
+    airport: &mut Airport,
+    previous: &HashMap<usize, (usize, usize)>,
+    previous_actions: &HashMap<usize, Action>,
+) {
+    let map = &airport.map;
+    let taxiway_at = |position: (usize, usize)| match &map.map[position.0][position.1] {
+        MapPoint::Taxiway((name, _)) => Some(name),
+        _ => None,
+    };
+    let priority = |plane: &Plane| {
+        let moved = plane.position != previous[&plane.id];
+        let staying_on_taxiway = taxiway_at(plane.position).is_some()
+            && taxiway_at(plane.position) == taxiway_at(previous[&plane.id]);
+        let departing = !matches!(
+            plane.current_action,
+            Action::Land(_) | Action::TaxiToGate(_)
+        );
+        (!moved, staying_on_taxiway, departing)
+    };
+
+    // Holding a plane back can put it in the way of another, so repeat until settled
+    loop {
+        let ground = airport
+            .planes
+            .iter()
+            .filter(|p| !p.out_of_map && p.altitude_ft == 0.0)
+            .collect::<Vec<&Plane>>();
+        let yielding = ground.iter().find_map(|plane| {
+            let rivals = ground
+                .iter()
+                .filter(|p| p.overlaps(plane))
+                .collect::<Vec<_>>();
+            let winner = rivals.iter().max_by_key(|p| priority(p))?;
+            rivals
+                .iter()
+                .find(|p| p.id != winner.id && p.position != previous[&p.id])
+                .map(|p| p.id)
+        });
+        match yielding {
+            Some(id) => {
+                let plane = airport.planes.iter_mut().find(|p| p.id == id).unwrap();
+                plane.position = previous[&id];
+                plane.current_action = previous_actions[&id].clone();
+            }
+            None => break,
+        }
+    }
+}
+
+// Tugs follow the plane they are hooked up to, and leave once it has been pushed back.
+// Vehicles not hooked up to a plane stay parked where they are.
+fn update_ground_vehicles(airport: &mut Airport) {
+    let planes = &airport.planes;
+    airport.vehicles.retain_mut(|vehicle| {
+        let id = match vehicle.assisting {
+            Some(id) => id,
+            None => return true,
+        };
+        match planes.iter().find(|plane| plane.id == id) {
+            Some(plane) if matches!(plane.current_action, Action::AtGate(_) | Action::Pushback) => {
+                vehicle.position = plane.position;
+                true
+            }
+            _ => false,
+        }
+    });
+}
+
+// Tile a plane on a gate taxi line came from: the line tile leading into it, or the one
+// opposite its direction where the line starts
+fn gate_taxi_line_behind(
+    map: &Map,
+    position: (usize, usize),
+    direction: &Direction,
+) -> (usize, usize) {
+    [
+        Direction::North,
+        Direction::South,
+        Direction::East,
+        Direction::West,
+    ]
+    .into_iter()
+    .filter(|dir| !map.is_at_edge(position, dir))
+    .find(|dir| match dir.clone().fetch_mappoint(map, position) {
+        MapPoint::GateTaxiLine((_, next)) => next == dir.clone().get_opposite_dir(),
+        _ => false,
+    })
+    .unwrap_or(direction.clone().get_opposite_dir())
+    .go(map, position)
+    .unwrap_or(position)
+}
+
+// Moves the plane a step in the direction, or off the map if that is where the step leads
+fn move_plane(plane: &mut Plane, map: &Map, direction: Direction) {
+    match direction.go(map, plane.position) {
+        Some(position) => plane.position = position,
+        None => plane.out_of_map = true,
+    }
+}
+
+// Times how long each takeoff and landing keeps its runway occupied, from touchdown or lining
+// up until the plane vacates the runway or leaves the ground
+fn track_runway_occupancy(airport: &mut Airport) {
+    for plane in airport.planes.iter_mut() {
+        let on_runway = !plane.out_of_map
+            && plane.altitude_ft == 0.0
+            && matches!(
+                airport
+                    .map
+                    .map
+                    .get(plane.position.0)
+                    .and_then(|row| row.get(plane.position.1)),
+                Some(MapPoint::Runway(_))
+            );
+        let operating = matches!(
+            plane.current_action,
+            Action::Land(_) | Action::TaxiOntoRunway(_) | Action::Takeoff
+        );
+        match (plane.runway_entry_tick, on_runway) {
+            (None, true) if operating => plane.runway_entry_tick = Some(airport.tick),
+            (Some(entry), false) => {
+                airport
+                    .runway_occupancy
+                    .entry(plane.runway.name)
+                    .or_insert_with(DurationStats::new)
+                    .record(airport.tick - entry);
+                plane.runway_entry_tick = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+// Tells arrivals that have just turned off the runway onto a taxiway to contact Ground
+fn hand_off_to_ground(airport: &mut Airport, previous: &HashMap<usize, (usize, usize)>) {
+    for index in 0..airport.planes.len() {
+        let plane = &airport.planes[index];
+        if plane.out_of_map || plane.on_ground_frequency || plane.altitude_ft > 0.0 {
+            continue;
+        }
+        let (row, col) = previous[&plane.id];
+        let from_runway = matches!(airport.map.map[row][col], MapPoint::Runway(_));
+        let onto_taxiway = airport.map.map[plane.position.0][plane.position.1]
+            .clone()
+            .check_if_taxiway();
+        if !from_runway || !onto_taxiway {
+            continue;
+        }
+        airport.planes[index].on_ground_frequency = true;
+        let plane = &airport.planes[index];
+        let name = AIRWAY_IDS.get(plane.name.get(..2).unwrap()).unwrap();
+        let code = plane.name.get(2..).unwrap();
+        announce_clearance(
+            &airport.transcript,
+            airport.tick,
+            &plane.name,
+            format!("{} {}, contact ground point niner.", name, code),
+        );
+    }
+}
+
+// Clears aircraft that have just finished their landing roll to the nearest free gate
+fn assign_gates_to_landed_aircraft(airport: &mut Airport, landing: &[usize]) {
+    for id in landing {
+        let index = match airport.planes.iter().position(|p| p.id == *id) {
+            Some(index) => index,
+            None => continue,
+        };
+        if !matches!(airport.planes[index].current_action, Action::HoldPosition) {
+            continue;
+        }
+        let gate = match airport.nearest_free_gate(airport.planes[index].position) {
+            Some(gate) => gate,
+            None => continue,
+        };
+        airport.planes[index].current_action = Action::TaxiToGate(gate);
+
+        let clearance = create_atc_clearance(airport, &airport.planes[index]);
+        announce_clearance(
+            &airport.transcript,
+            airport.tick,
+            &airport.planes[index].name,
+            clearance,
+        );
+    }
+}
+
+// Warns about planes that should be moving on the ground but have not moved in a while, and
+// returns the warnings given
+fn detect_stuck_aircraft(airport: &mut Airport) -> Vec<String> {
+    let mut warnings = vec![];
+    for plane in airport.planes.iter_mut().filter(|p| !p.out_of_map) {
+        if !plane.is_moving_on_ground() || plane.stationary_ticks != airport.stuck_after + 1 {
+            continue;
+        }
+        let mut warning = format!(
+            "{} has not moved for {} ticks while {:?}",
+            plane.name, plane.stationary_ticks, plane.current_action
+        );
+        if airport.hold_stuck {
+            plane.current_action = Action::HoldPosition;
+            warning += ", holding position";
+        }
+        if let Ok(mut error) = ERROR.lock() {
+            error.message = warning.clone();
+            error.timer = AtomicUsize::new(5);
+        }
+        warnings.push(warning);
+    }
+    warnings
+}
+
+// Arrivals divert to an alternate airport while a thunderstorm is over the airport
+fn divert_from_thunderstorm(airport: &mut Airport) {
+    if airport.weather.condition != WeatherCondition::Thunderstorm {
+        return;
+    }
+    for plane in airport
+        .planes
+        .iter_mut()
+        .filter(|p| !p.out_of_map && matches!(p.current_action, Action::InAir | Action::GoAround))
+    {
+        plane.out_of_map = true;
+        plane.diverted = true;
+        airport.diversions += 1;
+        if let Ok(mut error) = ERROR.lock() {
+            error.message = fill_message(
+                &airport.messages,
+                "diverted_thunderstorm",
+                &[("flight", &plane.name)],
+            );
+            error.timer = AtomicUsize::new(5);
+        }
+    }
+}
+
+// Something a plane is waiting on the controller for. It stands while the plane is still
+// doing what it was doing when it asked.
+#[derive(Debug, Clone)]
+struct PlaneRequest {
+    plane: usize,
+    action: Action,
+    message: String,
+}
+
+// Ticks a clearance waits to be read back before the plane asks for it again
+const READBACK_TICKS: usize = 10;
+
+// Clearance accepted for a plane but not carried out until the controller reads it back
+#[derive(Debug, Clone)]
+struct PendingReadback {
+    plane: usize,
+    command: String,
+    tick: usize,
+}
+
+// Drops clearances that were not read back in time, and has their planes ask again
+fn expire_readbacks(airport: &mut Airport) {
+    let tick = airport.tick;
+    let (expired, pending) = airport
+        .pending_readbacks
+        .drain(..)
+        .partition::<Vec<PendingReadback>, _>(|p| tick >= p.tick + READBACK_TICKS);
+    airport.pending_readbacks = pending;
+    for readback in expired {
+        airport.requests.retain(|r| r.plane != readback.plane);
+        if let Some(plane) = airport.planes.iter().find(|p| p.id == readback.plane) {
+            let message = airport.message("say_again", &[("flight", &plane.name)]);
+            airport.requests.push(PlaneRequest {
+                plane: plane.id,
+                action: plane.current_action.clone(),
+                message,
+            });
+        }
+    }
+}
+
+// Planes ask for pushback once their turnaround is done, and for departure once they are
+// holding short of the runway
+fn detect_plane_requests(airport: &mut Airport) {
+    let mut requests = airport
+        .requests
+        .iter()
+        .filter(|request| {
+            airport.planes.iter().any(|p| {
+                p.id == request.plane && !p.out_of_map && p.current_action == request.action
+            })
+        })
+        .cloned()
+        .collect::<Vec<PlaneRequest>>();
+
+    for plane in airport.planes.iter().filter(|p| !p.out_of_map) {
+        let previous = airport.last_actions.get(&plane.id);
+        let message = match (previous, &plane.current_action) {
+            (Some(Action::AtGate((_, AtGateAction::Standby))), _) => None,
+            (_, Action::AtGate((_, AtGateAction::Standby))) => {
+                Some(airport.message("ready_for_pushback", &[("flight", &plane.name)]))
+            }
+            (Some(Action::HoldShort), Action::HoldPosition) => Some(airport.message(
+                "ready_for_departure",
+                &[
+                    ("flight", &plane.name),
+                    ("runway", &plane.runway.name.to_string()),
+                ],
+            )),
+            _ => None,
+        };
+        // A plane left holding short asks again once, when its timeout runs out
+        let timeout = airport.hold_short_timeout;
+        let timed_out = timeout
+            .zip(plane.held_short_tick)
+            .is_some_and(|(timeout, since)| since + timeout == airport.tick)
+            && plane.current_action == Action::HoldPosition;
+        let message = match timed_out {
+            true => {
+                requests.retain(|request| request.plane != plane.id);
+                Some(airport.message(
+                    "still_holding_short",
+                    &[
+                        ("flight", &plane.name),
+                        ("runway", &plane.runway.name.to_string()),
+                    ],
+                ))
+            }
+            false => message,
+        };
+        if let Some(message) = message {
+            requests.push(PlaneRequest {
+                plane: plane.id,
+                action: plane.current_action.clone(),
+                message,
+            });
+        }
+    }
+
+    airport.requests = requests;
+    airport.last_actions = airport
+        .planes
+        .iter()
+        .map(|p| (p.id, p.current_action.clone()))
+        .collect();
+}
+
+// Sends the planes that have held the longest to an alternate airport while more planes are
+// holding in the air than there is room for
+fn divert_excess_holders(airport: &mut Airport) {
+    let capacity = match airport.holding_capacity {
+        Some(capacity) => capacity,
+        None => return,
+    };
+    loop {
+        let holding = airport
+            .planes
+            .iter_mut()
+            .filter(|p| {
+                !p.out_of_map && matches!(p.current_action, Action::InAir | Action::GoAround)
+            })
+            .collect::<Vec<&mut Plane>>();
+        if holding.len() <= capacity {
+            break;
+        }
+        let oldest = holding
+            .into_iter()
+            .min_by_key(|p| (p.airborne_since.unwrap_or(0), p.id))
+            .unwrap();
+        oldest.out_of_map = true;
+        oldest.diverted = true;
+        airport.diversions += 1;
+        if let Ok(mut error) = ERROR.lock() {
+            error.message = fill_message(
+                &airport.messages,
+                "diverted_holding_full",
+                &[("flight", &oldest.name)],
+            );
+            error.timer = AtomicUsize::new(5);
+        }
+    }
+}
+
+// Sends arrivals that have flown longer than --max-airborne without a landing clearance to
+// an alternate airport
+fn divert_overdue_arrivals(airport: &mut Airport) {
+    let limit = match airport.max_airborne {
+        Some(limit) => limit,
+        None => return,
+    };
+    let tick = airport.tick;
+    for plane in airport.planes.iter_mut().filter(|p| {
+        !p.out_of_map
+            && !p.cleared_to_land
+            && matches!(p.current_action, Action::InAir | Action::GoAround)
+            && p.airborne_since.is_some_and(|since| tick >= since + limit)
+    }) {
+        plane.out_of_map = true;
+        plane.diverted = true;
+        airport.diversions += 1;
+        if let Ok(mut error) = ERROR.lock() {
+            error.message = fill_message(
+                &airport.messages,
+                "diverted_overdue",
+                &[("flight", &plane.name), ("ticks", &limit.to_string())],
+            );
+            error.timer = AtomicUsize::new(5);
+        }
+    }
+}
+
+// Range of ticks of fuel arrivals enter the map with
+const ARRIVAL_FUEL_TICKS: (usize, usize) = (150, 900);
+// Fuel below which an arrival is sequenced ahead of the others
+const LOW_FUEL_TICKS: usize = 120;
+
+// Order inbound planes are to land in. Low fuel planes go first, the one with the least
+// fuel leading, then the rest by priority and how close they are to the runway threshold.
+fn landing_sequence(airport: &Airport) -> Vec<usize> {
+    let mut inbound = airport
+        .planes
+        .iter()
+        .filter(|p| !p.out_of_map && p.current_action == Action::InAir)
+        .collect::<Vec<&Plane>>();
+    inbound.sort_by_key(|p| match p.fuel_ticks < LOW_FUEL_TICKS {
+        true => (false, Priority::Medevac, p.fuel_ticks),
+        false => (
+            true,
+            p.priority.clone(),
+            distance_to_threshold(&airport.map, p.position, &p.runway).unwrap_or(usize::MAX),
+        ),
+    });
+    inbound.iter().map(|p| p.id).collect()
+}
+
+// Closest airborne planes may get to each other, in tiles and in feet
+const MIN_SEPARATION_TILES: usize = 2;
+const MIN_SEPARATION_FT: f64 = 1000.0;
+
+// Warns when two airborne planes come too close to each other, once per encounter
+fn detect_separation_loss(airport: &mut Airport, out: &mut dyn Write) {
+    let airborne = airport
+        .planes
+        .iter()
+        .filter(|p| !p.out_of_map && p.altitude_ft > 0.0)
+        .collect::<Vec<&Plane>>();
+    let mut losses = vec![];
+    for (i, plane) in airborne.iter().enumerate() {
+        for another_plane in airborne.iter().skip(i + 1) {
+            let tiles = plane.position.0.abs_diff(another_plane.position.0)
+                + plane.position.1.abs_diff(another_plane.position.1);
+            let feet = (plane.altitude_ft - another_plane.altitude_ft).abs();
+            if tiles <= MIN_SEPARATION_TILES && feet < MIN_SEPARATION_FT {
+                losses.push((plane.id, another_plane.id));
+            }
+        }
+    }
+
+    for (id, another_id) in losses.iter() {
+        if airport.separation_losses.contains(&(*id, *another_id)) {
+            continue;
+        }
+        let name = |id: &usize| {
+            let plane = airport.planes.iter().find(|p| p.id == *id).unwrap();
+            plane.name.clone()
+        };
+        if let Ok(mut error) = ERROR.lock() {
+            error.message = format!(
+                "Loss of separation between {} and {}",
+                name(id),
+                name(another_id)
+            );
+            error.timer = AtomicUsize::new(5);
+        }
+        sound_alert(airport.alerts, out);
+    }
+    airport.separation_losses = losses;
+}
+
+// A collision the planes are heading for if they carry on as cleared
+#[derive(Debug, Clone)]
+struct PredictedConflict {
+    planes: (usize, usize),
+    position: (usize, usize),
+    ticks: usize, // Ticks from now
+}
+
+// Moves a copy of the airport forward up to `horizon` ticks, with no new commands, and
+// returns the first collision each pair of planes would run into
+fn predict_conflicts(airport: &Airport, horizon: usize) -> Vec<PredictedConflict> {
+    let mut future = airport.clone();
+    future.transcript = None;
+    // The dry run must not leave its clearances and errors on the screen
+    let messages = [&*ERROR, &*ATC, &*AOC, &*STATUS];
+    let saved = messages
+        .iter()
+        .map(|message| {
+            let message = message.lock().unwrap();
+            (
+                message.message.clone(),
+                message.timer.load(Ordering::SeqCst),
+            )
+        })
+        .collect::<Vec<(String, usize)>>();
+
+    let mut conflicts: Vec<PredictedConflict> = vec![];
+    for ticks in 1..=horizon {
+        update_aircraft_position(&mut future, None);
+        future.tick += 1;
+        let fleet = future
+            .planes
+            .iter()
+            .filter(|p| !p.out_of_map)
+            .collect::<Vec<&Plane>>();
+        for (i, plane) in fleet.iter().enumerate() {
+            for another_plane in fleet.iter().skip(i + 1) {
+                let planes = (plane.id, another_plane.id);
+                if plane.overlaps(another_plane) && !conflicts.iter().any(|c| c.planes == planes) {
+                    conflicts.push(PredictedConflict {
+                        planes,
+                        position: plane.position,
+                        ticks,
+                    });
+                }
+            }
+        }
+    }
+
+    for (message, (text, timer)) in messages.iter().zip(saved) {
+        let mut message = message.lock().unwrap();
+        message.message = text;
+        message.timer = AtomicUsize::new(timer);
+    }
+    conflicts
+}
+
+// Warns once about each pair of planes that is heading for a collision
+fn warn_of_predicted_conflicts(airport: &mut Airport, out: &mut dyn Write) {
+    let horizon = match airport.conflict_horizon {
+        Some(horizon) => horizon,
+        None => return,
+    };
+    let conflicts = predict_conflicts(airport, horizon);
+    for conflict in conflicts.iter() {
+        if airport.predicted_conflicts.contains(&conflict.planes) {
+            continue;
+        }
+        let name = |id: usize| {
+            let plane = airport.planes.iter().find(|p| p.id == id).unwrap();
+            plane.name.clone()
+        };
+        if let Ok(mut error) = ERROR.lock() {
+            error.message = format!(
+                "{} and {} will collide at {:?} in {} ticks",
+                name(conflict.planes.0),
+                name(conflict.planes.1),
+                conflict.position,
+                conflict.ticks
+            );
+            error.timer = AtomicUsize::new(5);
+        }
+        sound_alert(airport.alerts, out);
+    }
+    airport.predicted_conflicts = conflicts.iter().map(|c| c.planes).collect();
+}
+
+// Rings the terminal bell, if alerts are turned on
+fn sound_alert(enabled: bool, out: &mut dyn Write) {
+    if enabled {
+        out.write_all(b"\x07").unwrap();
+        out.flush().unwrap();
+    }
+}
+
+// Wording of the messages that can be changed with --lang, by id. Placeholders in braces
+// are filled in when the message is given.
+const DEFAULT_MESSAGES: [(&str, &str); 86] = [
+    (
+        "land",
+        "{airline} {code}, you are cleared to land on runway {runway}.",
+    ),
+    (
+        "land_terse",
+        "{flight}, cleared to land on runway {runway}.",
+    ),
+    (
+        "land_verbose",
+        "{airline} {code}, you are cleared to land on runway {runway}. Winds {wind}. Read back.",
+    ),
+    (
+        "land_exit",
+        "{airline} {code}, you are cleared to land on runway {runway}, exit via taxiway {taxiway}.",
+    ),
+    (
+        "land_exit_terse",
+        "{flight}, cleared to land on runway {runway}, exit via taxiway {taxiway}.",
+    ),
+    (
+        "land_exit_verbose",
+        "{airline} {code}, you are cleared to land on runway {runway}, exit via taxiway {taxiway}. Winds {wind}. Read back.",
+    ),
+    (
+        "takeoff",
+        "{airline} {code}, you are cleared for takeoff, runway {runway}, winds {wind}.",
+    ),
+    (
+        "takeoff_terse",
+        "{flight}, runway {runway}, cleared for takeoff.",
+    ),
+    (
+        "takeoff_verbose",
+        "{airline} {code}, you are cleared for takeoff, runway {runway}, winds {wind}. Read back.",
+    ),
+    (
+        "immediate_takeoff",
+        "{airline} {code}, runway {runway}, cleared for immediate takeoff, winds {wind}.",
+    ),
+    (
+        "immediate_takeoff_terse",
+        "{flight}, runway {runway}, cleared for immediate takeoff.",
+    ),
+    (
+        "immediate_takeoff_verbose",
+        "{airline} {code}, runway {runway}, cleared for immediate takeoff, winds {wind}. Read back.",
+    ),
+    (
+        "hold_position",
+        "{airline} {code}, hold position, traffic crossing.",
+    ),
+    ("hold_position_terse", "{flight}, hold position."),
+    (
+        "hold_position_verbose",
+        "{airline} {code}, hold position, traffic crossing. Winds {wind}. Read back.",
+    ),
+    (
+        "pushback",
+        "{airline} {code}, pushback approved, expect runway {runway} for departure.",
+    ),
+    (
+        "pushback_terse",
+        "{flight}, pushback approved, expect runway {runway}.",
+    ),
+    (
+        "pushback_verbose",
+        "{airline} {code}, pushback approved, expect runway {runway} for departure. Winds {wind}. Read back.",
+    ),
+    (
+        "taxi_onto_runway",
+        "{airline} {code}, taxi directly to runway {runway}.",
+    ),
+    (
+        "taxi_onto_runway_terse",
+        "{flight}, taxi to runway {runway}.",
+    ),
+    (
+        "taxi_onto_runway_verbose",
+        "{airline} {code}, taxi directly to runway {runway}. Winds {wind}. Read back.",
+    ),
+    (
+        "hold_short",
+        "{airline} {code}, hold short of runway {runway} for landing traffic.",
+    ),
+    (
+        "hold_short_terse",
+        "{flight}, hold short of runway {runway}.",
+    ),
+    (
+        "hold_short_verbose",
+        "{airline} {code}, hold short of runway {runway} for landing traffic. Winds {wind}. Read back.",
+    ),
+    ("taxi_to_gate", "{airline} {code}, taxi to gate {gate}."),
+    ("taxi_to_gate_terse", "{flight}, taxi to gate {gate}."),
+    (
+        "taxi_to_gate_verbose",
+        "{airline} {code}, taxi to gate {gate}. Winds {wind}. Read back.",
+    ),
+    (
+        "taxi_to_gate_via",
+        "{airline} {code}, taxi to gate {gate} via taxiway {taxiway}.",
+    ),
+    (
+        "taxi_to_gate_via_terse",
+        "{flight}, taxi to gate {gate} via taxiway {taxiway}.",
+    ),
+    (
+        "taxi_to_gate_via_verbose",
+        "{airline} {code}, taxi to gate {gate} via taxiway {taxiway}. Winds {wind}. Read back.",
+    ),
+    ("deice", "{airline} {code}, taxi to de-icing pad {pad}."),
+    ("deice_terse", "{flight}, taxi to de-icing pad {pad}."),
+    (
+        "deice_verbose",
+        "{airline} {code}, taxi to de-icing pad {pad}. Winds {wind}. Read back.",
+    ),
+    // Replaced by --collision-message if given
+    (
+        "collision",
+        "🎧 Attention, Air Traffic Control, this is Ground Operations. \
+        We have a Code 34 incident on the tarmac involving {first} and {second}, \
+        which have come into contact. \
+        Emergency services have been alerted and are en route. \
+        All ground movement is currently halted. \
+        Please hold all departures and redirect incoming traffic to alternate taxiways. \
+        We will update as more information becomes available. Over.",
+    ),
+    (
+        "aoc_inclement",
+        "⚠️  Airport Operations Center (AOC): \n\
+        Attention all passengers and crew, \
+        due to the current severe weather conditions, \
+        all departing flights have been temporarily halted for passenger safety. \
+        Incoming flights that are close to landing will proceed as scheduled. \
+        We appreciate your understanding and cooperation. \
+        Please stay tuned to the flight information displays \
+        and airport announcements for further updates. \
+        We sincerely apologize for any inconvenience caused. \
+        Your safety is our top priority. Thank you.",
+    ),
+    (
+        "aoc_thunderstorm",
+        "⚠️  Airport Operations Center (AOC): \n\
+        Thunderstorms are over the airport and a ground stop is in effect. \
+        No aircraft will push back, taxi onto a runway or depart until it passes. \
+        Incoming flights are being diverted to alternate airports. \
+        Thank you for your patience.",
+    ),
+    ("ready_for_pushback", "{flight}, ready for pushback."),
+    (
+        "ready_for_departure",
+        "{flight}, holding short of runway {runway}, ready for departure.",
+    ),
+    (
+        "still_holding_short",
+        "{flight}, still holding short of runway {runway}, requesting departure.",
+    ),
+    (
+        "established_on_final",
+        "{flight}, established on final, runway {runway}.",
+    ),
+    ("say_again", "{flight}, say again."),
+    ("going_around", "{flight} is going around, {reason}"),
+    ("go_around_no_clearance", "no landing clearance"),
+    (
+        "go_around_unstable",
+        "unstable approach at {speed} {unit}",
+    ),
+    ("go_around_high", "{altitude} {unit} over the threshold"),
+    ("go_around_runway_closed", "runway {runway} is closed"),
+    ("go_around_runway_occupied", "runway {runway} is occupied"),
+    (
+        "diverted_thunderstorm",
+        "{flight} diverted to alternate, thunderstorm over the airport",
+    ),
+    (
+        "diverted_holding_full",
+        "{flight} diverted to alternate, holding is full",
+    ),
+    (
+        "diverted_overdue",
+        "{flight} diverted to alternate, not cleared to land within {ticks} ticks",
+    ),
+    ("wrong_length", "Wrong user input length."),
+    ("plane_not_found", "Plane not found"),
+    ("invalid_command", "Invalid command: {keyword}"),
+    (
+        "missing_destination",
+        "Must contain a runway/gate/terminal number",
+    ),
+    ("runway_not_found", "Runway not found"),
+    ("runway_closed", "Runway {runway} is closed"),
+    ("taxiway_not_found", "Taxiway not found"),
+    ("gate_not_found", "Gate not found"),
+    (
+        "invalid_in_air",
+        "Not a valid action when plane is in the air",
+    ),
+    (
+        "invalid_landing",
+        "Not a valid action when in the process of landing",
+    ),
+    (
+        "invalid_takeoff",
+        "Not a valid action when in the process of takeoff",
+    ),
+    (
+        "invalid_holding_position",
+        "Not a valid action when holding position",
+    ),
+    (
+        "invalid_taxiing_onto_runway",
+        "Not a valid action when taxiing onto runway",
+    ),
+    (
+        "invalid_holding_short",
+        "Not a valid action when holding short",
+    ),
+    (
+        "invalid_taxiing_to_gate",
+        "Not a valid action when taxiing to gate",
+    ),
+    ("invalid_deicing", "Not a valid action when de-icing"),
+    (
+        "invalid_pushback",
+        "Not a valid action when in the process of pushback",
+    ),
+    (
+        "invalid_going_around",
+        "Not a valid action when in the process of going around",
+    ),
+    ("invalid_at_gate", "Not a valid action when at gate"),
+    (
+        "turnaround_not_done",
+        "Wait for the plane to finish its turnaround process",
+    ),
+    (
+        "crossing_runway_in_use",
+        "Runway {runway} crosses runway {other}, which is in use",
+    ),
+    (
+        "crosswind_limit",
+        "Crosswind of {wind} {unit} on runway {runway} is above the {limit} {unit} limit for a {category} aircraft",
+    ),
+    (
+        "tailwind_limit",
+        "Tailwind of {wind} {unit} on runway {runway} is above the {limit} {unit} limit for a {category} aircraft",
+    ),
+    (
+        "runway_not_vacated",
+        "Runway {runway} has not been vacated by the previous arrival",
+    ),
+    (
+        "arrival_spacing",
+        "Arrivals on runway {runway} must be {ticks} ticks apart",
+    ),
+    (
+        "departure_spacing",
+        "Departures on runway {runway} must wait {ticks} ticks after a landing clearance",
+    ),
+    (
+        "priority_departs_first",
+        "{priority} flight {flight} departs runway {runway} first",
+    ),
+    (
+        "inclement_no_takeoff",
+        "Cannot takeoff during inclement weather, return back to the gate",
+    ),
+    (
+        "inclement_no_pushback",
+        "Cannot pushback during inclement weather",
+    ),
+    (
+        "ground_stop",
+        "Ground stop in effect due to thunderstorms",
+    ),
+    (
+        "deice_before_pushback",
+        "Wait for the plane to be de-iced before pushback",
+    ),
+    (
+        "snow_no_takeoff",
+        "Cannot takeoff in snow without de-icing",
+    ),
+    ("deice_pad_not_found", "De-icing pad {pad} not found"),
+    (
+        "deice_pad_not_on_the_way",
+        "De-icing pad {pad} is not on the way",
+    ),
+    (
+        "quiet_hours_hold",
+        "Runway {runway} is closed for departures during quiet hours, hold until they end",
+    ),
+    (
+        "quiet_hours_alternate",
+        "Runway {runway} is closed for departures during quiet hours, use runway {alternates}",
+    ),
+];
+
+// Fills in the placeholders of a message, for callers that hold the planes mutably
+fn fill_message(messages: &HashMap<String, String>, id: &str, values: &[(&str, &str)]) -> String {
+    let template = messages.get(id).map_or(id, String::as_str);
+    values
+        .iter()
+        .fold(template.to_string(), |text, (key, value)| {
+            text.replace(&format!("{{{}}}", key), value)
+        })
+}
+
+fn default_messages() -> HashMap<String, String> {
+    DEFAULT_MESSAGES
+        .iter()
+        .map(|(id, text)| (id.to_string(), text.to_string()))
+        .collect()
+}
+
+// Where a plane came to grief, in words, e.g. "taxiway 7"
+fn collision_location(map: &Map, plane: &Plane) -> String {
+    if plane.altitude_ft > 0.0 {
+        return format!("the approach to runway {}", plane.runway.name);
+    }
+    match map
+        .map
+        .get(plane.position.0)
+        .and_then(|row| row.get(plane.position.1))
+    {
+        Some(MapPoint::Runway((0, _))) => "a runway intersection".to_string(),
+        Some(MapPoint::Runway((name, _))) => format!("runway {}", name),
+        Some(MapPoint::Taxiway((name, _))) => format!("taxiway {}", name),
+        Some(MapPoint::Gate(gate)) | Some(MapPoint::GateTaxiLine((gate, _))) => {
+            format!("gate {}", gate)
+        }
+        Some(MapPoint::DeicePad(pad)) => format!("de-icing pad {}", pad),
+        _ => "the apron".to_string(),
+    }
+}
+
+// Function to detect and handle collisions
+fn detect_and_handle_collisions(airport: &mut Airport, score: &mut Score, out: &mut dyn Write) {
+    let fleet = airport.planes.clone();
+    let mut crashed_planes = None;
+    for (i, plane) in fleet.iter().enumerate() {
+        for another_plane in fleet.iter().skip(i + 1) {
+            if plane.overlaps(another_plane)
+                && plane.id != another_plane.id
+                && plane.out_of_map == false
+                && another_plane.out_of_map == false
+            {
+                crashed_planes = Some((plane, another_plane));
+                break;
+            }
+        }
+    }
+    // Planes that swapped tiles since the last check passed through each other
+    if airport.detect_swaps && crashed_planes.is_none() {
+        let last = &airport.last_positions;
+        'swaps: for (i, plane) in fleet.iter().enumerate().filter(|(_, p)| !p.out_of_map) {
+            for another_plane in fleet.iter().skip(i + 1).filter(|p| !p.out_of_map) {
+                if plane.position != another_plane.position
+                    && last.get(&plane.id) == Some(&another_plane.position)
+                    && last.get(&another_plane.id) == Some(&plane.position)
+                {
+                    crashed_planes = Some((plane, another_plane));
+                    break 'swaps;
+                }
+            }
+        }
+    }
+    airport.last_positions = fleet.iter().map(|p| (p.id, p.position)).collect();
+    let mut crashed_vehicle = None;
+    for plane in fleet.iter().filter(|p| !p.out_of_map) {
+        if let Some(vehicle) = airport
+            .vehicles
+            .iter()
+            .find(|v| plane.footprint().contains(&v.position) && v.assisting != Some(plane.id))
+        {
+            crashed_vehicle = Some((plane, vehicle));
+        }
+    }
+
+    // Take appropriate actions in response to collisions
+    if crashed_planes.is_some() {
+        let (plane1, plane2) = crashed_planes.unwrap();
+        let collision_message = airport.message(
+            "collision",
+            &[
+                ("first", &plane1.name),
+                ("second", &plane2.name),
+                ("location", &collision_location(&airport.map, plane1)),
+            ],
+        );
+        out.write_all(collision_message.as_bytes()).unwrap();
+        sound_alert(airport.alerts, out);
+
+        score.crash += 1;
+    }
+    if let Some((plane, vehicle)) = crashed_vehicle {
+        let collision_message = airport.message(
+            "collision",
+            &[
+                ("first", &plane.name),
+                (
+                    "second",
+                    &format!("{:?} {}", vehicle.kind, vehicle.id).to_lowercase(),
+                ),
+                ("location", &collision_location(&airport.map, plane)),
+            ],
+        );
+        out.write_all(collision_message.as_bytes()).unwrap();
+        sound_alert(airport.alerts, out);
+
+        score.crash += 1;
+    }
+}
+
+// Function to handle ground staff alerts
+fn _handle_ground_alerts(_airport: &mut Airport, _alert: _GroundAlert) {
+    // Take appropriate actions in response to ground staff alerts
+}
+
+fn parse_user_input(command: String, airport: &Airport) -> Result<Plane, Rejection> {
+    /*
+        Language is:
+        l <aircraft> <runway_number>        : Landing at runway X
+        l <aircraft> <runway_number> <taxiway> : Landing at runway X, exiting by taxiway Y
+        t <aircraft> <runway_number>        : Takeoff from runway X
+        it <aircraft> <runway_number>       : Immediate takeoff from runway X
+        hp <aircraft>                       : Hold position
+        p <aircraft>                        : Pushback
+        tor <aircraft> <runway_number>      : Taxi onto runway X
+        hs <aircraft> <runway_number>       : Hold short of runway X
+        t2g <aircraft> <gate_number>        : Taxi to gate X
+        t2g <aircraft> <gate_number> via <taxiway,...> : Taxi to gate X along the taxiways
+
+        TODO:
+        t2t <aircraft> <terminal_number>    : Taxi to terminal X
+    */
+    let mut command = command.split_whitespace().collect::<Vec<_>>();
+    // Keywords are matched in any case, like aircraft
+    let keyword = command
+        .first()
+        .ok_or_else(|| Rejection::new(RejectCode::Syntax, airport.message("wrong_length", &[])))?
+        .to_lowercase();
+    command[0] = &keyword;
+    let mut via = vec![];
+    if command.len() == 5 && command[0] == "t2g" && command[3].eq_ignore_ascii_case("via") {
+        via = command[4].split(',').map(taxiway_name).collect();
+        command.truncate(3);
+    }
+    let mut exit = None;
+    if command.len() == 4 && command[0] == "l" {
+        let taxiway = taxiway_name(command[3]);
+        if !airport.map.has_taxiway(&taxiway) {
+            return Err(Rejection::new(
+                RejectCode::UnknownTarget,
+                airport.message("taxiway_not_found", &[]),
+            ));
+        }
+        exit = Some(taxiway);
+        command.truncate(3);
+    }
+    if command.len() > 3 || command.len() < 2 {
+        return Err(Rejection::new(
+            RejectCode::Syntax,
+            airport.message("wrong_length", &[]),
+        ));
+    }
+    let keyword = command[0];
+    let aircraft = command[1].to_string().to_lowercase();
+    let mut plane = airport
+        .planes
+        .iter()
+        .find(|plane| !plane.out_of_map && plane.name.to_lowercase() == aircraft)
+        .ok_or_else(|| {
+            Rejection::new(
+                RejectCode::UnknownAircraft,
+                airport.message("plane_not_found", &[]),
+            )
+        })?
+        .clone();
+
+    let valid_commands = [
+        "hp", "p", "l", "t", "it", "tor", "hs", "t2r", "t2g", "deice",
+    ];
+    if !valid_commands.contains(&keyword) {
+        return Err(Rejection::new(
+            RejectCode::Syntax,
+            airport.message("invalid_command", &[("keyword", keyword)]),
+        ));
+    }
+    if keyword == "hp" || keyword == "p" {
+        if command.len() != 2 {
+            return Err(Rejection::new(
+                RejectCode::Syntax,
+                airport.message("wrong_length", &[]),
+            ));
+        }
+    } else if command.len() != 3 {
+        return Err(Rejection::new(
+            RejectCode::Syntax,
+            airport.message("missing_destination", &[]),
+        ));
+    }
+    let mut destination_num = None;
+    if keyword != "hp" && keyword != "p" {
+        destination_num = Some(command[2].to_string());
+        if keyword != "t2g" && keyword != "deice" {
+            // Check if runway exists, and if it does, set the plane's runway
+            if !airport
+                .runways
+                .contains_key(&destination_num.clone().unwrap())
+            {
+                return Err(Rejection::new(
+                    RejectCode::UnknownTarget,
+                    airport.message("runway_not_found", &[]),
+                ));
+            }
+            let runway = airport
+                .runways
+                .get(&destination_num.clone().unwrap())
+                .unwrap();
+            if keyword != "hs" && airport.closed_runways.contains(&runway.name) {
+                return Err(airport
+                    .message("runway_closed", &[("runway", &runway.name.to_string())])
+                    .into());
+            }
+            plane.runway = runway.clone();
+        }
+    }
+
+    let action = match keyword {
+        "l" => Action::Land(exit),
+        "t" | "it" => Action::Takeoff,
+        "hp" => Action::HoldPosition,
+        "p" => Action::Pushback,
+        "tor" => Action::TaxiOntoRunway(destination_num.clone().unwrap().parse::<usize>().unwrap()),
+        "hs" => Action::HoldShort,
+        "t2g" => Action::TaxiToGate(destination_num.clone().unwrap()),
+        "deice" => Action::Deice(destination_num.clone().unwrap()),
+        _ => Action::HoldPosition, // Should never happen
+    };
+
+    // Runways that cross each other cannot be used for takeoffs and landings at once
+    if matches!(action, Action::Land(_) | Action::Takeoff) {
+        if let Some(other) = airport.crossing_runway_in_use(plane.runway.name, plane.id) {
+            return Err(airport
+                .message(
+                    "crossing_runway_in_use",
+                    &[
+                        ("runway", &plane.runway.name.to_string()),
+                        ("other", &other.to_string()),
+                    ],
+                )
+                .into());
+        }
+        let (crosswind, tailwind) = plane.runway.wind_components(&airport.weather);
+        for (id, wind, limits) in [
+            ("crosswind_limit", crosswind, &airport.crosswind_limits),
+            ("tailwind_limit", tailwind, &airport.tailwind_limits),
+        ] {
+            if let Some(limit) = limits.get(plane.category()) {
+                if wind > *limit {
+                    return Err(airport
+                        .message(
+                            id,
+                            &[
+                                ("wind", &format!("{:.0}", airport.units.speed(wind))),
+                                ("limit", &format!("{:.0}", airport.units.speed(*limit))),
+                                ("unit", airport.units.speed_unit()),
+                                ("runway", &plane.runway.name.to_string()),
+                                ("category", plane.category()),
+                            ],
+                        )
+                        .into());
+                }
+            }
+        }
+    }
+
+    /*
+        Valid successors for each action:
+        InAir: Land (assigning the runway exit)
+        Land: -
+        HoldPosition: TaxiToGate (after landing), TaxiToRunway, HoldShort, TaxiOntoRunway, Deice
+        Pushback: -
+        TaxiOntoRunway: HoldPosition, HoldShort, Takeoff, TaxiToRunway, TaxiToGate, Deice
+        HoldShort: HoldPosition, TaxiOntoRunway, Takeoff, TaxiToRunway
+        TaxiToGate: HoldPosition
+        Takeoff: -
+        AtGate: Pushback (only when on standby)
+        Deice: HoldPosition
+    */
+    let in_air = plane.current_action == Action::InAir;
+    match plane.current_action.clone() {
+        Action::InAir => match action {
+            Action::Land(_) => {}
+            _ => return Err(airport.message("invalid_in_air", &[]).into()),
+        },
+        Action::Land(_) => return Err(airport.message("invalid_landing", &[]).into()),
+        Action::Takeoff => return Err(airport.message("invalid_takeoff", &[]).into()),
+        Action::HoldPosition => match action {
+            Action::TaxiToGate(_)
+            | Action::HoldShort
+            | Action::TaxiOntoRunway(_)
+            | Action::Deice(_) => {}
+            _ => {
+                return Err(airport.message("invalid_holding_position", &[]).into());
+            }
+        },
+        Action::TaxiOntoRunway(_) => match action {
+            // Need TaxiToGate during emergency situations
+            Action::HoldPosition | Action::HoldShort | Action::TaxiToGate(_) | Action::Deice(_) => {
+            }
+            Action::Takeoff => {
+                if airport.weather.condition == WeatherCondition::InclementWeather {
+                    return Err(airport.message("inclement_no_takeoff", &[]).into());
+                }
+            }
+            _ => {
+                return Err(airport.message("invalid_taxiing_onto_runway", &[]).into());
+            }
+        },
+        Action::HoldShort => match action {
+            Action::HoldPosition | Action::TaxiOntoRunway(_) => {}
+            Action::Takeoff => {
+                if airport.weather.condition == WeatherCondition::InclementWeather {
+                    return Err(airport.message("inclement_no_takeoff", &[]).into());
+                }
+            }
+            _ => {
+                return Err(airport.message("invalid_holding_short", &[]).into());
+            }
+        },
+        Action::TaxiToGate(_) => match action {
+            Action::HoldPosition => {}
+            _ => {
+                return Err(airport.message("invalid_taxiing_to_gate", &[]).into());
+            }
+        },
+        Action::Deice(_) => match action {
+            Action::HoldPosition => {}
+            _ => {
+                return Err(airport.message("invalid_deicing", &[]).into());
+            }
+        },
+        Action::Pushback => return Err(airport.message("invalid_pushback", &[]).into()),
+        Action::GoAround => return Err(airport.message("invalid_going_around", &[]).into()),
+        Action::AtGate((gate, at_gate_action)) => match action {
+            Action::Pushback => {
+                if at_gate_action != AtGateAction::Standby {
+                    return Err(airport.message("turnaround_not_done", &[]).into());
+                }
+                if airport.weather.condition == WeatherCondition::InclementWeather {
+                    return Err(airport.message("inclement_no_pushback", &[]).into());
+                }
+                // Departures that started at the gate filed a random or favoured runway when
+                // they appeared, and keep it
+                if plane.turning_around || airport.departure_runways == DepartureRunways::Gate {
+                    plane.runway = airport.filed_runway(&gate);
+                }
+            }
+            _ => {
+                return Err(airport.message("invalid_at_gate", &[]).into());
+            }
+        },
+    }
+
+    // Arrivals on the same runway are spaced out, and wait for the one before to vacate it
+    // An arrival cleared again, e.g. for another exit, is not held up by its own clearance
+    let recleared = airport
+        .planes
+        .iter()
+        .any(|p| p.id == plane.id && p.cleared_to_land && p.runway.name == plane.runway.name);
+    if matches!(action, Action::Land(_)) && airport.arrival_spacing.is_some() && !recleared {
+        let runway = plane.runway.name;
+        let previous_arrival = airport.planes.iter().any(|p| {
+            !p.out_of_map
+                && p.id != plane.id
+                && p.cleared_to_land
+                && p.current_action == Action::InAir
+                && p.runway.name == runway
+        });
+        if previous_arrival || airport.runway_occupied(runway) {
+            return Err(airport
+                .message("runway_not_vacated", &[("runway", &runway.to_string())])
+                .into());
+        }
+        if !airport.arrival_spacing_elapsed(runway) {
+            return Err(airport
+                .message(
+                    "arrival_spacing",
+                    &[
+                        ("runway", &runway.to_string()),
+                        ("ticks", &airport.arrival_spacing.unwrap_or(0).to_string()),
+                    ],
+                )
+                .into());
+        }
+    }
+
+    // Departures are spaced from arrivals the same way, unless cleared for an immediate
+    // takeoff, which accepts the closer spacing
+    plane.immediate_takeoff = keyword == "it";
+    if action == Action::Takeoff && !plane.immediate_takeoff && airport.arrival_spacing.is_some() {
+        let runway = plane.runway.name;
+        let arrival = airport.planes.iter().any(|p| {
+            !p.out_of_map
+                && p.cleared_to_land
+                && p.current_action == Action::InAir
+                && p.runway.name == runway
+        });
+        if arrival || !airport.arrival_spacing_elapsed(runway) {
+            return Err(airport
+                .message(
+                    "departure_spacing",
+                    &[
+                        ("runway", &runway.to_string()),
+                        ("ticks", &airport.arrival_spacing.unwrap_or(0).to_string()),
+                    ],
+                )
+                .into());
+        }
+    }
+
+    // Medevac and VIP departures holding short go onto the runway ahead of other planes
+    let on_runway = airport.map.map[plane.position.0][plane.position.1]
+        .clone()
+        .check_if_runway();
+    let entering = match action {
+        Action::TaxiOntoRunway(runway) => Some(runway),
+        Action::Takeoff if !on_runway => Some(plane.runway.name),
+        _ => None,
+    };
+    if let Some(runway) = entering {
+        if let Some(ahead) = airport.planes.iter().find(|p| {
+            !p.out_of_map
+                && p.id != plane.id
+                && p.priority < plane.priority
+                && p.runway.name == runway
+                && p.current_action == Action::HoldPosition
+                && match airport.map.map[p.position.0][p.position.1].clone() {
+                    MapPoint::Taxiway((_, dir)) => dir
+                        .fetch_mappoint(&airport.map, p.position)
+                        .check_if_runway(),
+                    _ => false,
+                }
+        }) {
+            return Err(airport
+                .message(
+                    "priority_departs_first",
+                    &[
+                        ("priority", ahead.priority.name()),
+                        ("flight", &ahead.name),
+                        ("runway", &runway.to_string()),
+                    ],
+                )
+                .into());
+        }
+    }
+
+    if airport.weather.condition == WeatherCondition::Thunderstorm
+        && matches!(
+            action,
+            Action::Pushback | Action::TaxiOntoRunway(_) | Action::Takeoff
+        )
+    {
+        return Err(airport.message("ground_stop", &[]).into());
+    }
+    if airport.weather.condition == WeatherCondition::Snow
+        && action == Action::Pushback
+        && !plane.de_iced
+    {
+        return Err(airport.message("deice_before_pushback", &[]).into());
+    }
+    if airport.weather.condition == WeatherCondition::Snow
+        && action == Action::Takeoff
+        && !plane.de_iced
+    {
+        return Err(airport.message("snow_no_takeoff", &[]).into());
+    }
+    if let Action::Deice(pad) = &action {
+        if !airport.map.has_deice_pad(pad) {
+            return Err(Rejection::new(
+                RejectCode::UnknownTarget,
+                airport.message("deice_pad_not_found", &[("pad", pad)]),
+            ));
+        }
+        if !deice_pad_ahead(&airport.map, plane.position, pad) {
+            return Err(airport
+                .message("deice_pad_not_on_the_way", &[("pad", pad)])
+                .into());
+        }
+    }
+
+    if action == Action::Takeoff && plane.runway.noise_sensitive && airport.is_quiet_hours() {
+        let mut alternates = airport
+            .runways
+            .values()
+            .filter(|r| r.is_usable() && !r.noise_sensitive)
+            .filter(|r| !airport.closed_runways.contains(&r.name))
+            .map(|r| r.name)
+            .collect::<Vec<usize>>();
+        alternates.sort();
+        let alternates = alternates
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<String>>();
+        let runway = plane.runway.name.to_string();
+        return Err(match alternates.len() {
+            0 => airport.message("quiet_hours_hold", &[("runway", &runway)]),
+            _ => airport.message(
+                "quiet_hours_alternate",
+                &[
+                    ("runway", &runway),
+                    ("alternates", &alternates.join(" or ")),
+                ],
+            ),
+        }
+        .into());
+    }
+
+    if let Action::TaxiToGate(gate) = &action {
+        if !airport.gates.contains_key(gate) {
+            return Err(Rejection::new(
+                RejectCode::UnknownTarget,
+                airport.message("gate_not_found", &[]),
+            ));
+        }
+        // Taxiing follows the arrows on the map, so it cannot find its way to a gate that
+        // is cut off from where the plane is, or that the arrows lead away from
+        trace_taxi_route(&airport.map, plane.position, &plane.runway, gate)?;
+        if !via.is_empty() {
+            check_taxi_via(&airport.map, plane.position, &plane.runway, gate, &via)?;
+        }
+        plane.taxi_route = via;
+    }
+
+    if let Some(intention) = intention(&action, &plane.runway) {
+        plane.intention = intention;
+    }
+
+    // A new clearance ends any wait at the hold line
+    plane.held_short_tick = None;
+    match action {
+        // The plane keeps flying its approach and lands once it crosses the threshold
+        Action::Land(exit) if in_air => {
+            plane.landing_exit = exit;
+            plane.cleared_to_land = true;
+        }
+        // Sent to the pad to be de-iced afresh
+        Action::Deice(_) => {
+            plane.de_iced = false;
+            plane.current_action = action;
+        }
+        _ => plane.current_action = action,
+    }
+
+    Ok(plane)
+}
+
+// Where a ground clearance sends the plane, in words
+fn intention(action: &Action, runway: &Runway) -> Option<String> {
+    match action {
+        Action::TaxiToGate(gate) => Some(format!("to gate {}", gate)),
+        Action::Pushback | Action::TaxiOntoRunway(_) | Action::HoldShort => {
+            Some(format!("to runway {} for departure", runway.name))
+        }
+        Action::Deice(pad) => Some(format!("to de-icing pad {}", pad)),
+        _ => None,
+    }
+}
+
+fn create_atc_clearance(airport: &Airport, plane: &Plane) -> String {
+    let name = AIRWAY_IDS.get(plane.name.get(..2).unwrap()).unwrap();
+    let code = plane.name.get(2..).unwrap().to_string();
+    let runway = plane.runway.spoken_name();
+    // Planes not first in the landing sequence are told their place in it
+    let number = match &plane.current_action {
+        Action::InAir | Action::Land(_) => landing_sequence(airport)
+            .iter()
+            .position(|id| *id == plane.id)
+            .filter(|position| *position > 0)
+            .map_or(String::new(), |position| {
+                format!(", number {}", position + 1)
+            }),
+        _ => String::new(),
+    };
+    let code = format!("{}{}", code, number);
+    let flight = format!("{}{}", plane.name, number);
+    let wind = spoken_wind(airport);
+    // Each clearance is worded for every phraseology, e.g. "land", "land_terse" and
+    // "land_verbose"
+    let say = |id: &str, values: &[(&str, &str)]| {
+        let id = match airport.phraseology {
+            Phraseology::Standard => id.to_string(),
+            Phraseology::Terse => format!("{}_terse", id),
+            Phraseology::Verbose => format!("{}_verbose", id),
+        };
+        let common = [
+            ("airline", *name),
+            ("code", code.as_str()),
+            ("flight", flight.as_str()),
+            ("wind", wind.as_str()),
+        ];
+        airport.message(&id, &[values, &common].concat())
+    };
+    match &plane.current_action {
+        Action::InAir | Action::Land(_) => match &plane.landing_exit {
+            Some(exit) => say("land_exit", &[("runway", &runway), ("taxiway", exit)]),
+            None => say("land", &[("runway", &runway)]),
+        },
+        Action::Takeoff => say(
+            match plane.immediate_takeoff {
+                true => "immediate_takeoff",
+                false => "takeoff",
+            },
+            &[("runway", &runway)],
+        ),
+        Action::HoldPosition => say("hold_position", &[]),
+        Action::Pushback => say("pushback", &[("runway", &runway)]),
+        Action::TaxiOntoRunway(num) => {
+            let runway = match airport.runways.get(&num.to_string()) {
+                Some(runway) => runway.spoken_name(),
+                None => num.to_string(),
+            };
+            say("taxi_onto_runway", &[("runway", &runway)])
+        }
+        Action::HoldShort => say("hold_short", &[("runway", &runway)]),
+        Action::TaxiToGate(gate) if !plane.taxi_route.is_empty() => say(
+            "taxi_to_gate_via",
+            &[("gate", gate), ("taxiway", &plane.taxi_route.join(", "))],
+        ),
+        Action::TaxiToGate(gate) => {
+            // Find the taxiway closest to the plane's position
+            let point: MapPoint = airport.map.map[plane.position.0][plane.position.1].clone();
+            let taxiway = match point {
+                MapPoint::Taxiway((name, _)) => Some(name),
+                MapPoint::Runway((_, dir)) => {
+                    match dir.fetch_mappoint(&airport.map, plane.position) {
+                        MapPoint::Taxiway((name, _)) => Some(name),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match taxiway {
+                None => say("taxi_to_gate", &[("gate", gate)]),
+                Some(taxiway) => say("taxi_to_gate_via", &[("gate", gate), ("taxiway", &taxiway)]),
+            }
+        }
+        Action::Deice(pad) => say("deice", &[("pad", pad)]),
+        Action::AtGate(_) => "".to_string(),
+        Action::GoAround => "".to_string(),
+    }
+}
+
+// Wind as read out in clearances, e.g. "two seven zero at one zero, gusting one eight knots"
+fn spoken_wind(airport: &Airport) -> String {
+    let speed =
+        |knots: f64| spoken_digits(&(airport.units.speed(knots).round() as usize).to_string());
+    let gust = match airport.weather.wind_gust {
+        Some(gust) => format!(", gusting {}", speed(gust)),
+        None => String::new(),
+    };
+    format!(
+        "{} at {}{} {}",
+        spoken_digits(&format!("{:03}", airport.weather.wind_direction)),
+        speed(airport.weather.wind_speed),
+        gust,
+        airport.units.spoken_speed_unit()
+    )
+}
+
+// Weather report in the style of a METAR, e.g. "METAR 010930Z 27010G18KT 6000 RA". The time
+// counts from day 1, 00:00 at the start of the simulation.
+fn metar(airport: &Airport) -> String {
+    let minutes = airport.tick as u64 * airport.tick_ms / 60_000;
+    let time = format!(
+        "{:02}{:02}{:02}Z",
+        1 + minutes / (24 * 60),
+        minutes / 60 % 24,
+        minutes % 60
+    );
+    let direction = match (airport.weather.wind_direction + 5) / 10 * 10 % 360 {
+        0 => 360,
+        direction => direction,
+    };
+    let gust = match airport.weather.wind_gust {
+        Some(gust) => format!("G{:02.0}", gust),
+        None => String::new(),
+    };
+    let wind = format!(
+        "{:03}{:02.0}{}KT",
+        direction, airport.weather.wind_speed, gust
+    );
+    let (visibility, weather) = airport.weather.condition.metar();
+    format!("METAR {} {} {:04} {}", time, wind, visibility, weather)
+}
+
+// Turns colours off and shows the cursor again, whatever state the last frame left them in
+fn restore_terminal(out: &mut dyn Write) {
+    out.write_all(format!("{}\x1B[?25h\n", RESET_COLOR).as_bytes())
+        .unwrap();
+    out.flush().unwrap();
+}
+
+// Average runway occupancy time of each runway, for the summary
+fn runway_occupancy_lines(airport: &Airport) -> Vec<String> {
+    let mut runways = airport.runway_occupancy.keys().collect::<Vec<&usize>>();
+    runways.sort();
+    runways
+        .into_iter()
+        .filter_map(|runway| {
+            let stats = &airport.runway_occupancy[runway];
+            stats.mean().map(|mean| {
+                format!(
+                    "Runway {} occupancy:\t{} (mean {:.1}, min {}, max {} ticks)",
+                    runway, stats.count, mean, stats.min, stats.max
+                )
+            })
+        })
+        .collect()
+}
+
+// End of simulation report
+fn summary_text(airport: &Airport, score: &Score) -> String {
+    let on_ground = airport
+        .planes
+        .iter()
+        .filter(|plane| !plane.out_of_map && plane.altitude_ft == 0.0)
+        .count();
+    let in_air = airport.planes.len() - score.takeoff - score.diversion - on_ground;
+    [
+        "Simulation summary".to_string(),
+        format!("Ticks:\t\t{}", airport.tick),
+        format!("Planes:\t\t{}", airport.planes.len()),
+        format!("Takeoffs:\t{}", score.takeoff),
+        format!("On the ground:\t{}", on_ground),
+        format!("In the air:\t{}", in_air),
+        format!("Crashes:\t{}", score.crash),
+        format!("Missed approaches:\t{}", score.missed_approach),
+        format!("Diversions:\t{}", score.diversion),
+        match airport.turnarounds.mean() {
+            Some(mean) => format!(
+                "Turnarounds:\t{} (mean {:.1}, min {}, max {} ticks)",
+                airport.turnarounds.count, mean, airport.turnarounds.min, airport.turnarounds.max
+            ),
+            None => "Turnarounds:\t0".to_string(),
+        },
+    ]
+    .into_iter()
+    .chain(runway_occupancy_lines(airport))
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+fn update_score(airport: &mut Airport, score: &mut Score) {
+    // Update the score based on the current game state
+    let mut num_takeoffs = 0;
+    for plane in airport.planes.iter() {
+        // Only a departure leaves the map while taking off, an overrun or a taxi off the edge
+        // does not count
+        if plane.out_of_map && plane.current_action == Action::Takeoff {
+            num_takeoffs += 1;
+        }
+    }
+    for _ in score.takeoff..num_takeoffs {
+        airport.throughput.record(airport.tick);
+    }
+    score.takeoff = num_takeoffs;
+    score.missed_approach = airport.missed_approaches;
+    score.diversion = airport.diversions;
+}
+
+// Chance of something happening within a tick of the given length, given its chance of
+// happening within one second
+fn chance_in_tick(chance_per_second: f64, seconds: f64) -> f64 {
+    1.0 - (1.0 - chance_per_second).powf(seconds)
+}
+
+// Changes the weather condition, with chances of a change scaled to the length of a tick
+fn simulate_weather(airport: &mut Airport, seconds: f64) {
+    let mut rng = rand::thread_rng();
+    let previous = airport.weather.condition.clone();
+    let next = airport
+        .weather_transitions
+        .iter()
+        .filter(|transition| transition.from == previous)
+        .find(|transition| {
+            rng.gen_bool(chance_in_tick(transition.chance_per_second, seconds).clamp(0.0, 1.0))
+        })
+        .map(|transition| transition.to.clone());
+    if let Some(next) = next {
+        change_weather(airport, next);
+    }
+    simulate_wind_direction_and_speed(&mut airport.weather, 10);
+}
+
+// Sets the weather condition, along with the AOC alert for it
+fn change_weather(airport: &mut Airport, next: WeatherCondition) {
+    let message = match next {
+        WeatherCondition::InclementWeather => airport.message("aoc_inclement", &[]),
+        WeatherCondition::Thunderstorm => airport.message("aoc_thunderstorm", &[]),
+        // The alert for the weather left behind no longer applies
+        _ => String::new(),
+    };
+    if let Ok(mut aoc) = AOC.lock() {
+        aoc.message = message;
+    }
+    airport.weather.condition = next;
+}
+
+fn simulate_wind_direction_and_speed(weather: &mut Weather, prob: usize) {
+    let mut rng = rand::thread_rng();
+    if rng.gen_range(0..100) < prob {
+        let distribution = weather.wind.for_condition(&weather.condition);
+        weather.wind_speed = distribution.sample();
+        let peak = distribution.sample();
+        weather.wind_gust = (peak >= weather.wind_speed + GUST_SPREAD_KT).then_some(peak);
+    }
+
+    if prob == 100 || rng.gen_range(0..100) < 5 {
+        let normal_wind_direction = Normal::new(weather.wind_direction as f64, 20.0).unwrap();
+        let dir = normal_wind_direction.sample(&mut rand::thread_rng());
+        weather.wind_direction = if dir > 360.0 {
+            f64::min(dir - 360.0, 360.0)
+        } else if dir < 0.0 {
+            f64::max(dir + 360.0, 0.0)
+        } else {
+            dir
+        } as usize;
+    }
+}
+
+// Ticks between scheduled arrivals, until changed with the rate command
+const LANDING_INTERVAL: usize = 60;
+
+// Whether an arrival is scheduled this tick and the weather lets it through.
+// Each scheduled arrival books the next one an interval later, give or take the jitter.
+// An interval of 0 pauses arrivals.
+fn is_arrival_due(airport: &mut Airport) -> bool {
+    if airport.landing_interval == 0 || airport.tick < airport.next_arrival_tick {
+        return false;
+    }
+    let mut rng = rand::thread_rng();
+    let jitter = airport.arrival_jitter as isize;
+    let gap = airport.landing_interval as isize + rng.gen_range(-jitter..=jitter);
+    airport.next_arrival_tick = airport.tick + gap.max(1) as usize;
+
+    let rate = airport
+        .arrival_rates
+        .for_condition(&airport.weather.condition);
+    rng.gen_bool(rate)
+}
+
+// Where a new plane is to appear. Anything left unset is picked as usual: arrivals fly in
+// for runway 1 somewhere across the approach corridor.
+#[derive(Debug, Clone, Default)]
+struct Spawn {
+    at_gate: bool,
+    runway: Option<String>,
+    position: Option<(usize, usize)>, // Entry tile of an arrival
+    mid_turnaround: bool,             // Start a plane at a gate on a random turnaround step
+}
+
+fn spawn_landing_aircraft(airport: &mut Airport, at_gate: bool) {
+    spawn_aircraft(
+        airport,
+        &Spawn {
+            at_gate,
+            ..Default::default()
+        },
+    );
+}
+
+fn spawn_aircraft(airport: &mut Airport, spawn: &Spawn) {
+    // Spawn new aircraft for landing
+    let runways = &airport.runways;
+    let num_planes = airport.planes.len();
+
+    let mut rng = rand::thread_rng();
+    let plane_name = match airport.unique_plane_name() {
+        Some(name) => name,
+        None => {
+            if let Ok(mut error) = ERROR.lock() {
+                error.message = "No flight numbers left for a new plane".to_string();
+                error.timer = AtomicUsize::new(5);
+            }
+            return;
+        }
+    };
+
+    let flight_type = airport.flight_types.choose(&mut rng).unwrap().clone();
+    let priority = match rng.gen::<f64>() {
+        chance if chance < airport.medevac_ratio => Priority::Medevac,
+        chance if chance < airport.medevac_ratio + airport.vip_ratio => Priority::Vip,
+        _ => Priority::Normal,
+    };
+    let favoured = match spawn.at_gate {
+        true => None,
+        false => airport.favoured_runway(),
+    };
+    let runway = match (&spawn.runway, favoured) {
+        (Some(name), _) => runways.get(name).cloned(),
+        (None, Some(favoured)) => Some(favoured),
+        (None, None) => airport.default_runway(),
+    };
+    let mut runway = match runway {
+        Some(runway) => runway,
+        None => {
+            if let Ok(mut error) = ERROR.lock() {
+                error.message = "No such runway for a new plane".to_string();
+                error.timer = AtomicUsize::new(5);
+            }
+            return;
+        }
+    };
+    let (position, current_action, heading) = match spawn.at_gate {
+        true => {
+            let random_gate = match airport.free_gates().choose(&mut rand::thread_rng()) {
+                Some(gate) => *gate,
+                None => {
+                    if let Ok(mut error) = ERROR.lock() {
+                        error.message = "No free gate for a new plane".to_string();
+                        error.timer = AtomicUsize::new(5);
+                    }
+                    return;
+                }
+            };
+            // Parked nose-in, facing away from the gate taxi line
+            let heading = MapPoint::Gate(random_gate.number.clone())
+                .check_for_gate_taxi_line_all_directions(
+                    &airport.map,
+                    random_gate.position,
+                    random_gate.number.clone(),
+                    true,
+                )
+                .1
+                .get_opposite_dir();
+            runway = airport.filed_runway(&random_gate.number);
+            // De-icing is left to the weather, as it is skipped unless it snows
+            let step = match spawn.mid_turnaround {
+                true => flight_type
+                    .turnaround()
+                    .into_iter()
+                    .filter(|step| *step != AtGateAction::DeIce)
+                    .collect::<Vec<AtGateAction>>()
+                    .choose(&mut rng)
+                    .cloned()
+                    .unwrap_or(AtGateAction::Standby),
+                false => AtGateAction::Standby,
+            };
+            (
+                random_gate.position,
+                Action::AtGate((random_gate.number.clone(), step)),
+                heading,
+            )
+        }
+        false => {
+            // Spread arrivals across the corridor, either side of the centreline
+            let (rows, cols) = airport.map.bounds();
+            // A runway the map does not have has no approach to fly
+            let Some((row, col)) = approach_entry(&airport.map, &runway) else {
+                return;
+            };
+            let shift = |centre: usize, offset: usize, size: usize| {
+                (centre + offset)
+                    .saturating_sub(airport.corridor_width / 2)
+                    .min(size - 1)
+            };
+            let tiles = match spawn.position {
+                Some(position) => vec![position],
+                None => (0..airport.corridor_width.max(1))
+                    .map(|offset| match runway.side {
+                        Direction::East | Direction::West => (shift(row, offset, rows), col),
+                        _ => (row, shift(col, offset, cols)),
+                    })
+                    .collect(),
+            };
+            // Only enter on a tile no other plane is on
+            let free_tiles = tiles
+                .into_iter()
+                .filter(|tile| {
+                    !airport
+                        .planes
+                        .iter()
+                        .any(|p| !p.out_of_map && p.footprint().contains(tile))
+                })
+                .collect::<Vec<(usize, usize)>>();
+            let position = match free_tiles.choose(&mut rng) {
+                Some(position) => *position,
+                // The whole corridor entry is taken, so try again next tick
+                None => {
+                    airport.next_arrival_tick = airport.tick + 1;
+                    return;
+                }
+            };
+            (position, Action::InAir, runway.side.clone())
+        }
+    };
+    let (altitude_ft, speed_kt) = match current_action {
+        Action::InAir => (
+            glide_path_altitude(&airport.map, position, &runway),
+            Normal::new(ARRIVAL_SPEED_KT.0, ARRIVAL_SPEED_KT.1)
+                .unwrap()
+                .sample(&mut rng),
+        ),
+        _ => (0.0, 0.0),
+    };
+
+    let airborne_since = match current_action {
+        Action::InAir => Some(airport.tick),
+        _ => None,
+    };
+    let turning_around = current_action == Action::InAir;
+    let plane = Plane {
+        id: num_planes + 1,
+        name: plane_name,
+        current_action,
+        position,
+        runway,
+        out_of_map: false,
+        diverted: false,
+        altitude_ft,
+        speed_kt,
+        heading,
+        stationary_ticks: 0,
+        pushback_held: false,
+        taxi_route: vec![],
+        landing_exit: None,
+        cleared_to_land: false,
+        on_final: false,
+        go_around_reason: None,
+        intention: String::new(),
+        on_ground_frequency: spawn.at_gate,
+        runway_entry_tick: None,
+        held_short_tick: None,
+        de_iced: false,
+        gate_arrival_tick: None,
+        cleared_to_cross: None,
+        heavy: rng.gen_bool(airport.heavy_ratio),
+        fuel_ticks: rng.gen_range(ARRIVAL_FUEL_TICKS.0..=ARRIVAL_FUEL_TICKS.1),
+        airborne_since,
+        turning_around,
+        flight_type,
+        priority,
+        immediate_takeoff: false,
+    };
+
+    airport.planes.push(plane);
+}
+
+// Parks departures at the given fraction of the gates, each somewhere in its turnaround
+fn prefill_gates(airport: &mut Airport, fraction: f64) {
+    let count = (airport.gates.len() as f64 * fraction).round() as usize;
+    for _ in 0..count {
+        spawn_aircraft(
+            airport,
+            &Spawn {
+                at_gate: true,
+                mid_turnaround: true,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+// Reads a table of "from -> to: chance" weather changes, skipping blank lines and # comments
+fn load_weather_table(table_path: &str) -> Result<Vec<WeatherTransition>, String> {
+    let table_file = File::open(table_path)
+        .map_err(|e| format!("Failed to open weather table {}: {}", table_path, e))?;
+    let mut table = vec![];
+    for line in BufReader::new(table_file).lines() {
+        let line = line.map_err(|e| format!("Failed to read line in weather table: {}", e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        table.push(line.parse::<WeatherTransition>()?);
+    }
+    Ok(table)
+}
+
+// Reads a bundle of "id = text" lines over the default wording, skipping blank lines and
+// # comments. A \n in the text starts a new line.
+fn load_messages(bundle_path: &str) -> Result<HashMap<String, String>, String> {
+    let bundle_file = File::open(bundle_path)
+        .map_err(|e| format!("Failed to open message bundle {}: {}", bundle_path, e))?;
+    let mut messages = default_messages();
+    for line in BufReader::new(bundle_file).lines() {
+        let line = line.map_err(|e| format!("Failed to read line in message bundle: {}", e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (id, text) = line.split_once('=').ok_or(format!(
+            "Message bundle lines must look like \"id = text\", not \"{}\"",
+            line
+        ))?;
+        let id = id.trim();
+        if !messages.contains_key(id) {
+            return Err(format!("Unknown message id {} in message bundle", id));
+        }
+        messages.insert(id.to_string(), text.trim().replace("\\n", "\n"));
+    }
+    Ok(messages)
+}
+
+// Reads a script of "tick: command" lines, skipping blank lines and # comments
+fn load_script(script_path: &str) -> Result<Vec<(usize, String)>, String> {
+    let script_file = File::open(script_path)
+        .map_err(|e| format!("Failed to open script file {}: {}", script_path, e))?;
+    let mut script = vec![];
+    for line in BufReader::new(script_file).lines() {
+        let line = line.map_err(|e| format!("Failed to read line in script: {}", e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (tick, command) = line.split_once(':').ok_or(format!(
+            "Script lines must look like \"tick: command\", not \"{}\"",
+            line
+        ))?;
+        let tick = tick
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("Failed to parse script tick in \"{}\"", line))?;
+        script.push((tick, command.trim().to_string()));
+    }
+    script.sort_by_key(|(tick, _)| *tick);
+    Ok(script)
+}
+
+// Sends the scripted commands due this tick to the game loop
+fn feed_script(script: &[(usize, String)], tick: usize, sender: &Sender<Command>) {
+    for (_, command) in script.iter().filter(|(at, _)| *at == tick) {
+        sender
+            .send(("script".to_string(), command.clone()))
+            .expect("Failed to send scripted command");
+    }
+}
+
+// Command text along with the client that sent it
+type Command = (String, String);
+
+// Accepts any number of command consoles, each read on its own thread
+fn user_input_thread(listener: TcpListener, token: Option<String>, sender: Sender<Command>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let token = token.clone();
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            handle_console(stream, token, sender);
+        });
+    }
+}
+
+// Forwards a console's commands to the game loop. With a token set, the console's first
+// line must be the token, and consoles that send the wrong one are disconnected.
+fn handle_console(stream: TcpStream, token: Option<String>, sender: Sender<Command>) {
+    let client = match stream.peer_addr() {
+        Ok(address) => address.to_string(),
+        Err(_) => return,
+    };
+    let replies = stream.try_clone();
+    let mut reader = BufReader::new(stream);
+    if let Some(token) = token {
+        let mut handshake = String::new();
+        if reader.read_line(&mut handshake).is_err() || handshake.trim() != token {
+            return;
+        }
+    }
+    // A console that stops reading its replies is dropped rather than holding up the game
+    if let Ok(replies) = replies {
+        let _ = replies.set_write_timeout(Some(Duration::from_millis(100)));
+        if let Ok(mut consoles) = CONSOLES.lock() {
+            consoles.insert(client.clone(), replies);
+        }
+    }
+    for line in reader.lines() {
+        let user_input = match line {
+            Ok(line) => line.trim().to_string(),
+            Err(_) => break,
+        };
+        // Send the user input to the main game loop through the channel
+        if sender.send((client.clone(), user_input)).is_err() {
+            break;
+        }
+    }
+    if let Ok(mut consoles) = CONSOLES.lock() {
+        consoles.remove(&client);
+    }
+}
+
+// Sends the token, if there is one, and then every line typed in to the simulator
+fn command_console(token: Option<String>) {
+    let mut stream = TcpStream::connect("localhost:8080")
+        .expect("Failed to connect, start the simulator with --sim first");
+    if let Some(token) = token {
+        stream.write_all(format!("{}\n", token).as_bytes()).unwrap();
+    }
+    // Print the result of each command as it comes back
+    let replies = BufReader::new(stream.try_clone().unwrap());
+    thread::spawn(move || {
+        for line in replies.lines().map_while(Result::ok) {
+            println!("{}", line);
+        }
+    });
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        stream.write_all(line.as_bytes()).unwrap();
+        stream.write_all(b"\n").unwrap();
+        stream.flush().unwrap();
+    }
+}
+
+// Where a running game takes its commands from, and how it speaks and keeps time
+struct SimIo<'a> {
+    script: &'a [(usize, String)],
+    sender: &'a Sender<Command>,
+    receiver: &'a Receiver<Command>,
+    tts: &'a mut dyn Speaker,
+    time: &'a Time,
+}
+
+// Runs the game until a crash or the tick limit
+fn run_simulation(
+    airport: &mut Airport,
+    score: &mut Score,
+    args: &Args,
+    io: SimIo,
+    interrupted: &AtomicBool,
+) {
+    while airport.tick < args.max_ticks.unwrap_or(usize::MAX) && !interrupted.load(Ordering::SeqCst)
+    {
+        feed_script(io.script, airport.tick, io.sender);
+        if args.autopilot {
+            for command in autopilot_commands(airport) {
+                io.sender
+                    .send(("autopilot".to_string(), command))
+                    .expect("Failed to send autopilot command");
+            }
+        }
+        let spawn_plane = is_arrival_due(airport);
+        update_game_state(airport, spawn_plane, score, io.receiver, io.tts, None, None);
+        // Sleep for a bit
+        thread::sleep(Duration::from_millis(io.time.step_duration));
+        if score.crash > 0 {
+            break;
+        }
+    }
+}
+
+// Main function to run the game
+fn main() {
+    let args = Args::parse();
+    if let Some(map_path) = &args.validate_map {
+        let spacing = Spacing {
+            top_bottom: 0,
+            left_right: 0,
+        };
+        let problems = match build_airport_map(map_path, spacing) {
+            Ok(map) => validate_map(&map),
+            Err(e) => vec![e],
+        };
+        if problems.is_empty() {
+            println!("{}: no problems found", map_path);
+            return;
+        }
+        println!("{}: {} problem(s) found", map_path, problems.len());
+        for problem in problems.iter() {
+            println!("  {}", problem);
+        }
+        std::process::exit(1);
+    }
+    if !args.sim {
+        command_console(args.token.clone());
+        return;
+    }
+
+    // The render file is rewritten every frame, so check it can be written before starting
+    if let Some(path) = &args.render_to {
+        if let Err(e) = File::create(path) {
+            eprintln!("Could not write the render file {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+
+    // Initialize and run your ATC game here
+    let mut airport = match construct_airport_from(&args, "./src/airport.map") {
+        Ok(airport) => airport,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let time: Time = Time {
+        step_duration: args.tick_ms,
+    };
+    let mut score = Score {
+        takeoff: 0,
+        crash: 0,
+        missed_approach: 0,
+        diversion: 0,
+    };
+
+    // Channel for communication between threads
+    let (sender, receiver): (Sender<Command>, Receiver<Command>) = channel();
+    let script_sender = sender.clone();
+    let script = match &args.script {
+        Some(script_path) => match load_script(script_path) {
+            Ok(script) => script,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => vec![],
+    };
+
+    // Separate thread for handling user input
+    let listener = TcpListener::bind("localhost:8080").expect("Failed to bind address");
+    let token = args.token.clone();
+    std::thread::spawn(move || {
+        user_input_thread(listener, token, sender);
+    });
+
+    // TTS
+    let mut tts = Tts::default().expect("Could not initialize TTS");
+
+    // On Ctrl-C, finish the current tick and wrap up as if the simulation had ended
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupt = interrupted.clone();
+    ctrlc::set_handler(move || interrupt.store(true, Ordering::SeqCst))
+        .expect("Could not set the Ctrl-C handler");
+
+    // Spawn the first aircraft at a gate
+    prefill_gates(&mut airport, args.prefill_gates);
+    spawn_landing_aircraft(&mut airport, true);
+
+    run_simulation(
+        &mut airport,
+        &mut score,
+        &args,
+        SimIo {
+            script: &script,
+            sender: &script_sender,
+            receiver: &receiver,
+            tts: &mut tts,
+            time: &time,
+        },
+        &interrupted,
+    );
+
+    // The end of the game goes wherever the game was drawn
+    let mut out = screen(&args.render_to, false);
+    restore_terminal(&mut out);
+    writeln!(out, "{}", summary_text(&airport, &score)).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Arguments as if the simulator was started with only --sim
+    fn args() -> Args {
+        Args {
+            sim: true,
+            spacing_tb: 2,
+            spacing_lr: 20,
+            auto_gate: false,
+            autopilot: false,
+            wind_clear: "10,1,0,20".parse().unwrap(),
+            wind_rain: "30,5,20,40".parse().unwrap(),
+            wind_inclement: "50,10,50,60".parse().unwrap(),
+            wind_thunderstorm: "40,10,25,60".parse().unwrap(),
+            wind_snow: "20,5,10,35".parse().unwrap(),
+            quiet_hours: vec![],
+            arrivals_clear: 1.0,
+            arrivals_rain: 0.7,
+            arrivals_inclement: 0.3,
+            arrivals_thunderstorm: 0.0,
+            arrivals_snow: 0.5,
+            arr_dep_ratio: 1.0,
+            prefill_gates: 0.0,
+            wind_limit: 35.0,
+            heavy_ratio: 0.0,
+            medevac_ratio: 0.0,
+            vip_ratio: 0.0,
+            crosswind_limit: vec![],
+            tailwind_limit: vec![],
+            stuck_after: 10,
+            hold_short_timeout: None,
+            hold_stuck: false,
+            crossing_clearance: false,
+            detect_swaps: false,
+            alerts: false,
+            collision_message: None,
+            lang: None,
+            script: None,
+            transcript: None,
+            speak_errors: false,
+            debug: false,
+            stable_gate: 5,
+            final_fix: None,
+            alias: vec![],
+            arrival_jitter: 0,
+            gate_runway: vec![],
+            departure_runways: DepartureRunways::Gate,
+            flight_numbers: vec![],
+            reserve_name: vec![],
+            corridor_width: 1,
+            render_to: None,
+            validate_map: None,
+            token: None,
+            render_interval: 1,
+            weather_table: None,
+            weather_interval: 1,
+            tick_ms: 1000,
+            flight_types: vec![FlightType::Mixed],
+            units: Units::Imperial,
+            phraseology: Phraseology::Standard,
+            sort: PlaneSort::Id,
+            layout: Layout::Stacked,
+            holding_capacity: None,
+            max_airborne: None,
+            predict_conflicts: None,
+            readback_ratio: 0.0,
+            arrival_spacing: None,
+            max_ticks: None,
+        }
+    }
+
+    // Keeps what would have been read out
+    impl Speaker for Vec<String> {
+        fn say(&mut self, text: String) {
+            self.push(text);
+        }
+    }
+
+    // Builds the airport from the shipped map
+    fn construct_airport(args: &Args) -> Airport {
+        construct_airport_from(args, "./src/airport.map").expect("Failed to construct the airport")
+    }
+
+    // Drives a plane from gate standby through pushback, taxi, line-up and takeoff.
+    // Returns once the plane has left the map, or an error if it gets stuck on the way.
+    fn drive_departure(airport: &mut Airport, plane_id: usize) -> Result<(), String> {
+        let plane = airport
+            .planes
+            .iter()
+            .find(|p| p.id == plane_id)
+            .ok_or("Plane not found")?;
+        let (name, runway) = (plane.name.clone(), plane.runway.name);
+
+        advance_until(airport, plane_id, |p| {
+            matches!(p.current_action, Action::AtGate((_, AtGateAction::Standby)))
+        })?;
+        issue_command(airport, format!("p {}", name)).map_err(|e| e.reason)?;
+        advance_until(airport, plane_id, |p| {
+            matches!(p.current_action, Action::HoldPosition)
+        })?;
+        issue_command(airport, format!("hs {} {}", name, runway)).map_err(|e| e.reason)?;
+        advance_until(airport, plane_id, |p| {
+            matches!(p.current_action, Action::HoldPosition)
+        })?;
+        issue_command(airport, format!("tor {} {}", name, runway)).map_err(|e| e.reason)?;
+        advance_until(airport, plane_id, |p| {
+            matches!(p.current_action, Action::TaxiOntoRunway(0))
+        })?;
+        issue_command(airport, format!("t {} {}", name, runway)).map_err(|e| e.reason)?;
+        advance_until(airport, plane_id, |p| p.out_of_map)
+    }
+
+    // Advances the simulation a tick at a time until the plane satisfies `done`, failing if it
+    // stops making progress
+    fn advance_until(
+        airport: &mut Airport,
+        plane_id: usize,
+        done: impl Fn(&Plane) -> bool,
+    ) -> Result<(), String> {
+        const STUCK_AFTER: usize = 10;
+        let mut last_state = None;
+        let mut unchanged = 0;
+        loop {
+            let plane = airport
+                .planes
+                .iter()
+                .find(|p| p.id == plane_id)
+                .ok_or("Plane not found")?;
+            if done(plane) {
+                return Ok(());
+            }
+            let state = Some((plane.position, plane.current_action.clone()));
+            if state == last_state {
+                unchanged += 1;
+                if unchanged >= STUCK_AFTER {
+                    return Err(format!(
+                        "{} is stuck at {:?} while {:?}",
+                        plane.name, plane.position, plane.current_action
+                    ));
+                }
+            } else {
+                unchanged = 0;
+                last_state = state;
+            }
+            update_aircraft_position(airport, None);
+            airport.tick += 1;
+        }
+    }
+
+    fn score() -> Score {
+        Score {
+            takeoff: 0,
+            crash: 0,
+            missed_approach: 0,
+            diversion: 0,
+        }
+    }
+
+    // A path in the temp dir that no other test, nor another run of the tests, uses
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+        std::env::temp_dir().join(format!("roger_{}_{}_{}", std::process::id(), count, name))
+    }
+
+    #[test]
+    fn small_spacing_bounds() {
+        let mut a = args();
+        a.spacing_tb = 0;
+        a.spacing_lr = 1;
+        let mut airport = construct_airport(&a);
+        let (rows, cols) = airport.map.bounds();
+        assert_eq!((rows, cols), (airport.map.length, airport.map.width + 2));
+        assert_eq!(airport.map.map.len(), rows);
+        assert!(airport.map.map.iter().all(|row| row.len() == cols));
+
+        // Arrivals enter on the left edge and departures leave over the right one
+        spawn_landing_aircraft(&mut airport, false);
+        assert_eq!(airport.planes[0].position.1, 0);
+        airport.planes.clear();
+        spawn_landing_aircraft(&mut airport, true);
+        let departure = &mut airport.planes[0];
+        // Start of runway 1, on the top row with one column of spacing before it
+        departure.position = (0, 1);
+        departure.runway = airport.runways["1"].clone();
+        departure.current_action = Action::Takeoff;
+        let mut last = departure.position;
+        for _ in 0..cols {
+            update_aircraft_position(&mut airport, None);
+            if airport.planes[0].out_of_map {
+                break;
+            }
+            last = airport.planes[0].position;
+        }
+        assert!(airport.planes[0].out_of_map);
+        assert_eq!(last.1, cols - 1);
+    }
+
+    #[test]
+    fn auto_gate_after_landing() {
+        let mut a = args();
+        a.auto_gate = true;
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].speed_kt = 140.0;
+        airport.planes[0].cleared_to_land = true;
+        for _ in 0..200 {
+            update_aircraft_position(&mut airport, None);
+            if matches!(airport.planes[0].current_action, Action::AtGate(_)) {
+                break;
+            }
+        }
+        let gate = match &airport.planes[0].current_action {
+            Action::AtGate((gate, _)) => gate.clone(),
+            action => panic!("{:?}", action),
+        };
+        assert_eq!(airport.planes[0].position, airport.gates[&gate].position);
+    }
+
+    #[test]
+    fn full_departure_from_gate() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, true);
+        let id = airport.planes[0].id;
+        drive_departure(&mut airport, id).unwrap();
+        assert!(airport.planes[0].out_of_map);
+        assert!(airport.tick > 0);
+    }
+
+    #[test]
+    fn wind_within_configured_bounds() {
+        let airport = construct_airport(&args());
+        for condition in [
+            WeatherCondition::Clear,
+            WeatherCondition::Rain,
+            WeatherCondition::InclementWeather,
+            WeatherCondition::Thunderstorm,
+            WeatherCondition::Snow,
+        ] {
+            let distribution = airport.weather.wind.for_condition(&condition);
+            for _ in 0..1000 {
+                let speed = distribution.sample();
+                assert!(
+                    (distribution.min..=distribution.max).contains(&speed),
+                    "{:?} {}",
+                    condition,
+                    speed
+                );
+            }
+        }
+        let windy = "45,2,40,50".parse::<WindDistribution>().unwrap();
+        assert!((0..1000).all(|_| (40.0..=50.0).contains(&windy.sample())));
+        assert!("10,1,0".parse::<WindDistribution>().is_err());
+        for invalid in ["nan,1,0,20", "10,inf,0,20", "10,-1,0,20"] {
+            assert!(invalid.parse::<WindDistribution>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn extreme_wind_samples_clamped() {
+        // Far outside either bound, every sample lands on the bound
+        let calm = "-100,0,0,60".parse::<WindDistribution>().unwrap();
+        assert_eq!(calm.sample(), 0.0);
+        let gale = "500,0,0,60".parse::<WindDistribution>().unwrap();
+        assert_eq!(gale.sample(), MAX_WIND_SPEED);
+        let wide = "30,1000,5,55".parse::<WindDistribution>().unwrap();
+        let samples = (0..1000).map(|_| wide.sample()).collect::<Vec<f64>>();
+        assert!(samples.iter().all(|s| (5.0..=55.0).contains(s)));
+        assert!(samples.contains(&5.0) && samples.contains(&55.0));
+        // Bounds outside 0-60 knots are refused
+        assert!("10,1,-5,20".parse::<WindDistribution>().is_err());
+        assert!("10,1,0,61".parse::<WindDistribution>().is_err());
+        assert!("10,1,20,10".parse::<WindDistribution>().is_err());
+        // So is a distribution that is not a number, for any of the weather conditions
+        for flag in [
+            "--wind-clear",
+            "--wind-rain",
+            "--wind-inclement",
+            "--wind-thunderstorm",
+            "--wind-snow",
+        ] {
+            assert!(Args::try_parse_from(["atc", flag, "nan,1,0,20"]).is_err());
+            assert!(Args::try_parse_from(["atc", flag, "10,1,0,20"]).is_ok());
+        }
+    }
+
+    #[test]
+    fn glide_path_descends_to_touchdown() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].speed_kt = 140.0;
+        airport.planes[0].cleared_to_land = true;
+        let mut last = airport.planes[0].altitude_ft;
+        assert!(last > THRESHOLD_CROSSING_FT);
+        while airport.planes[0].current_action == Action::InAir {
+            update_aircraft_position(&mut airport, None);
+            let plane = &airport.planes[0];
+            if plane.current_action == Action::InAir {
+                assert!(plane.altitude_ft < last, "{} {}", plane.altitude_ft, last);
+                last = plane.altitude_ft;
+            }
+        }
+        assert!(matches!(airport.planes[0].current_action, Action::Land(_)));
+        // One tile out the plane is at most a tile's descent above the runway
+        assert!(last <= GLIDE_SLOPE_FT_PER_TILE);
+        assert_eq!(airport.planes[0].altitude_ft, 0.0);
+    }
+
+    #[test]
+    fn list_mentions_every_plane() {
+        let mut airport = construct_airport(&args());
+        assert_eq!(list_planes(&airport), "No active planes");
+        spawn_landing_aircraft(&mut airport, false);
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        let list = list_planes(&airport);
+        assert_eq!(list.lines().count(), 3);
+        for plane in airport.planes.iter() {
+            let entry = format!("{} {} runway", plane.id, plane.name);
+            assert!(
+                list.lines().any(|line| line.starts_with(&entry)),
+                "{}",
+                list
+            );
+        }
+    }
+
+    #[test]
+    fn quiet_hours_restrict_takeoff() {
+        let mut a = args();
+        a.quiet_hours = vec!["10-20".parse().unwrap()];
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        // Runway 1 of the shipped map marked noise sensitive, as by R1EQ in a map file
+        airport.runways.get_mut("1").unwrap().noise_sensitive = true;
+        spawn_landing_aircraft(&mut airport, true);
+        let runway = airport.runways["1"].clone();
+        airport.planes[0].current_action = Action::TaxiOntoRunway(0);
+        let takeoff = format!("t {} 1", airport.planes[0].name);
+        airport.tick = 15;
+        let err = parse_user_input(takeoff.clone(), &airport)
+            .unwrap_err()
+            .reason;
+        assert_eq!(
+            err,
+            "Runway 1 is closed for departures during quiet hours, hold until they end"
+        );
+        // A runway that is not noise sensitive is offered instead
+        let mut other = runway.clone();
+        other.name = 2;
+        other.noise_sensitive = false;
+        airport.runways.insert("2".to_string(), other);
+        let err = parse_user_input(takeoff.clone(), &airport)
+            .unwrap_err()
+            .reason;
+        assert!(err.ends_with("use runway 2"), "{}", err);
+        // Unless it is closed
+        airport.closed_runways.push(2);
+        let err = parse_user_input(takeoff.clone(), &airport)
+            .unwrap_err()
+            .reason;
+        assert!(err.ends_with("hold until they end"), "{}", err);
+        airport.tick = 20;
+        assert!(parse_user_input(takeoff, &airport).is_ok());
+    }
+
+    #[test]
+    fn heading_follows_movement() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (2, 40);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldPosition;
+        let name = airport.planes[0].name.clone();
+        issue_command(&mut airport, format!("t2g {} 3", name)).unwrap();
+        let mut moves = 0;
+        while !matches!(airport.planes[0].current_action, Action::AtGate(_)) && moves < 100 {
+            let previous = airport.planes[0].position;
+            update_aircraft_position(&mut airport, None);
+            let plane = &airport.planes[0];
+            if let Some(direction) = Direction::between(previous, plane.position) {
+                assert_eq!(plane.heading, direction);
+            }
+            moves += 1;
+        }
+        assert!(matches!(
+            airport.planes[0].current_action,
+            Action::AtGate(_)
+        ));
+        // Pushing back, the plane faces away from where it moves
+        airport.planes[0].current_action = Action::AtGate(("3".to_string(), AtGateAction::Standby));
+        issue_command(&mut airport, format!("p {}", name)).unwrap();
+        let previous = airport.planes[0].position;
+        update_aircraft_position(&mut airport, None);
+        let plane = &airport.planes[0];
+        let direction = Direction::between(previous, plane.position).unwrap();
+        assert_eq!(plane.heading, direction.get_opposite_dir());
+    }
+
+    #[test]
+    fn close_and_reopen_runway() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, false);
+        spawn_landing_aircraft(&mut airport, true);
+        let landing = format!("l {} 1", airport.planes[0].name);
+        let departure = format!("tor {} 1", airport.planes[1].name);
+        airport.planes[1].current_action = Action::HoldPosition;
+        assert_eq!(
+            airport.set_closed("1", true),
+            Ok("Runway 1 is now closed.".to_string())
+        );
+        // Neither an arrival nor a departure is cleared onto it
+        let err = parse_user_input(landing.clone(), &airport)
+            .unwrap_err()
+            .reason;
+        assert_eq!(err, "Runway 1 is closed");
+        let err = parse_user_input(departure.clone(), &airport)
+            .unwrap_err()
+            .reason;
+        assert_eq!(err, "Runway 1 is closed");
+        airport.set_closed("1", false).unwrap();
+        let landed = parse_user_input(landing, &airport).unwrap();
+        assert!(landed.cleared_to_land);
+        assert!(parse_user_input(departure, &airport).is_ok());
+        assert!(airport.set_closed("9", true).is_err());
+    }
+
+    #[test]
+    fn help_describes_glyphs_and_commands() {
+        let help = help_text();
+        let runway = format!(
+            "{} {}\tRunway",
+            MapPoint::Runway((1, Direction::North)).glyph(),
+            MapPoint::Runway((1, Direction::East)).glyph()
+        );
+        assert!(help.contains(&runway), "{}", help);
+        let taxiway = MapPoint::Taxiway(("1".to_string(), Direction::North));
+        assert!(help
+            .lines()
+            .any(|line| line.starts_with(taxiway.glyph()) && line.contains("Taxiway")));
+        for (syntax, _) in COMMANDS.iter() {
+            assert!(help.contains(syntax), "{}", syntax);
+        }
+    }
+
+    #[test]
+    fn fewer_arrivals_in_bad_weather() {
+        let spawns = |condition: WeatherCondition| {
+            let mut airport = construct_airport(&args());
+            airport.weather.condition = condition;
+            let mut due = 0;
+            for tick in 0..20000 {
+                airport.tick = tick;
+                if is_arrival_due(&mut airport) {
+                    due += 1;
+                }
+            }
+            due
+        };
+        let clear = spawns(WeatherCondition::Clear);
+        let inclement = spawns(WeatherCondition::InclementWeather);
+        assert!(clear > 0);
+        assert!(inclement * 2 < clear, "{} {}", inclement, clear);
+        assert_eq!(spawns(WeatherCondition::Thunderstorm), 0);
+    }
+
+    #[test]
+    fn stuck_in_dead_end() {
+        let mut a = args();
+        a.stuck_after = 3;
+        a.hold_stuck = true;
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (2, 40);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldPosition;
+        let name = airport.planes[0].name.clone();
+        issue_command(&mut airport, format!("t2g {} 3", name)).unwrap();
+        let on_taxiway = |airport: &Airport| {
+            let (row, col) = airport.planes[0].position;
+            matches!(airport.map.map[row][col], MapPoint::Taxiway(_))
+        };
+        while !on_taxiway(&airport) {
+            update_aircraft_position(&mut airport, None);
+        }
+        // Cut the taxiway just ahead of the plane, leaving it in a dead end
+        let (row, col) = airport.planes[0].position;
+        let ahead = match &airport.map.map[row][col] {
+            MapPoint::Taxiway((_, direction)) => direction.clone().go(&airport.map, (row, col)),
+            point => panic!("{:?}", point),
+        };
+        let (row, col) = ahead.unwrap();
+        airport.map.map[row][col] = MapPoint::Empty;
+        let mut warnings = vec![];
+        for _ in 0..20 {
+            update_aircraft_position(&mut airport, None);
+            warnings.extend(detect_stuck_aircraft(&mut airport));
+            if airport.planes[0].current_action == Action::HoldPosition {
+                break;
+            }
+        }
+        assert_eq!(airport.planes[0].current_action, Action::HoldPosition);
+        assert_eq!(airport.planes[0].stationary_ticks, a.stuck_after + 1);
+        assert_eq!(
+            warnings,
+            vec![format!(
+                "{} has not moved for 4 ticks while TaxiToGate(\"3\"), holding position",
+                name
+            )]
+        );
+    }
+
+    #[test]
+    fn script_runs_on_its_ticks() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        let path = temp_path("script.txt");
+        std::fs::write(
+            &path,
+            format!(
+                "# Push back, then line up\n\n1: p {}\n6: hs {} 1\n",
+                name, name
+            ),
+        )
+        .unwrap();
+        let script = load_script(path.to_str().unwrap()).unwrap();
+        assert_eq!(script.len(), 2);
+        let bad = temp_path("bad_script.txt");
+        std::fs::write(&bad, "soon: p AA100\n").unwrap();
+        assert_eq!(
+            load_script(bad.to_str().unwrap()),
+            Err("Failed to parse script tick in \"soon: p AA100\"".to_string())
+        );
+        let (sender, receiver) = channel();
+        let mut spoken: Vec<String> = vec![];
+        let mut score = score();
+        let mut heard = vec![];
+        for _ in 0..8 {
+            // Keep the weather from turning to snow, which needs de-icing before pushback
+            airport.weather.condition = WeatherCondition::Clear;
+            feed_script(&script, airport.tick, &sender);
+            let tick = airport.tick;
+            update_game_state(
+                &mut airport,
+                false,
+                &mut score,
+                &receiver,
+                &mut spoken,
+                None,
+                None,
+            );
+            heard.extend(spoken.drain(..).map(|text| (tick, text)));
+        }
+        assert_eq!(heard.len(), 2, "{:?}", heard);
+        assert_eq!(heard[0].0, 1);
+        assert!(heard[0].1.contains("pushback approved"), "{:?}", heard);
+        assert_eq!(heard[1].0, 6);
+        assert!(heard[1].1.contains("hold short"), "{:?}", heard);
+    }
+
+    #[test]
+    fn pushback_waits_for_lane() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, false);
+        let gate = match &airport.planes[0].current_action {
+            Action::AtGate((gate, _)) => gate.clone(),
+            action => panic!("{:?}", action),
+        };
+        let (row, col) = airport.gates[&gate].position;
+        // Another plane stands on the gate line right behind the departure
+        airport.planes[1].position = (row - 1, col);
+        airport.planes[1].altitude_ft = 0.0;
+        airport.planes[1].current_action = Action::HoldPosition;
+        let name = airport.planes[0].name.clone();
+        airport.planes[0] = parse_user_input(format!("p {}", name), &airport).unwrap();
+        for _ in 0..5 {
+            update_aircraft_position(&mut airport, None);
+            assert_eq!(airport.planes[0].position, (row, col));
+            assert_eq!(airport.planes[0].current_action, Action::Pushback);
+            assert!(airport.planes[0].pushback_held);
+        }
+        // Once the lane clears, the pushback goes ahead
+        airport.planes[1].out_of_map = true;
+        update_aircraft_position(&mut airport, None);
+        assert_eq!(airport.planes[0].position, (row - 1, col));
+        assert!(!airport.planes[0].pushback_held);
+    }
+
+    #[test]
+    fn airlines_drawn_in_their_colours() {
+        assert_ne!(airline_color("AA100"), airline_color("DL200"));
+        assert_eq!(airline_color("ZZ100"), DEFAULT_COLOR);
+
+        // Each plane's glyph on the map is drawn in its airline's colour
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[0].name = "AA100".to_string();
+        airport.planes[1].name = "DL200".to_string();
+        for plane in airport.planes.iter() {
+            let drawn = plane_cell(plane);
+            assert!(drawn.starts_with(airline_color(&plane.name)), "{:?}", drawn);
+            assert!(drawn.contains(plane.heading.plane_glyph()), "{:?}", drawn);
+        }
+        assert_ne!(
+            plane_cell(&airport.planes[0]),
+            plane_cell(&airport.planes[1])
+        );
+    }
+
+    #[test]
+    fn unstable_approach_goes_around() {
+        for (speed, stable) in [(400.0, false), (140.0, true)] {
+            let mut airport = construct_airport(&args());
+            airport.weather.condition = WeatherCondition::Clear;
+            airport.weather.wind_speed = 0.0;
+            spawn_landing_aircraft(&mut airport, false);
+            airport.planes[0].speed_kt = speed;
+            airport.planes[0].cleared_to_land = true;
+            for _ in 0..60 {
+                update_aircraft_position(&mut airport, None);
+                if airport.planes[0].current_action != Action::InAir {
+                    break;
+                }
             }
-            Action::AtGate((_, ref mut atgate_action)) => {
-                let actions = all::<AtGateAction>().collect::<Vec<_>>();
-                let mut iter = actions.iter();
-                while let Some(action) = iter.next() {
-                    if action.to_owned() == atgate_action.to_owned() {
-                        match iter.next() {
-                            Some(next_action) => *atgate_action = next_action.to_owned(),
-                            None => *atgate_action = AtGateAction::Standby,
-                        }
-                    }
+            match stable {
+                true => assert!(matches!(airport.planes[0].current_action, Action::Land(_))),
+                false => {
+                    assert_eq!(airport.planes[0].current_action, Action::GoAround);
+                    let gate = airport.stable_gate;
+                    let distance = distance_to_threshold(
+                        &airport.map,
+                        airport.planes[0].position,
+                        &airport.planes[0].runway,
+                    );
+                    assert_eq!(distance, Some(gate));
                 }
             }
         }
     }
-}
 
-// Function to detect and handle collisions
-fn detect_and_handle_collisions(airport: &mut Airport, score: &mut Score) {
-    let fleet = airport.planes.clone();
-    let mut crashed_planes = None;
-    for (i, plane) in fleet.iter().enumerate() {
-        for another_plane in fleet.iter().skip(i + 1) {
-            if plane.position == another_plane.position
-                && plane.id != another_plane.id
-                && plane.out_of_map == false
-                && another_plane.out_of_map == false
-            {
-                crashed_planes = Some((plane, another_plane));
+    #[test]
+    fn alias_behaves_like_command() {
+        let mut a = args();
+        a.alias = vec![("push".to_string(), "p".to_string())];
+        let mut airport = construct_airport(&a);
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        assert_eq!(
+            airport.expand_alias(&format!("push {} 1", name)),
+            format!("p {} 1", name)
+        );
+        let direct = parse_user_input(format!("p {}", name), &airport);
+        let via_alias = parse_user_input(airport.expand_alias(&format!("push {}", name)), &airport);
+        assert!(direct.is_ok());
+        assert_eq!(
+            direct.unwrap().current_action,
+            via_alias.unwrap().current_action
+        );
+
+        // "land" clears an arrival to land just as "l" does
+        let mut a = args();
+        a.alias = vec![("land".to_string(), "l".to_string())];
+        let mut airport = construct_airport(&a);
+        spawn_landing_aircraft(&mut airport, false);
+        let name = airport.planes[0].name.clone();
+        let direct = parse_user_input(format!("l {} 1", name), &airport).unwrap();
+        let land = airport.expand_alias(&format!("land {} 1", name));
+        let via_alias = parse_user_input(land, &airport).unwrap();
+        assert!(via_alias.cleared_to_land);
+        assert_eq!(
+            (direct.current_action, direct.landing_exit),
+            (via_alias.current_action, via_alias.landing_exit)
+        );
+    }
+
+    #[test]
+    fn jitter_varies_arrival_gaps() {
+        let gaps = |jitter: usize| {
+            let mut a = args();
+            a.arrival_jitter = jitter;
+            let mut airport = construct_airport(&a);
+            let mut gaps = HashSet::new();
+            for tick in 0..3000 {
+                airport.tick = tick;
+                if tick >= airport.next_arrival_tick {
+                    is_arrival_due(&mut airport);
+                    gaps.insert(airport.next_arrival_tick - tick);
+                }
+            }
+            gaps
+        };
+        assert_eq!(gaps(0), HashSet::from([60]));
+        let jittered = gaps(5);
+        assert!(jittered.len() > 1);
+        assert!(jittered.iter().all(|gap| (55..=65).contains(gap)));
+    }
+
+    #[test]
+    fn taxi_via_named_taxiways() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (2, 40);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldPosition;
+        let name = airport.planes[0].name.clone();
+        // Taxiway 1 does not lead to gate 3 from here, and the route takes taxiway 4 as well
+        // as taxiway 7
+        for via in ["1", "7", "7,4"] {
+            let command = format!("t2g {} 3 via {}", name, via);
+            assert!(parse_user_input(command, &airport).is_err());
+        }
+        let plane = parse_user_input(format!("t2g {} 3 via T4,7", name), &airport).unwrap();
+        assert_eq!(plane.taxi_route, vec!["4", "7"]);
+        airport.planes[0] = plane;
+        let mut taxiways: Vec<String> = vec![];
+        for _ in 0..200 {
+            update_aircraft_position(&mut airport, None);
+            let (row, col) = airport.planes[0].position;
+            if let MapPoint::Taxiway((taxiway, _)) = &airport.map.map[row][col] {
+                if taxiways.last() != Some(taxiway) {
+                    taxiways.push(taxiway.clone());
+                }
+            }
+            if matches!(airport.planes[0].current_action, Action::AtGate(_)) {
                 break;
             }
         }
+        assert_eq!(taxiways, vec!["4", "7"]);
+        assert!(matches!(
+            airport.planes[0].current_action,
+            Action::AtGate(_)
+        ));
     }
 
-    // Take appropriate actions in response to collisions
-    if crashed_planes.is_some() {
-        let (plane1, plane2) = crashed_planes.unwrap();
-        let mut stdout = stdout();
-        let collision_message = format!(
-            "🎧 Attention, Air Traffic Control, this is Ground Operations. \
-            We have a Code 34 incident on the tarmac involving aircraft {} and {}. \
-            Two aircraft have come into contact. \
-            Emergency services have been alerted and are en route. \
-            All ground movement is currently halted. \
-            Please hold all departures and redirect incoming traffic to alternate taxiways. \
-            We will update as more information becomes available. Over.",
-            plane1.name, plane2.name
+    #[test]
+    fn gate_runway_names_pushback_runway() {
+        let mut airport = construct_airport(&args());
+        let runway = Runway {
+            name: 2,
+            heading: 180,
+            ..airport.runways["1"].clone()
+        };
+        airport.runways.insert("2".to_string(), runway);
+        airport
+            .gate_runways
+            .insert("4".to_string(), "2".to_string());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[0].current_action = Action::AtGate(("4".to_string(), AtGateAction::Standby));
+        let name = airport.planes[0].name.clone();
+        let plane = parse_user_input(format!("p {}", name), &airport).unwrap();
+        assert_eq!(plane.runway.name, 2);
+        assert!(create_atc_clearance(&airport, &plane).contains("expect runway one eight"));
+    }
+
+    #[test]
+    fn gate_runway_must_be_usable() {
+        let mut a = args();
+        // Runway 0 marks runway ends on the map and is not one planes can be given
+        a.gate_runway = vec![("4".to_string(), "0".to_string())];
+        let built = construct_airport_from(&a, "./src/airport.map");
+        assert_eq!(
+            built.err(),
+            Some("Runway 0 in --gate-runway not found".to_string())
         );
-        stdout.write_all(collision_message.as_bytes()).unwrap();
+        a.gate_runway = vec![("9".to_string(), "1".to_string())];
+        let built = construct_airport_from(&a, "./src/airport.map");
+        assert_eq!(
+            built.err(),
+            Some("Gate 9 in --gate-runway not found".to_string())
+        );
+    }
 
-        score.crash += 1;
+    #[test]
+    fn max_ticks_ends_with_summary() {
+        let mut a = args();
+        a.max_ticks = Some(5);
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        let mut score = score();
+        let (sender, receiver) = channel();
+        run_simulation(
+            &mut airport,
+            &mut score,
+            &a,
+            SimIo {
+                script: &[],
+                sender: &sender,
+                receiver: &receiver,
+                tts: &mut Vec::<String>::new(),
+                time: &Time { step_duration: 0 },
+            },
+            &AtomicBool::new(false),
+        );
+        assert_eq!(airport.tick, 5);
+        let summary = summary_text(&airport, &score);
+        assert!(summary.starts_with("Simulation summary"));
+        assert!(summary.contains("Ticks:\t\t5"));
     }
-}
 
-// Function to handle ground staff alerts
-fn _handle_ground_alerts(_airport: &mut Airport, _alert: _GroundAlert) {
-    // Take appropriate actions in response to ground staff alerts
-}
+    #[test]
+    fn landing_rolls_to_assigned_exit() {
+        for (exit, expected) in [(None, "8"), (Some(4), "4")] {
+            let a = args();
+            let mut airport = construct_airport(&a);
+            airport.weather.condition = WeatherCondition::Clear;
+            airport.weather.wind_speed = 0.0;
+            // A second exit, before taxiway 4 on the roll
+            let col = a.spacing_lr + 15;
+            for row in a.spacing_tb + 1..a.spacing_tb + 4 {
+                airport.map.map[row][col] = MapPoint::Taxiway(("8".to_string(), Direction::South));
+            }
+            spawn_landing_aircraft(&mut airport, false);
+            airport.planes[0].speed_kt = 140.0;
+            let command = match exit {
+                Some(exit) => format!("l {} 1 t{}", airport.planes[0].name, exit),
+                None => format!("l {} 1", airport.planes[0].name),
+            };
+            airport.planes[0] = parse_user_input(command, &airport).unwrap();
+            let id = airport.planes[0].id;
+            advance_until(&mut airport, id, |plane| {
+                plane.current_action == Action::HoldPosition
+            })
+            .unwrap();
+            let (row, col) = airport.planes[0].position;
+            assert!(
+                matches!(&airport.map.map[row][col], MapPoint::Taxiway((taxiway, _)) if *taxiway == expected),
+                "{:?}",
+                airport.map.map[row][col]
+            );
+        }
+    }
 
-fn parse_user_input(
-    command: String,
-    planes: &Vec<Plane>,
-    runways: &HashMap<String, Runway>,
-    weather: &Weather,
-) -> Result<Plane, String> {
-    /*
-        Language is:
-        l <aircraft> <runway_number>        : Landing at runway X
-        t <aircraft> <runway_number>        : Takeoff from runway X
-        hp <aircraft>                       : Hold position
-        p <aircraft>                        : Pushback
-        tor <aircraft> <runway_number>      : Taxi onto runway X
-        hs <aircraft> <runway_number>       : Hold short of runway X
-        t2g <aircraft> <gate_number>        : Taxi to gate X
+    #[test]
+    fn arrivals_enter_from_runway_side() {
+        let mut airport = construct_airport(&args());
+        let (_, cols) = airport.map.bounds();
+        spawn_landing_aircraft(&mut airport, false);
+        assert_eq!(airport.planes[0].position.1, 0);
 
-        TODO:
-        t2t <aircraft> <terminal_number>    : Taxi to terminal X
-    */
-    let command = command.split_whitespace().collect::<Vec<_>>();
-    if command.len() > 3 || command.len() < 2 {
-        return Err("Wrong user input length.".to_string());
+        // The same runway, landed the other way
+        let mut airport = construct_airport(&args());
+        for point in airport.map.map.iter_mut().flatten() {
+            if let MapPoint::Runway((_, side)) = point {
+                if *side == Direction::East {
+                    *side = Direction::West;
+                }
+            }
+        }
+        airport.runways.get_mut("1").unwrap().side = Direction::West;
+        spawn_landing_aircraft(&mut airport, false);
+        assert_eq!(airport.planes[0].runway.side, Direction::West);
+        assert_eq!(airport.planes[0].position.1, cols - 1);
     }
-    let keyword = command[0];
-    let aircraft = command[1].to_string().to_lowercase();
-    let mut plane = planes
-        .iter()
-        .find(|plane| plane.name.to_lowercase() == aircraft)
-        .ok_or("Plane not found")?
-        .clone();
 
-    let valid_commands = ["hp", "p", "l", "t", "tor", "hs", "t2r", "t2g"];
-    if !valid_commands.contains(&keyword) {
-        return Err("Invalid command: ".to_string() + keyword);
+    #[test]
+    fn snow_needs_deicing_before_pushback() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Snow;
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        assert!(parse_user_input(format!("p {}", name), &airport).is_err());
+        update_aircraft_position(&mut airport, None);
+        assert!(matches!(
+            airport.planes[0].current_action,
+            Action::AtGate((_, AtGateAction::DeIce))
+        ));
+        update_aircraft_position(&mut airport, None);
+        assert!(matches!(
+            airport.planes[0].current_action,
+            Action::AtGate((_, AtGateAction::Standby))
+        ));
+        assert!(parse_user_input(format!("p {}", name), &airport).is_ok());
+    }
+
+    #[test]
+    fn thunderstorm_ground_stop() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Thunderstorm;
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, false);
+        let name = airport.planes[0].name.clone();
+        assert!(parse_user_input(format!("p {}", name), &airport).is_err());
+        // Arrivals divert, and do not count as departures
+        divert_from_thunderstorm(&mut airport);
+        assert!(airport.planes[1].diverted && airport.planes[1].out_of_map);
+        assert!(!airport.planes[0].diverted);
+        let mut score = score();
+        update_score(&mut airport, &mut score);
+        assert_eq!(score.takeoff, 0);
+        // No new arrivals are let through
+        airport.next_arrival_tick = 0;
+        assert!(!is_arrival_due(&mut airport));
     }
-    if keyword != "hp" && keyword != "p" && command.len() != 3 {
-        return Err("Must contain a runway/gate/terminal number".to_string());
+
+    #[test]
+    fn takeoff_needs_deicing_in_snow() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Snow;
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        let id = airport.planes[0].id;
+        // On taxiway 7, east of pad A
+        airport.planes[0].position = (8, 40);
+        airport.planes[0].current_action = Action::TaxiOntoRunway(1);
+        assert!(parse_user_input(format!("t {} 1", name), &airport).is_err());
+        airport.planes[0] = parse_user_input(format!("deice {} A", name), &airport).unwrap();
+        advance_until(&mut airport, id, |plane| {
+            plane.current_action == Action::HoldPosition
+        })
+        .unwrap();
+        assert!(airport.planes[0].de_iced);
+        airport.planes[0] = parse_user_input(format!("tor {} 1", name), &airport).unwrap();
+        assert!(parse_user_input(format!("t {} 1", name), &airport).is_ok());
     }
-    let mut destination_num = None;
-    if keyword != "hp" && keyword != "p" {
-        destination_num = Some(command[2].to_string());
-        if keyword != "t2g" {
-            // Check if runway exists, and if it does, set the plane's runway
-            if !runways.contains_key(&destination_num.clone().unwrap()) {
-                return Err("Runway not found".to_string());
+
+    #[test]
+    fn tug_blocks_taxiing_plane() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, true);
+        // Taxiing west along taxiway 7, with a tug standing three tiles ahead
+        airport.planes[0].position = (8, 40);
+        airport.planes[0].current_action = Action::TaxiOntoRunway(1);
+        airport.vehicles.push(GroundVehicle {
+            id: 1,
+            kind: GroundVehicleKind::Tug,
+            position: (8, 37),
+            assisting: None,
+        });
+        for _ in 0..10 {
+            update_aircraft_position(&mut airport, None);
+        }
+        assert_eq!(airport.planes[0].position, (8, 38));
+        let mut score = score();
+        detect_and_handle_collisions(&mut airport, &mut score, &mut std::io::sink());
+        assert_eq!(score.crash, 0);
+    }
+
+    #[test]
+    fn flight_numbers_unique_and_in_range() {
+        let mut a = args();
+        a.flight_numbers = AIRWAY_IDS
+            .keys()
+            .filter(|airline| **airline != "BA")
+            .map(|airline| (airline.to_string(), (10, 12)))
+            .collect();
+        a.flight_numbers
+            .push(parse_flight_numbers("ba=500-501").unwrap());
+        let mut airport = construct_airport(&a);
+        let (rows, cols) = airport.map.bounds();
+        // Every name there is, and a few more attempts once they have run out
+        let names = AIRWAY_IDS.len() * 3 - 1;
+        for _ in 0..names + 3 {
+            spawn_landing_aircraft(&mut airport, false);
+            // Out of the way of the next arrival
+            for plane in airport.planes.iter_mut() {
+                plane.position = (rows - 1, cols - 1);
+            }
+        }
+        assert_eq!(airport.planes.len(), names);
+        let mut seen = HashSet::new();
+        for plane in airport.planes.iter() {
+            assert!(seen.insert(plane.name.clone()), "{} twice", plane.name);
+            let number = plane.name[2..].parse::<usize>().unwrap();
+            match &plane.name[..2] {
+                "BA" => assert!((500..=501).contains(&number), "{}", plane.name),
+                _ => assert!((10..=12).contains(&number), "{}", plane.name),
             }
-            let runway = runways.get(&destination_num.clone().unwrap()).unwrap();
-            plane.runway = runway.clone();
         }
+        assert!(parse_flight_numbers("XX=1-2").is_err());
+        assert!(parse_flight_numbers("BA=5-2").is_err());
     }
 
-    let action = match keyword {
-        "l" => Action::Land,
-        "t" => Action::Takeoff,
-        "hp" => Action::HoldPosition,
-        "p" => Action::Pushback,
-        "tor" => Action::TaxiOntoRunway(destination_num.clone().unwrap().parse::<usize>().unwrap()),
-        "hs" => Action::HoldShort,
-        "t2g" => Action::TaxiToGate(destination_num.clone().unwrap()),
-        _ => Action::HoldPosition, // Should never happen
-    };
+    #[test]
+    fn collision_bell_follows_alerts() {
+        for alerts in [false, true] {
+            let mut a = args();
+            a.alerts = alerts;
+            let mut airport = construct_airport(&a);
+            spawn_landing_aircraft(&mut airport, true);
+            spawn_landing_aircraft(&mut airport, true);
+            airport.planes[1].position = airport.planes[0].position;
+            let mut score = score();
+            let mut out = Vec::new();
+            detect_and_handle_collisions(&mut airport, &mut score, &mut out);
+            assert_eq!(score.crash, 1);
+            assert_eq!(out.contains(&0x07), alerts);
+        }
+    }
 
-    /*
-        Valid successors for each action:
-        InAir: -
-        Land: -
-        HoldPosition: TaxiToGate (after landing), TaxiToRunway, HoldShort, TaxiOntoRunway
-        Pushback: -
-        TaxiOntoRunway: HoldPosition, HoldShort, Takeoff, TaxiToRunway, TaxiToGate
-        HoldShort: HoldPosition, TaxiOntoRunway, Takeoff, TaxiToRunway
-        TaxiToGate: HoldPosition
-        Takeoff: -
-        AtGate: Pushback (only when on standby)
-    */
-    match plane.current_action {
-        Action::InAir => return Err("Not a valid action when plane is in the air".to_string()),
-        Action::Land => return Err("Not a valid action when in the process of landing".to_string()),
-        Action::Takeoff => {
-            return Err("Not a valid action when in the process of takeoff".to_string())
+    #[test]
+    fn corridor_spreads_arrivals() {
+        let mut a = args();
+        a.corridor_width = 5;
+        let mut entries = HashSet::new();
+        for _ in 0..30 {
+            let mut airport = construct_airport(&a);
+            airport.weather.condition = WeatherCondition::Clear;
+            airport.weather.wind_speed = 0.0;
+            spawn_landing_aircraft(&mut airport, false);
+            assert_eq!(airport.planes[0].position.1, 0);
+            entries.insert(airport.planes[0].position);
+            // Arrivals off the centreline still line up and land
+            airport.planes[0].speed_kt = 140.0;
+            airport.planes[0].cleared_to_land = true;
+            let id = airport.planes[0].id;
+            advance_until(&mut airport, id, |plane| {
+                plane.current_action != Action::InAir
+            })
+            .unwrap();
+            assert!(matches!(airport.planes[0].current_action, Action::Land(_)));
         }
-        Action::HoldPosition => match action {
-            Action::TaxiToGate(_) | Action::HoldShort | Action::TaxiOntoRunway(_) => {}
-            _ => {
-                return Err("Not a valid action when holding position".to_string());
+        assert!(entries.len() > 1);
+    }
+
+    #[test]
+    fn turnaround_time_recorded() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (2, 40);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::TaxiToGate("3".to_string());
+        let name = airport.planes[0].name.clone();
+        let mut arrived = None;
+        for _ in 0..200 {
+            update_aircraft_position(&mut airport, None);
+            if arrived.is_none() && matches!(airport.planes[0].current_action, Action::AtGate(_)) {
+                arrived = Some(airport.tick);
             }
-        },
-        Action::TaxiOntoRunway(_) => match action {
-            // Need TaxiToGate during emergency situations
-            Action::HoldPosition | Action::HoldShort | Action::TaxiToGate(_) => {}
-            Action::Takeoff => {
-                if weather.condition == WeatherCondition::InclementWeather {
-                    return Err(
-                        "Cannot takeoff during inclement weather, return back to the gate"
-                            .to_string(),
-                    );
-                }
+            airport.tick += 1;
+            if matches!(
+                airport.planes[0].current_action,
+                Action::AtGate((_, AtGateAction::Standby))
+            ) {
+                break;
             }
-            _ => {
-                return Err("Not a valid action when taxiing onto runway".to_string());
+        }
+        // The departure waits a while at the gate before asking to push back
+        airport.tick += 7;
+        airport.planes[0] = parse_user_input(format!("p {}", name), &airport).unwrap();
+        update_aircraft_position(&mut airport, None);
+        assert_eq!(airport.turnarounds.count, 1);
+        assert_eq!(airport.turnarounds.max, airport.tick - arrived.unwrap());
+        assert_eq!(airport.turnarounds.min, airport.turnarounds.max);
+        assert!(summary_text(&airport, &score()).contains("Turnarounds:\t1"));
+    }
+
+    #[test]
+    fn adjacent_swap_is_a_collision() {
+        for detect in [false, true] {
+            let mut a = args();
+            a.detect_swaps = detect;
+            let mut airport = construct_airport(&a);
+            spawn_landing_aircraft(&mut airport, true);
+            spawn_landing_aircraft(&mut airport, true);
+            airport.planes[0].position = (8, 30);
+            airport.planes[1].position = (8, 31);
+            let mut score = score();
+            detect_and_handle_collisions(&mut airport, &mut score, &mut std::io::sink());
+            assert_eq!(score.crash, 0);
+            // Passed through each other in one tick
+            airport.planes[0].position = (8, 31);
+            airport.planes[1].position = (8, 30);
+            detect_and_handle_collisions(&mut airport, &mut score, &mut std::io::sink());
+            assert_eq!(score.crash, detect as usize);
+        }
+    }
+
+    #[test]
+    fn only_consoles_with_the_token_are_heard() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || user_input_thread(listener, Some("secret".to_string()), sender));
+        let mut intruder = TcpStream::connect(address).unwrap();
+        intruder.write_all(b"wrong\nl AA100 1\n").unwrap();
+        let mut controller = TcpStream::connect(address).unwrap();
+        controller.write_all(b"secret\nhp BA200\n").unwrap();
+        let (client, command) = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(command, "hp BA200");
+        assert_eq!(client, controller.local_addr().unwrap().to_string());
+        assert!(receiver.recv_timeout(Duration::from_millis(300)).is_err());
+
+        // Without a token the first line is already a command
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || user_input_thread(listener, None, sender));
+        let mut console = TcpStream::connect(address).unwrap();
+        console.write_all(b"hp BA200\n").unwrap();
+        let (_, command) = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(command, "hp BA200");
+    }
+
+    #[test]
+    fn occupied_runway_is_a_missed_approach() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        // A plane stopped on the runway
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (2, 40);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldPosition;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[1].speed_kt = 140.0;
+        airport.planes[1].cleared_to_land = true;
+        let id = airport.planes[1].id;
+        advance_until(&mut airport, id, |plane| {
+            plane.current_action != Action::InAir
+        })
+        .unwrap();
+        assert_eq!(airport.planes[1].current_action, Action::GoAround);
+        assert_eq!(
+            airport.planes[1].go_around_reason.as_deref(),
+            Some("runway 1 is occupied")
+        );
+        assert_eq!(airport.missed_approaches, 1);
+        let mut score = score();
+        update_score(&mut airport, &mut score);
+        assert_eq!(score.missed_approach, 1);
+        assert!(summary_text(&airport, &score).contains("Missed approaches:\t1"));
+    }
+
+    #[test]
+    fn renders_every_few_ticks() {
+        let mut a = args();
+        a.render_interval = 3;
+        let mut airport = construct_airport(&a);
+        let mut rendered = vec![];
+        for tick in 0..7 {
+            airport.tick = tick;
+            if is_render_due(&mut airport) {
+                rendered.push(tick);
             }
-        },
-        Action::HoldShort => match action {
-            Action::HoldPosition | Action::TaxiOntoRunway(_) => {}
-            Action::Takeoff => {
-                if weather.condition == WeatherCondition::InclementWeather {
-                    return Err(
-                        "Cannot takeoff during inclement weather, return back to the gate"
-                            .to_string(),
-                    );
-                }
+        }
+        assert_eq!(rendered, vec![0, 3, 6]);
+    }
+
+    #[test]
+    fn only_changed_cells_are_written() {
+        let first: Frame = vec![
+            vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            text_row("status"),
+        ];
+        let mut second = first.clone();
+        second[0][1] = "Z".to_string();
+        assert_eq!(frame_diff(&first, &second), "\x1B[1;2HZ");
+        assert_eq!(frame_diff(&second, &second), "");
+        // The first frame is drawn in full on a cleared screen
+        assert!(frame_diff(&vec![], &first).starts_with("\x1B[2J"));
+        // Lines no longer drawn are cleared
+        assert_eq!(
+            frame_diff(&second, &second[..1].to_vec()),
+            "\x1B[2;1H\x1B[K"
+        );
+    }
+
+    #[test]
+    fn departure_heavy_mix_fills_gates() {
+        let mut a = args();
+        a.arr_dep_ratio = 0.0;
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        let (_sender, receiver) = channel();
+        let gates = airport.gates.len();
+        // Departures until the gates run out, then arrivals
+        for _ in 0..gates + 2 {
+            update_game_state(
+                &mut airport,
+                true,
+                &mut score(),
+                &receiver,
+                &mut Vec::<String>::new(),
+                None,
+                None,
+            );
+        }
+        let at_gate = airport
+            .planes
+            .iter()
+            .filter(|plane| matches!(plane.current_action, Action::AtGate(_)))
+            .count();
+        assert_eq!(at_gate, gates);
+        assert_eq!(airport.planes.len(), gates + 2);
+    }
+
+    #[test]
+    fn taxiway_traffic_has_right_of_way_at_junction() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        // Gate 1's line joins taxiway 7 at (8, 28). One plane pushes back up the gate line
+        // and the other taxis along the taxiway, both next to the junction.
+        airport.planes[0].position = (9, 28);
+        airport.planes[0].current_action = Action::Pushback;
+        airport.planes[1].current_action = Action::TaxiOntoRunway(1);
+        airport.planes[1].position = (8, 29);
+        update_aircraft_position(&mut airport, None);
+        assert_eq!(airport.planes[0].position, (9, 28));
+        assert_eq!(airport.planes[1].position, (8, 28));
+        let mut score = score();
+        detect_and_handle_collisions(&mut airport, &mut score, &mut std::io::sink());
+        assert_eq!(score.crash, 0);
+        // The pushback carries on once the taxiing plane has gone by
+        for _ in 0..2 {
+            update_aircraft_position(&mut airport, None);
+            detect_and_handle_collisions(&mut airport, &mut score, &mut std::io::sink());
+        }
+        assert_eq!(airport.planes[0].position, (8, 28));
+        assert_eq!(score.crash, 0);
+    }
+
+    #[test]
+    fn plane_table_in_sort_order() {
+        let mut a = args();
+        a.sort = "runway".parse().unwrap();
+        let mut airport = construct_airport(&a);
+        for name in ["AA100", "DL200", "UA300"] {
+            spawn_landing_aircraft(&mut airport, true);
+            airport.planes.last_mut().unwrap().name = name.to_string();
+        }
+        airport.planes[0].runway.name = 2;
+        airport.planes[1].runway.name = 0;
+        airport.planes[2].runway.name = 1;
+        let names = table_planes(&airport)
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, vec!["DL200", "UA300", "AA100"]);
+        assert!("bogus".parse::<PlaneSort>().is_err());
+    }
+
+    #[test]
+    fn runway_heading_spoken_in_clearance() {
+        let mut airport = construct_airport(&args());
+        assert_eq!(airport.runways["1"].heading, 90);
+        // As if the map read "R1E270"
+        airport.map.runway_headings.insert(1, 270);
+        airport.runways = Runway::new(&airport.map);
+        assert_eq!(airport.runways["1"].heading, 270);
+        spawn_landing_aircraft(&mut airport, false);
+        let clearance = create_atc_clearance(&airport, &airport.planes[0]);
+        assert!(clearance.contains("runway two seven"), "{}", clearance);
+        airport.planes[0].runway.heading = 90;
+        assert_eq!(airport.planes[0].runway.spoken_name(), "zero niner");
+        airport.planes[0].runway.heading = 360;
+        assert_eq!(airport.planes[0].runway.spoken_name(), "three six");
+    }
+
+    #[test]
+    fn back_to_back_spawns_never_share_a_tile() {
+        let mut a = args();
+        a.corridor_width = 3;
+        let mut airport = construct_airport(&a);
+        // Only as many arrivals as there are entry tiles get in, the rest wait a tick
+        for _ in 0..5 {
+            spawn_landing_aircraft(&mut airport, false);
+        }
+        assert_eq!(airport.planes.len(), 3);
+        let positions = airport
+            .planes
+            .iter()
+            .map(|plane| plane.position)
+            .collect::<HashSet<(usize, usize)>>();
+        assert_eq!(positions.len(), 3);
+        assert_eq!(airport.next_arrival_tick, airport.tick + 1);
+    }
+
+    #[test]
+    fn throughput_over_recent_departures() {
+        let mut airport = construct_airport(&args());
+        for tick in [10, 100, 200, 290] {
+            airport.throughput.record(tick);
+        }
+        // Four departures in the last five minutes, then two once the early ones drop out
+        assert!((airport.throughput.per_minute(300) - 0.8).abs() < 1e-9);
+        assert!((airport.throughput.per_minute(400) - 0.4).abs() < 1e-9);
+        airport.throughput.record(500);
+        assert_eq!(airport.throughput.departures.len(), 2);
+    }
+
+    #[test]
+    fn plane_holds_short_until_cleared_to_cross() {
+        for crossing_clearance in [false, true] {
+            let mut a = args();
+            a.crossing_clearance = crossing_clearance;
+            let mut airport = construct_airport(&a);
+            spawn_landing_aircraft(&mut airport, false);
+            // On taxiway 1, next to the runway it crosses
+            let (top, left) = (a.spacing_tb, a.spacing_lr);
+            airport.planes[0].position = (top + 1, left + 3);
+            airport.planes[0].altitude_ft = 0.0;
+            airport.planes[0].current_action = Action::TaxiToGate("1".to_string());
+            let mut seen = vec![];
+            for _ in 0..3 {
+                update_aircraft_position(&mut airport, None);
+                seen.push(airport.planes[0].position);
             }
-            _ => {
-                return Err("Not a valid action when holding short".to_string());
+            if !crossing_clearance {
+                assert_eq!(seen[0].0, top);
+                continue;
             }
-        },
-        Action::TaxiToGate(_) => match action {
-            Action::HoldPosition => {}
-            _ => {
-                return Err("Not a valid action when taxiing to gate".to_string());
+            assert!(seen.iter().all(|position| *position == (top + 1, left + 3)));
+            let name = airport.planes[0].name.clone();
+            let reply = airport.clear_to_cross(&name, "1").unwrap();
+            assert!(reply.contains("cross runway"), "{}", reply);
+            update_aircraft_position(&mut airport, None);
+            assert_eq!(airport.planes[0].position.0, top);
+        }
+    }
+
+    #[test]
+    fn clearance_written_to_transcript() {
+        let path = temp_path("transcript.log");
+        let mut a = args();
+        a.transcript = Some(path.to_str().unwrap().to_string());
+        let mut airport = construct_airport(&a);
+        spawn_landing_aircraft(&mut airport, false);
+        airport.tick = 42;
+        let name = airport.planes[0].name.clone();
+        let (sender, receiver) = channel();
+        sender
+            .send(("test".to_string(), format!("l {} 1", name)))
+            .unwrap();
+        let mut spoken = Vec::<String>::new();
+        update_aircraft_from_user_input(&mut airport, &receiver, &mut spoken);
+        let transcript = std::fs::read_to_string(&path).unwrap();
+        let entry = transcript.lines().next().unwrap();
+        assert!(entry.starts_with(&format!("42\t{}\t", name)), "{}", entry);
+        assert!(entry.contains("cleared to land"));
+        assert_eq!(transcript.lines().count(), 1);
+    }
+
+    #[test]
+    fn departure_blocks_landing_on_crossing_runway() {
+        let a = args();
+        let mut airport = construct_airport(&a);
+        assert!(airport.runway_intersections.is_empty());
+        // Runway 2 running south up to runway 1 only meets it
+        let col = a.spacing_lr + 20;
+        for row in 0..a.spacing_tb {
+            airport.map.map[row][col] = MapPoint::Runway((2, Direction::South));
+        }
+        assert!(runway_intersections(&airport.map).is_empty());
+        // Running on across it, it crosses runway 1
+        for row in 0..a.spacing_tb + 3 {
+            if row != a.spacing_tb {
+                airport.map.map[row][col] = MapPoint::Runway((2, Direction::South));
+            }
+        }
+        airport.runway_intersections = runway_intersections(&airport.map);
+        assert_eq!(airport.runway_intersections, vec![(1, 2)]);
+        let runway = Runway {
+            name: 2,
+            side: Direction::South,
+            heading: 180,
+            ..airport.runways["1"].clone()
+        };
+        airport.runways.insert("2".to_string(), runway);
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (a.spacing_tb, a.spacing_lr + 10);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::Takeoff;
+        let name = airport.planes[1].name.clone();
+        let error = parse_user_input(format!("l {} 2", name), &airport)
+            .unwrap_err()
+            .reason;
+        assert!(error.contains("crosses runway 1"), "{}", error);
+        airport.planes[0].current_action = Action::HoldPosition;
+        assert!(parse_user_input(format!("l {} 2", name), &airport).is_ok());
+        // An arrival cleared to land on runway 1 is using it while still on approach
+        let spawn = Spawn {
+            runway: Some("2".to_string()),
+            ..Default::default()
+        };
+        spawn_aircraft(&mut airport, &spawn);
+        let (landing, arrival) = (airport.planes[1].id, airport.planes[2].id);
+        let runway = airport.runways["1"].clone();
+        let cleared = &mut airport.planes[2];
+        cleared.runway = runway;
+        cleared.cleared_to_land = true;
+        assert_eq!(airport.crossing_runway_in_use(2, landing), Some(1));
+        assert_eq!(airport.crossing_runway_in_use(2, arrival), None);
+    }
+
+    #[test]
+    fn departure_asks_for_pushback_once() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[0].current_action = Action::AtGate(("1".to_string(), AtGateAction::Clean));
+        detect_plane_requests(&mut airport);
+        assert!(airport.requests.is_empty());
+        for _ in 0..20 {
+            update_aircraft_position(&mut airport, None);
+            detect_plane_requests(&mut airport);
+            if !airport.requests.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(airport.requests.len(), 1);
+        assert!(airport.requests[0].message.ends_with("ready for pushback."));
+        detect_plane_requests(&mut airport);
+        assert_eq!(airport.requests.len(), 1);
+        // Answered once the plane is pushing back
+        airport.planes[0].current_action = Action::Pushback;
+        detect_plane_requests(&mut airport);
+        assert!(airport.requests.is_empty());
+    }
+
+    #[test]
+    fn heavy_covers_two_tiles() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[0].heavy = true;
+        airport.planes[0].heading = Direction::East;
+        airport.planes[0].position = (5, 10);
+        // Nose on the tile, tail on the one behind it
+        assert_eq!(airport.planes[0].footprint(), vec![(5, 10), (5, 9)]);
+        for (other, crashes) in [((5, 9), 1), ((5, 10), 1), ((5, 11), 0)] {
+            airport.planes[1].position = other;
+            let mut score = score();
+            detect_and_handle_collisions(&mut airport, &mut score, &mut std::io::sink());
+            assert_eq!(score.crash, crashes, "{:?}", other);
+        }
+        // Held on taxiway 7, facing west with its tail on (8, 31). A plane taxiing up
+        // behind stops short of the tail.
+        airport.planes[0].heading = Direction::West;
+        airport.planes[0].position = (8, 30);
+        airport.planes[0].current_action = Action::HoldPosition;
+        airport.planes[1].position = (8, 34);
+        airport.planes[1].altitude_ft = 0.0;
+        airport.planes[1].current_action = Action::TaxiOntoRunway(1);
+        let mut score = score();
+        for _ in 0..5 {
+            update_aircraft_position(&mut airport, None);
+            detect_and_handle_collisions(&mut airport, &mut score, &mut std::io::sink());
+        }
+        assert_eq!(airport.planes[1].position, (8, 32));
+        assert_eq!(score.crash, 0);
+    }
+
+    #[test]
+    fn gate_panel_shows_parked_and_inbound() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[0].current_action = Action::AtGate(("2".to_string(), AtGateAction::Standby));
+        airport.planes[1].current_action = Action::TaxiToGate("3".to_string());
+        let panel = gate_panel(&airport);
+        assert_eq!(panel[0], "1\tFree      -");
+        assert_eq!(panel[1], format!("2\tOccupied  {}", airport.planes[0].name));
+        assert_eq!(panel[2], format!("3\tInbound   {}", airport.planes[1].name));
+        // Gate 10 comes after gate 9, and a lettered gate after both
+        for number in ["A", "10", "9"] {
+            let gate = Gate {
+                number: number.to_string(),
+                position: (0, 0),
+                is_occupied: false,
+            };
+            airport.gates.insert(number.to_string(), gate);
+        }
+        let order = gate_panel(&airport)
+            .iter()
+            .map(|line| line.split('\t').next().unwrap().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(order[order.len() - 3..], ["9", "10", "A"]);
+    }
+
+    #[test]
+    fn weather_coloured_by_severity() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::InclementWeather;
+        assert!(dashboard(&airport, &score()).contains("\x1B[31mInclementWeather"));
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 10.0;
+        let line = dashboard(&airport, &score());
+        assert!(line.contains("\x1B[32mClear"));
+        assert!(!line.contains("\x1B[1;31m"));
+        // Wind over the limit is picked out in bold red
+        airport.weather.wind_speed = 40.0;
+        assert!(dashboard(&airport, &score()).contains("\x1B[1;31m40.00 kn"));
+    }
+
+    #[test]
+    fn rate_zero_pauses_arrivals() {
+        let mut airport = construct_airport(&args());
+        let (sender, receiver) = channel();
+        sender
+            .send(("test".to_string(), "rate 0".to_string()))
+            .unwrap();
+        update_aircraft_from_user_input(&mut airport, &receiver, &mut Vec::<String>::new());
+        for tick in 0..1000 {
+            airport.tick = tick;
+            assert!(!is_arrival_due(&mut airport));
+        }
+        assert!(airport.set_landing_interval("x").is_err());
+        airport.set_landing_interval("5").unwrap();
+        airport.tick += 5;
+        assert!(is_arrival_due(&mut airport));
+    }
+
+    #[test]
+    fn low_fuel_lands_first() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, false);
+        let first = airport.planes[0].id;
+        // The first arrival flies on a couple of tiles before the second appears
+        update_aircraft_position(&mut airport, Some(first));
+        update_aircraft_position(&mut airport, Some(first));
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].fuel_ticks = 500;
+        airport.planes[1].fuel_ticks = 500;
+        let (closer, farther) = (airport.planes[0].id, airport.planes[1].id);
+        assert_eq!(landing_sequence(&airport), vec![closer, farther]);
+        airport.planes[1].fuel_ticks = 60;
+        assert_eq!(landing_sequence(&airport), vec![farther, closer]);
+        let clearance = create_atc_clearance(&airport, &airport.planes[0]);
+        assert!(
+            clearance.contains(", number 2, you are cleared to land"),
+            "{}",
+            clearance
+        );
+        let clearance = create_atc_clearance(&airport, &airport.planes[1]);
+        assert!(!clearance.contains("number"), "{}", clearance);
+    }
+
+    #[test]
+    fn spawn_on_runway_two_approach() {
+        let a = args();
+        let mut airport = construct_airport(&a);
+        // Runway 2 running south, its approach coming in over the top edge
+        let col = a.spacing_lr + 20;
+        for row in a.spacing_tb + 1..a.spacing_tb + 4 {
+            airport.map.map[row][col] = MapPoint::Runway((2, Direction::South));
+        }
+        let runway = Runway {
+            name: 2,
+            side: Direction::South,
+            heading: 180,
+            ..airport.runways["1"].clone()
+        };
+        airport.runways.insert("2".to_string(), runway);
+        let spawn = Spawn {
+            runway: Some("2".to_string()),
+            position: Some((0, col)),
+            ..Default::default()
+        };
+        spawn_aircraft(&mut airport, &spawn);
+        assert_eq!(airport.planes[0].runway.name, 2);
+        assert_eq!(airport.planes[0].position, (0, col));
+        assert_eq!(airport.planes[0].heading, Direction::South);
+        // A runway that is not on the map gives no plane
+        let spawn = Spawn {
+            runway: Some("9".to_string()),
+            ..Default::default()
+        };
+        spawn_aircraft(&mut airport, &spawn);
+        assert_eq!(airport.planes.len(), 1);
+    }
+
+    #[test]
+    fn oldest_holder_diverts_over_capacity() {
+        let mut a = args();
+        a.holding_capacity = Some(2);
+        a.corridor_width = 5;
+        let mut airport = construct_airport(&a);
+        for tick in 0..3 {
+            airport.tick = tick;
+            spawn_landing_aircraft(&mut airport, false);
+        }
+        assert_eq!(airport.planes.len(), 3);
+        divert_excess_holders(&mut airport);
+        assert!(airport.planes[0].diverted && airport.planes[0].out_of_map);
+        assert!(!airport.planes[1].diverted && !airport.planes[2].diverted);
+        let mut score = score();
+        update_score(&mut airport, &mut score);
+        assert_eq!((score.takeoff, score.diversion), (0, 1));
+        assert!(summary_text(&airport, &score).contains("Diversions:\t1"));
+    }
+
+    #[test]
+    fn telemetry_called_once_per_tick() {
+        let mut airport = construct_airport(&args());
+        let (_sender, receiver) = channel();
+        let mut score = score();
+        let mut ticks = vec![];
+        let mut record = |airport: &Airport, _: &Score, tick: usize| {
+            assert_eq!(airport.tick, tick);
+            ticks.push(tick);
+        };
+        for _ in 0..5 {
+            update_game_state(
+                &mut airport,
+                false,
+                &mut score,
+                &receiver,
+                &mut Vec::<String>::new(),
+                None,
+                Some(&mut record),
+            );
+        }
+        assert_eq!(ticks, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn weather_holds_between_updates() {
+        let mut a = args();
+        a.weather_interval = 500;
+        a.render_interval = 1000;
+        let mut airport = construct_airport(&a);
+        let (_sender, receiver) = channel();
+        let mut score = score();
+        let mut tick = |airport: &mut Airport| {
+            update_game_state(
+                airport,
+                false,
+                &mut score,
+                &receiver,
+                &mut Vec::<String>::new(),
+                None,
+                None,
+            )
+        };
+        tick(&mut airport);
+        let wind = (airport.weather.wind_direction, airport.weather.wind_speed);
+        airport.weather.condition = WeatherCondition::Rain;
+        for _ in 1..500 {
+            tick(&mut airport);
+            assert_eq!(airport.weather.condition, WeatherCondition::Rain);
+            assert_eq!(
+                (airport.weather.wind_direction, airport.weather.wind_speed),
+                wind
+            );
+        }
+        assert_eq!(airport.last_weather_tick, Some(0));
+        tick(&mut airport);
+        assert_eq!(airport.last_weather_tick, Some(500));
+    }
+
+    #[test]
+    fn runway_without_direction_holds() {
+        for action in [Action::InAir, Action::Land(None)] {
+            let mut airport = construct_airport(&args());
+            spawn_landing_aircraft(&mut airport, false);
+            airport.planes[0].runway.side = Direction::StayPut;
+            airport.planes[0].current_action = action;
+            let before = airport.planes[0].position;
+            update_aircraft_position(&mut airport, None);
+            assert_eq!(airport.planes[0].current_action, Action::HoldPosition);
+            assert_eq!(airport.planes[0].position, before);
+        }
+    }
+
+    #[test]
+    fn errors_spoken_only_when_asked() {
+        for speak_errors in [true, false] {
+            let mut a = args();
+            a.speak_errors = speak_errors;
+            let mut airport = construct_airport(&a);
+            let (sender, receiver) = channel();
+            sender
+                .send(("test".to_string(), "l ZZ991 1".to_string()))
+                .unwrap();
+            let mut spoken = Vec::<String>::new();
+            update_aircraft_from_user_input(&mut airport, &receiver, &mut spoken);
+            assert_eq!(
+                spoken.iter().any(|text| text.contains("not found")),
+                speak_errors,
+                "{:?}",
+                spoken
+            );
+        }
+    }
+
+    #[test]
+    fn validator_reports_unreachable_gate() {
+        let spacing = Spacing {
+            top_bottom: 0,
+            left_right: 0,
+        };
+        let mut map = build_airport_map("./src/airport.map", spacing).unwrap();
+        assert_eq!(validate_map(&map), Vec::<String>::new());
+        // Cut gate 1's taxi line
+        map.map[8][8] = MapPoint::Empty;
+        assert_eq!(
+            validate_map(&map),
+            vec!["Gate 1 cannot be reached from a runway".to_string()]
+        );
+        // Taxiway 1 cut off at both ends
+        map.map[1][3] = MapPoint::Empty;
+        map.map[6][3] = MapPoint::Empty;
+        let problems = validate_map(&map);
+        assert!(
+            problems.contains(&"Taxiway 1 is not connected to a runway".to_string()),
+            "{:?}",
+            problems
+        );
+        map.map[12][20] = MapPoint::Gate("2".to_string());
+        assert!(validate_map(&map).contains(&"Gate 2 appears more than once".to_string()));
+    }
+
+    #[test]
+    fn taxi_to_cut_off_gate_rejected() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, false);
+        // Off the runway onto taxiway 4
+        airport.planes[0].position = (4, 52);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldPosition;
+        let name = airport.planes[0].name.clone();
+        assert!(parse_user_input(format!("t2g {} 1", name), &airport).is_ok());
+        // Gate 1's taxi line cut half way
+        airport.map.map[10][28] = MapPoint::Empty;
+        let error = parse_user_input(format!("t2g {} 1", name), &airport)
+            .unwrap_err()
+            .reason;
+        assert_eq!(error, "No taxi route to gate 1");
+        assert!(parse_user_input(format!("t2g {} 2", name), &airport).is_ok());
+    }
+
+    #[test]
+    fn crosswind_limit_by_category() {
+        let mut a = args();
+        a.crosswind_limit = vec![("light".to_string(), 15.0), ("heavy".to_string(), 30.0)];
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldShort;
+        airport.planes[0].position = (2, 40);
+        // Straight across the runway, between the two limits
+        let heading = airport.runways["1"].heading as usize;
+        airport.weather.wind_direction = (heading + 90) % 360;
+        airport.weather.wind_speed = 20.0;
+        let name = airport.planes[0].name.clone();
+        airport.planes[0].heavy = true;
+        assert!(parse_user_input(format!("t {} 1", name), &airport).is_ok());
+        airport.planes[0].heavy = false;
+        let error = parse_user_input(format!("t {} 1", name), &airport)
+            .unwrap_err()
+            .reason;
+        assert!(
+            error.starts_with("Crosswind of 20 kn on runway 1"),
+            "{}",
+            error
+        );
+        assert!(parse_wind_limit("medium=10").is_err());
+    }
+
+    #[test]
+    fn render_into_buffer_and_file() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, true);
+        let mut out = Vec::new();
+        render(&mut airport, &score(), &mut out);
+        let screen = String::from_utf8(out).unwrap();
+        assert!(screen.starts_with("\x1B[2J"));
+        assert!(screen.contains("Planes: 1"));
+        assert!(screen.contains(&airport.planes[0].name));
+        assert!(screen.contains("Gates"));
+
+        // With --render-to, a collision and its bell go to the file with the frame
+        let path = temp_path("render_to.txt");
+        let mut a = args();
+        a.render_to = Some(path.to_str().unwrap().to_string());
+        a.alerts = true;
+        let mut airport = construct_airport(&a);
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[1].position = airport.planes[0].position;
+        let (_sender, receiver) = channel();
+        update_game_state(
+            &mut airport,
+            false,
+            &mut score(),
+            &receiver,
+            &mut Vec::<String>::new(),
+            None,
+            None,
+        );
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(file.contains("Planes: 2"));
+        assert!(file.contains("Code 34"));
+        assert!(file.contains('\x07'));
+        // A render file that cannot be written no longer stops the game
+        let unwritable = Some(std::env::temp_dir().to_str().unwrap().to_string());
+        assert!(super::screen(&unwritable, true).write_all(b"frame").is_ok());
+    }
+
+    #[test]
+    fn tick_drawn_into_writer() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[1].position = airport.planes[0].position;
+        let (_sender, receiver) = channel();
+        let mut score = score();
+        let mut out = Vec::new();
+        update_game_state(
+            &mut airport,
+            false,
+            &mut score,
+            &receiver,
+            &mut Vec::<String>::new(),
+            Some(&mut out),
+            None,
+        );
+        // The dashboard and the collision report both go to the writer given
+        let screen = String::from_utf8(out).unwrap();
+        assert!(screen.contains("Planes: 2"), "{}", screen);
+        assert!(screen.contains("Takeoffs: 0"), "{}", screen);
+        assert!(screen.contains("Code 34"), "{}", screen);
+        assert_eq!(score.crash, 1);
+    }
+
+    #[test]
+    fn passenger_turnaround_skips_cargo() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        let gate = match &airport.planes[0].current_action {
+            Action::AtGate((gate, _)) => gate.clone(),
+            action => panic!("{:?}", action),
+        };
+        airport.planes[0].flight_type = FlightType::Passenger;
+        airport.planes[0].current_action = Action::AtGate((gate, AtGateAction::ShutdownProcedure));
+        let mut steps = vec![];
+        for _ in 0..30 {
+            update_aircraft_position(&mut airport, None);
+            if let Action::AtGate((_, step)) = &airport.planes[0].current_action {
+                steps.push(step.clone());
+            }
+        }
+        assert!(steps.contains(&AtGateAction::BoardPassengers));
+        assert!(!steps.contains(&AtGateAction::UnloadCargo));
+        assert!(!steps.contains(&AtGateAction::LoadAdditionalCargo));
+        assert!(!steps.contains(&AtGateAction::CustomsInspection));
+        assert_eq!(steps.last(), Some(&AtGateAction::Standby));
+        assert!(FlightType::International
+            .turnaround()
+            .contains(&AtGateAction::CustomsInspection));
+    }
+
+    #[test]
+    fn landing_needs_clearance() {
+        for cleared in [false, true] {
+            let mut airport = construct_airport(&args());
+            airport.weather.condition = WeatherCondition::Clear;
+            airport.weather.wind_speed = 0.0;
+            spawn_landing_aircraft(&mut airport, false);
+            airport.planes[0].speed_kt = 140.0;
+            if cleared {
+                let name = airport.planes[0].name.clone();
+                airport.planes[0] = parse_user_input(format!("l {} 1", name), &airport).unwrap();
             }
-        },
-        Action::Pushback => {
-            return Err("Not a valid action when in the process of pushback".to_string())
-        }
-        Action::AtGate((_, at_gate_action)) => match action {
-            Action::Pushback => {
-                if at_gate_action != AtGateAction::Standby {
-                    return Err("Wait for the plane to finish its turnaround process".to_string());
+            let id = airport.planes[0].id;
+            advance_until(&mut airport, id, |plane| {
+                plane.current_action != Action::InAir
+            })
+            .unwrap();
+            match cleared {
+                true => assert!(matches!(airport.planes[0].current_action, Action::Land(_))),
+                false => {
+                    assert_eq!(airport.planes[0].current_action, Action::GoAround);
+                    assert_eq!(airport.missed_approaches, 1);
                 }
-                if weather.condition == WeatherCondition::InclementWeather {
-                    return Err("Cannot pushback during inclement weather".to_string());
-                }
-            }
-            _ => {
-                return Err("Not a valid action when at gate".to_string());
             }
-        },
+        }
     }
 
-    plane.current_action = action;
+    #[test]
+    fn wind_shown_in_chosen_units() {
+        for (units, shown) in [(Units::Imperial, "10.00 kn"), (Units::Metric, "18.52 km/h")] {
+            let mut a = args();
+            a.units = units;
+            let mut airport = construct_airport(&a);
+            airport.weather.wind_speed = 10.0;
+            let line = dashboard(&airport, &score());
+            assert!(line.ends_with(shown), "{}", line);
+        }
+        assert!("nautical".parse::<Units>().is_err());
+    }
 
-    Ok(plane)
-}
+    #[test]
+    fn intention_shows_gate_while_taxiing() {
+        let mut airport = construct_airport(&args());
+        // Gate 3 renamed to A12
+        let mut gate = airport.gates.remove("3").unwrap();
+        gate.number = "A12".to_string();
+        airport.map.map[gate.position.0][gate.position.1] = MapPoint::Gate("A12".to_string());
+        airport.gates.insert("A12".to_string(), gate);
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (2, 40);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldPosition;
+        let name = airport.planes[0].name.clone();
+        airport.planes[0] = parse_user_input(format!("t2g {} A12", name), &airport).unwrap();
+        assert_eq!(airport.planes[0].intention, "to gate A12");
+        let mut out = Vec::new();
+        render(&mut airport, &score(), &mut out);
+        assert!(String::from_utf8(out).unwrap().contains("to gate A12"));
+        // Holding on the way keeps the intention
+        let plane = parse_user_input(format!("hp {}", name), &airport).unwrap();
+        assert_eq!(plane.intention, "to gate A12");
+    }
 
-fn create_atc_clearance(airport: &Airport, plane: &Plane) -> String {
-    let name = AIRWAY_IDS.get(plane.name.get(..2).unwrap()).unwrap();
-    let code = plane.name.get(2..).unwrap().to_string();
-    let clearance = match &plane.current_action {
-        Action::Land => format!(
-            "{} {}, you are cleared to land on runway {}.",
-            name, code, plane.runway.name
-        ),
-        Action::Takeoff => {
-            format!(
-                "{} {}, you are cleared for takeoff, runway {}. Conditions {:.2} at {} knots.",
-                name,
-                code,
-                plane.runway.name,
-                airport.weather.wind_direction,
-                airport.weather.wind_speed as usize
-            )
-        }
-        Action::HoldPosition => format!("{} {}, hold position, traffic crossing.", name, code),
-        Action::Pushback => format!(
-            "{} {}, pushback approved, expect runway {} for departure.",
-            name, code, plane.runway.name
-        ),
-        Action::TaxiOntoRunway(num) => {
-            format!("{} {}, taxi directly to runway {}.", name, code, num)
+    #[test]
+    fn arrivals_use_favoured_runway() {
+        let a = args();
+        let mut airport = construct_airport(&a);
+        // Runway 2 running south across the top of the map
+        let col = a.spacing_lr + 20;
+        for row in 0..a.spacing_tb + 3 {
+            if row != a.spacing_tb {
+                airport.map.map[row][col] = MapPoint::Runway((2, Direction::South));
+            }
         }
-        Action::HoldShort => {
-            format!(
-                "{} {}, hold short of runway {} for landing traffic.",
-                name, code, plane.runway.name
-            )
+        let runway = Runway {
+            name: 2,
+            side: Direction::South,
+            heading: 180,
+            ..airport.runways["1"].clone()
+        };
+        airport.runways.insert("2".to_string(), runway);
+        // Runway 1 lands east; wind from the south favours runway 2
+        airport.weather.wind_direction = 180;
+        airport.weather.wind_speed = 20.0;
+        assert_eq!(airport.favoured_runway().unwrap().name, 2);
+        assert!(dashboard(&airport, &score()).contains("Runway in use: 2"));
+        spawn_landing_aircraft(&mut airport, false);
+        assert_eq!(airport.planes[0].runway.name, 2);
+        // Not while it is closed
+        airport.closed_runways.push(2);
+        assert_eq!(airport.favoured_runway().unwrap().name, 1);
+        airport.closed_runways.clear();
+        airport.weather.wind_direction = 90;
+        assert_eq!(airport.favoured_runway().unwrap().name, 1);
+    }
+
+    #[test]
+    fn weather_chance_per_second() {
+        for chance in [2.0 / 300.0, 0.05, 0.1] {
+            // Two half-second ticks or four quarter-second ticks add up to one second
+            let half = chance_in_tick(chance, 0.5);
+            let quarter = chance_in_tick(chance, 0.25);
+            assert!((1.0 - (1.0 - half).powi(2) - chance).abs() < 1e-12);
+            assert!((1.0 - (1.0 - quarter).powi(4) - chance).abs() < 1e-12);
+            assert!((chance_in_tick(chance, 1.0) - chance).abs() < 1e-12);
         }
-        Action::TaxiToGate(gate) => {
-            // Find the taxiway closest to the plane's position
-            let point: MapPoint = airport.map.map[plane.position.0][plane.position.1].clone();
-            let taxiway = match point {
-                MapPoint::Taxiway((num, _)) => num,
-                MapPoint::Runway((_, dir)) => {
-                    let next = dir.go(plane.position);
-                    let next_point = airport.map.map[next.0][next.1].clone();
-                    match next_point {
-                        MapPoint::Taxiway((num, _)) => num,
-                        _ => 0,
+        // Rain lasts a second as often with one long tick as with ten short ones
+        let mut airport = construct_airport(&args());
+        for (seconds, ticks) in [(1.0, 1), (0.1, 10)] {
+            let mut rained = 0;
+            for _ in 0..4000 {
+                airport.weather.condition = WeatherCondition::Rain;
+                for _ in 0..ticks {
+                    if airport.weather.condition == WeatherCondition::Rain {
+                        simulate_weather(&mut airport, seconds);
                     }
                 }
-                _ => 0,
-            };
-            match taxiway {
-                0 => format!("{} {}, taxi to gate {}.", name, code, gate.clone()),
-                _ => format!(
-                    "{} {}, taxi to gate {} via taxiway {}.",
-                    name,
-                    code,
-                    gate.clone(),
-                    taxiway
-                ),
+                if airport.weather.condition == WeatherCondition::Rain {
+                    rained += 1;
+                }
             }
+            let stayed = rained as f64 / 4000.0;
+            assert!((stayed - 0.95).abs() < 0.02, "{} {}", seconds, stayed);
         }
-        Action::InAir => "".to_string(),
-        Action::AtGate(_) => "".to_string(),
-    };
-    clearance
-}
+    }
 
-fn update_score(airport: &mut Airport, score: &mut Score) {
-    // Update the score based on the current game state
-    let mut num_takeoffs = 0;
-    for plane in airport.planes.iter() {
-        if plane.out_of_map {
-            num_takeoffs += 1;
+    #[test]
+    fn handed_to_ground_once() {
+        let path = temp_path("handoff.log");
+        let mut a = args();
+        a.transcript = Some(path.to_str().unwrap().to_string());
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].speed_kt = 140.0;
+        airport.planes[0].cleared_to_land = true;
+        let name = airport.planes[0].name.clone();
+        let id = airport.planes[0].id;
+        advance_until(&mut airport, id, |plane| {
+            plane.current_action == Action::HoldPosition
+        })
+        .unwrap();
+        let (row, col) = airport.planes[0].position;
+        assert!(airport.map.map[row][col].clone().check_if_taxiway());
+        airport.planes[0] = parse_user_input(format!("t2g {} 3", name), &airport).unwrap();
+        advance_until(&mut airport, id, |plane| {
+            matches!(plane.current_action, Action::AtGate(_))
+        })
+        .unwrap();
+        let transcript = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            transcript.matches("contact ground point niner").count(),
+            1,
+            "{}",
+            transcript
+        );
+    }
+
+    #[test]
+    fn cleanup_resets_the_terminal() {
+        let mut out = Vec::new();
+        restore_terminal(&mut out);
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("\x1B[0m"));
+        assert!(text.contains("\x1B[?25h"));
+    }
+
+    #[test]
+    fn taxi_along_l_shaped_gate_line() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        let (row, col) = airport.gates["1"].position;
+        let line = |direction: Direction| MapPoint::GateTaxiLine(("A".to_string(), direction));
+        // Gate 1's line leaves the taxiway three tiles further west and turns east to the gate
+        for row in row - 3..row {
+            airport.map.map[row][col] = MapPoint::Empty;
         }
+        airport.map.map[row - 3][col - 3] = line(Direction::South);
+        for col in col - 3..col {
+            airport.map.map[row - 2][col] = line(Direction::East);
+        }
+        airport.map.map[row - 2][col] = line(Direction::South);
+        airport.map.map[row - 1][col] = line(Direction::South);
+        assert_eq!(validate_map(&airport.map), Vec::<String>::new());
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (2, 40);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldPosition;
+        let name = airport.planes[0].name.clone();
+        let id = airport.planes[0].id;
+        issue_command(&mut airport, format!("t2g {} 1", name)).unwrap();
+        advance_until(&mut airport, id, |plane| {
+            matches!(plane.current_action, Action::AtGate(_))
+        })
+        .unwrap();
+        assert_eq!(airport.planes[0].position, (row, col));
+        // Pushed back the same way, out onto the taxiway
+        airport.planes[0].current_action = Action::AtGate(("1".to_string(), AtGateAction::Standby));
+        issue_command(&mut airport, format!("p {}", name)).unwrap();
+        advance_until(&mut airport, id, |plane| {
+            plane.current_action == Action::HoldPosition
+        })
+        .unwrap();
+        assert_eq!(airport.planes[0].position, (row - 4, col - 3));
     }
-    score.takeoff = num_takeoffs;
-}
 
-// Function to simulate weather conditions
-fn simulate_weather(airport: &mut Airport) {
-    let mut rng = rand::thread_rng();
-    airport.weather.condition = match airport.weather.condition {
-        WeatherCondition::Clear => {
-            if rng.gen_range(0..300) <= 1 {
-                WeatherCondition::Rain
-            } else if rng.gen_range(0..1000) <= 1 {
-                let inclement_weather = "⚠️  Airport Operations Center (AOC): \n\
-                    Attention all passengers and crew, \
-                    due to the current severe weather conditions, \
-                    all departing flights have been temporarily halted for passenger safety. \
-                    Incoming flights that are close to landing will proceed as scheduled. \
-                    We appreciate your understanding and cooperation. \
-                    Please stay tuned to the flight information displays \
-                    and airport announcements for further updates. \
-                    We sincerely apologize for any inconvenience caused. \
-                    Your safety is our top priority. Thank you.";
-                if let Ok(mut aoc) = AOC.lock() {
-                    aoc.message = inclement_weather.to_owned();
-                }
-                WeatherCondition::InclementWeather
-            } else {
-                WeatherCondition::Clear
+    #[test]
+    fn runway_occupancy_time() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].speed_kt = 140.0;
+        airport.planes[0].cleared_to_land = true;
+        let (mut touchdown, mut vacated) = (None, None);
+        for _ in 0..120 {
+            update_aircraft_position(&mut airport, None);
+            let plane = &airport.planes[0];
+            let on_runway = plane.altitude_ft == 0.0
+                && matches!(
+                    airport.map.map[plane.position.0][plane.position.1],
+                    MapPoint::Runway(_)
+                );
+            if on_runway && touchdown.is_none() {
+                touchdown = Some(airport.tick);
             }
-        }
-        WeatherCondition::Rain => {
-            if rng.gen_range(0..100) < 95 {
-                WeatherCondition::Rain
-            } else {
-                WeatherCondition::Clear
+            if !on_runway && touchdown.is_some() && vacated.is_none() {
+                vacated = Some(airport.tick);
             }
+            airport.tick += 1;
         }
-        WeatherCondition::InclementWeather => {
-            if rng.gen_range(0..100) < 98 {
-                WeatherCondition::InclementWeather
-            } else {
-                // No more inclement weather alert
-                if let Ok(mut aoc) = AOC.lock() {
-                    aoc.message = String::new();
+        let occupied = vacated.unwrap() - touchdown.unwrap();
+        let stats = &airport.runway_occupancy[&1];
+        assert_eq!((stats.count, stats.total), (1, occupied));
+        assert!(summary_text(&airport, &score())
+            .contains(&format!("Runway 1 occupancy:\t1 (mean {}.0", occupied)));
+    }
+
+    #[test]
+    fn off_every_edge() {
+        let mut airport = construct_airport(&args());
+        let (rows, cols) = airport.map.bounds();
+        let corners = [(0, 0), (0, cols - 1), (rows - 1, 0), (rows - 1, cols - 1)];
+        let directions = [
+            Direction::North,
+            Direction::South,
+            Direction::East,
+            Direction::West,
+        ];
+        spawn_landing_aircraft(&mut airport, true);
+        for &corner in &corners {
+            for direction in directions.iter() {
+                let coord = direction.step(corner);
+                let off = airport.map.position(coord).is_none();
+                assert_eq!(airport.map.is_at_edge(corner, direction), off);
+                assert_eq!(direction.clone().go(&airport.map, corner).is_none(), off);
+                if off {
+                    assert_eq!(airport.map.point(coord), MapPoint::Empty);
+                }
+                let plane = &mut airport.planes[0];
+                plane.position = corner;
+                plane.out_of_map = false;
+                move_plane(plane, &airport.map, direction.clone());
+                assert_eq!(plane.out_of_map, off);
+                if !off {
+                    assert_eq!(Some(plane.position), airport.map.position(coord));
                 }
-                WeatherCondition::Clear
+                // Departing over the edge takes the plane off the map rather than panicking
+                let plane = &mut airport.planes[0];
+                plane.position = corner;
+                plane.out_of_map = false;
+                plane.runway.side = direction.clone();
+                plane.current_action = Action::Takeoff;
+                update_aircraft_position(&mut airport, None);
+                assert_eq!(airport.planes[0].out_of_map, off);
             }
         }
-    };
-    simulate_wind_direction_and_speed(&mut airport.weather, 10);
-}
+    }
 
-fn simulate_wind_direction_and_speed(weather: &mut Weather, prob: usize) {
-    let mut rng = rand::thread_rng();
-    if rng.gen_range(0..100) < prob {
-        weather.wind_speed = match weather.condition {
-            WeatherCondition::Clear => {
-                let normal = Normal::new(10.0, 1.0).unwrap();
-                let mut s = normal.sample(&mut rand::thread_rng());
-                s = if s < 0.0 && s > 20.0 { 20.0 } else { s };
-                s
-            }
-            WeatherCondition::Rain => {
-                let normal = Normal::new(30.0, 5.0).unwrap();
-                let mut s = normal.sample(&mut rand::thread_rng());
-                s = if s < 20.0 && s > 40.0 { 40.0 } else { s };
-                s
+    #[test]
+    fn only_departures_score_as_takeoffs() {
+        let mut airport = construct_airport(&args());
+        let (_, cols) = airport.map.bounds();
+        let edge = (0, cols - 1);
+        for action in [Action::Takeoff, Action::Land(None), Action::HoldPosition] {
+            spawn_landing_aircraft(&mut airport, true);
+            let plane = airport.planes.last_mut().unwrap();
+            plane.position = edge;
+            plane.altitude_ft = 0.0;
+            plane.runway.side = Direction::East;
+            plane.current_action = action;
+        }
+        // A departure and a landing roll off the edge
+        update_aircraft_position(&mut airport, None);
+        // A plane taxiing off the edge
+        move_plane(&mut airport.planes[2], &airport.map, Direction::East);
+        assert!(airport.planes.iter().all(|p| p.out_of_map));
+        let mut score = score();
+        update_score(&mut airport, &mut score);
+        assert_eq!(score.takeoff, 1);
+    }
+
+    #[test]
+    fn autopilot_brings_arrival_to_gate() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].speed_kt = 140.0;
+        let mut at_gate = false;
+        for _ in 0..400 {
+            for command in autopilot_commands(&airport) {
+                let _ = issue_command(&mut airport, command);
             }
-            WeatherCondition::InclementWeather => {
-                let normal = Normal::new(50.0, 10.0).unwrap();
-                let mut s = normal.sample(&mut rand::thread_rng());
-                s = if s < 50.0 && s > 60.0 { 60.0 } else { s };
-                s
+            update_aircraft_position(&mut airport, None);
+            airport.tick += 1;
+            if matches!(airport.planes[0].current_action, Action::AtGate(_)) {
+                at_gate = true;
+                break;
             }
+        }
+        assert!(at_gate, "{:?}", airport.planes[0]);
+        assert_eq!(airport.missed_approaches, 0);
+    }
+
+    #[test]
+    fn autopilot_does_not_repeat_pushback() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Snow;
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        // Snow refuses pushback until the plane is de-iced
+        assert!(autopilot_commands(&airport).is_empty());
+        airport.weather.condition = WeatherCondition::Clear;
+        assert_eq!(autopilot_commands(&airport), vec![format!("p {}", name)]);
+        // Nor is pushback asked for again while a controller's clearance awaits readback
+        assert!(airport.hold_for_readback(&format!("p {}", name)).is_ok());
+        assert!(autopilot_commands(&airport).is_empty());
+    }
+
+    #[test]
+    fn predicts_converging_planes() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        let runway = airport.runways["1"].clone();
+        let start = runway_threshold(&airport.map, &runway).unwrap();
+        let mut ahead = start;
+        for _ in 0..3 {
+            ahead = runway.side.clone().go(&airport.map, ahead).unwrap();
+        }
+        airport.planes[0].position = start;
+        airport.planes[0].runway = runway.clone();
+        airport.planes[0].current_action = Action::Takeoff;
+        airport.planes[1].position = ahead;
+        airport.planes[1].current_action = Action::HoldPosition;
+        airport.planes[1].altitude_ft = 1000.0;
+        let ids = (airport.planes[0].id, airport.planes[1].id);
+        let conflicts = predict_conflicts(&airport, 5);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].planes, ids);
+        assert_eq!(conflicts[0].position, ahead);
+        assert!(conflicts[0].ticks <= 3);
+        // The prediction leaves the real planes where they are
+        assert_eq!(airport.planes[0].position, start);
+        airport.conflict_horizon = Some(5);
+        warn_of_predicted_conflicts(&mut airport, &mut std::io::sink());
+        assert!(airport.predicted_conflicts.contains(&ids));
+    }
+
+    #[test]
+    fn departures_file_varied_runways() {
+        let mut a = args();
+        a.departure_runways = DepartureRunways::Random;
+        let mut airport = construct_airport(&a);
+        let mut second = airport.runways["1"].clone();
+        second.name = 2;
+        airport.runways.insert("2".into(), second);
+        airport.gate_runways.clear();
+        let spawned = |airport: &mut Airport| {
+            spawn_landing_aircraft(airport, true);
+            // Gone, so that its gate is free for the next one
+            let plane = airport.planes.last_mut().unwrap();
+            plane.out_of_map = true;
+            plane.runway.name
         };
+        let seen = (0..40)
+            .map(|_| spawned(&mut airport))
+            .collect::<HashSet<usize>>();
+        assert_eq!(seen, HashSet::from([1, 2]));
+        // A gate with its own runway keeps it
+        for gate in airport.gates.keys().cloned().collect::<Vec<String>>() {
+            airport.gate_runways.insert(gate, "1".into());
+        }
+        assert!((0..20).all(|_| spawned(&mut airport) == 1));
     }
 
-    if prob == 100 || rng.gen_range(0..100) < 5 {
-        let normal_wind_direction = Normal::new(weather.wind_direction as f64, 20.0).unwrap();
-        let dir = normal_wind_direction.sample(&mut rand::thread_rng());
-        weather.wind_direction = if dir > 360.0 {
-            f64::min(dir - 360.0, 360.0)
-        } else if dir < 0.0 {
-            f64::max(dir + 360.0, 0.0)
-        } else {
-            dir
-        } as usize;
+    #[test]
+    fn verbose_clearances_longer_than_terse() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, true);
+        let mut plane = airport.planes[0].clone();
+        plane.current_action = Action::Pushback;
+        let mut clearances = vec![];
+        for phraseology in [
+            Phraseology::Terse,
+            Phraseology::Standard,
+            Phraseology::Verbose,
+        ] {
+            airport.phraseology = phraseology;
+            clearances.push(create_atc_clearance(&airport, &plane));
+        }
+        assert!(clearances[0].starts_with(&format!("{}, ", plane.name)));
+        assert!(clearances[2].contains("Winds") && clearances[2].ends_with("Read back."));
+        assert!(clearances[0].len() < clearances[1].len());
+        assert!(clearances[1].len() < clearances[2].len());
     }
-}
 
-fn spawn_landing_aircraft(airport: &mut Airport, at_gate: bool) {
-    // Spawn new aircraft for landing
-    let spacing = &airport.map.spacing;
-    let runways = &airport.runways;
-    let num_planes = airport.planes.len();
+    #[test]
+    fn takeoff_clearance_reads_back_gusts() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, true);
+        let mut plane = airport.planes[0].clone();
+        plane.current_action = Action::Takeoff;
+        airport.weather.wind_direction = 270;
+        airport.weather.wind_speed = 10.0;
+        airport.weather.wind_gust = Some(18.0);
+        let clearance = create_atc_clearance(&airport, &plane);
+        assert!(
+            clearance.contains("winds two seven zero at one zero, gusting one eight knots"),
+            "{}",
+            clearance
+        );
+        airport.weather.wind_gust = None;
+        let clearance = create_atc_clearance(&airport, &plane);
+        assert!(!clearance.contains("gusting"), "{}", clearance);
+    }
 
-    let mut rng = rand::thread_rng();
-    let airway_ids: Vec<_> = AIRWAY_IDS.keys().cloned().collect();
-    let plane_name = airway_ids[rng.gen_range(0..airway_ids.len())].to_string()
-        + &rng.gen_range(100..400).to_string();
+    #[test]
+    fn arrivals_spaced_on_a_runway() {
+        let mut a = args();
+        a.arrival_spacing = Some(30);
+        let mut airport = construct_airport(&a);
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, false);
+        let first = airport.planes[0].id;
+        update_aircraft_position(&mut airport, Some(first));
+        update_aircraft_position(&mut airport, Some(first));
+        spawn_landing_aircraft(&mut airport, false);
+        let names = airport
+            .planes
+            .iter()
+            .map(|p| p.name.clone())
+            .collect::<Vec<String>>();
+        assert!(issue_command(&mut airport, format!("l {} 1", names[0])).is_ok());
+        // Clearing the first arrival again, for another exit, is not held up by its own spacing
+        assert!(issue_command(&mut airport, format!("l {} 1 7", names[0])).is_ok());
+        let second = issue_command(&mut airport, format!("l {} 1", names[1]));
+        assert!(second.unwrap_err().reason.contains("not been vacated"));
+        // A plane that cannot land at all is told so, rather than about the spacing
+        spawn_landing_aircraft(&mut airport, true);
+        let at_gate = format!("l {} 1", airport.planes[2].name);
+        let refused = issue_command(&mut airport, at_gate).unwrap_err();
+        assert_eq!(refused.reason, "Not a valid action when at gate");
+        airport.planes[2].out_of_map = true;
+        // Once the first arrival is gone the spacing still has to run out
+        airport.planes[0].out_of_map = true;
+        let second = issue_command(&mut airport, format!("l {} 1", names[1]));
+        assert!(second.unwrap_err().reason.contains("30 ticks apart"));
+        airport.tick += 30;
+        assert!(issue_command(&mut airport, format!("l {} 1", names[1])).is_ok());
+    }
 
-    let (position, current_action) = match at_gate {
-        true => {
-            let random_gate = airport
-                .gates
-                .values()
-                .collect::<Vec<_>>()
-                .choose(&mut rand::thread_rng())
-                .unwrap()
-                .to_owned();
-            (
-                random_gate.position,
-                Action::AtGate((random_gate.number.clone(), AtGateAction::Standby)),
+    #[test]
+    fn prefilled_gates_mid_turnaround() {
+        let mut airport = construct_airport(&args());
+        assert_eq!(airport.gates.len(), 6);
+        prefill_gates(&mut airport, 0.5);
+        let steps = airport
+            .planes
+            .iter()
+            .map(|plane| match &plane.current_action {
+                Action::AtGate((gate, step)) => {
+                    assert!(plane.flight_type.turnaround().contains(step));
+                    (gate.clone(), step.clone())
+                }
+                action => panic!("{:?} is not at a gate", action),
+            })
+            .collect::<Vec<(String, AtGateAction)>>();
+        assert_eq!(steps.len(), 3);
+        let gates = steps.iter().map(|(gate, _)| gate).collect::<HashSet<_>>();
+        assert_eq!(gates.len(), 3);
+        assert!(steps.iter().any(|(_, step)| *step != AtGateAction::Standby));
+        // Every gate taken, and no more planes squeezed in
+        prefill_gates(&mut airport, 1.0);
+        assert_eq!(airport.planes.len(), 6);
+        assert!(airport.free_gates().is_empty());
+    }
+
+    #[test]
+    fn map_without_runways_spawns_nothing() {
+        // The shipped map with every runway tile blanked out
+        let path = temp_path("no_runways.map");
+        let map = std::fs::read_to_string("./src/airport.map").unwrap();
+        let blanked = map
+            .lines()
+            .map(|line| {
+                line.split(',')
+                    .map(|tile| match tile.starts_with('R') {
+                        true => "...",
+                        false => tile,
+                    })
+                    .collect::<Vec<&str>>()
+                    .join(",")
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        std::fs::write(&path, blanked).unwrap();
+        let spacing = Spacing {
+            top_bottom: 2,
+            left_right: 20,
+        };
+        let mut airport = construct_airport(&args());
+        airport.map = build_airport_map(path.to_str().unwrap(), spacing).unwrap();
+        airport.runways = Runway::new(&airport.map);
+        assert!(validate_map(&airport.map).contains(&"The map has no runways".to_string()));
+        assert!(airport.default_runway().is_none());
+        // Nor is an airport built from it
+        let built = construct_airport_from(&args(), path.to_str().unwrap());
+        assert_eq!(
+            built.err(),
+            Some("The airport map has no runways planes can use".to_string())
+        );
+        spawn_landing_aircraft(&mut airport, false);
+        spawn_landing_aircraft(&mut airport, true);
+        assert!(airport.planes.is_empty());
+    }
+
+    #[test]
+    fn map_without_runway_one_uses_another() {
+        let mut airport = construct_airport(&args());
+        for point in airport.map.map.iter_mut().flatten() {
+            if let MapPoint::Runway((name @ 1, _)) = point {
+                *name = 2;
+            }
+        }
+        airport.runways = Runway::new(&airport.map);
+        assert_eq!(airport.default_runway().map(|runway| runway.name), Some(2));
+        spawn_landing_aircraft(&mut airport, false);
+        spawn_landing_aircraft(&mut airport, true);
+        assert_eq!(airport.planes.len(), 2);
+        assert!(airport.planes.iter().all(|plane| plane.runway.name == 2));
+    }
+
+    #[test]
+    fn collision_reported_with_custom_template() {
+        let mut a = args();
+        a.collision_message = Some("{first} hit {second} on {location}!".to_string());
+        let mut airport = construct_airport(&a);
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        // Both on the first tile of taxiway 7
+        airport.planes[0].position = (8, 23);
+        airport.planes[1].position = (8, 23);
+        let mut out = vec![];
+        detect_and_handle_collisions(&mut airport, &mut score(), &mut out);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "{} hit {} on taxiway 7!",
+                airport.planes[0].name, airport.planes[1].name
             )
+        );
+        // A plane striking a ground vehicle is reported the same way
+        airport.planes[1].position = (9, 23);
+        airport.vehicles.push(GroundVehicle {
+            id: 1,
+            kind: GroundVehicleKind::Tug,
+            position: (8, 23),
+            assisting: None,
+        });
+        let mut out = vec![];
+        detect_and_handle_collisions(&mut airport, &mut score(), &mut out);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{} hit tug 1 on taxiway 7!", airport.planes[0].name)
+        );
+    }
+
+    #[test]
+    fn taxiway_named_with_a_letter() {
+        // The shipped map with taxiway 4 renamed to A
+        let path = temp_path("lettered_taxiway.map");
+        let map = std::fs::read_to_string("./src/airport.map").unwrap();
+        std::fs::write(&path, map.replace("T4", "TA")).unwrap();
+        let a = args();
+        let spacing = Spacing {
+            top_bottom: a.spacing_tb,
+            left_right: a.spacing_lr,
+        };
+        let mut airport = construct_airport(&a);
+        airport.map = build_airport_map(path.to_str().unwrap(), spacing).unwrap();
+        assert!(airport.map.has_taxiway("A") && !airport.map.has_taxiway("4"));
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (2, 40);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldPosition;
+        let name = airport.planes[0].name.clone();
+        let plane = parse_user_input(format!("t2g {} 3 via a,7", name), &airport).unwrap();
+        assert_eq!(plane.taxi_route, vec!["A", "7"]);
+        let clearance = create_atc_clearance(&airport, &plane);
+        assert!(clearance.contains("via taxiway A, 7"), "{}", clearance);
+        assert!(airport.set_closed("tA", true).is_ok());
+        assert_eq!(airport.closed_taxiways, vec!["A"]);
+    }
+
+    #[test]
+    fn weather_follows_custom_table() {
+        let path = temp_path("weather_table.txt");
+        std::fs::write(&path, "# Always snowing\nclear -> snow: 1.0\n").unwrap();
+        let mut a = args();
+        a.weather_table = Some(path.to_str().unwrap().to_string());
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        simulate_weather(&mut airport, 1.0);
+        assert_eq!(airport.weather.condition, WeatherCondition::Snow);
+        // Nothing in the table leads out of snow
+        for _ in 0..100 {
+            simulate_weather(&mut airport, 1.0);
         }
-        false => ((spacing.top_bottom, 0), Action::InAir),
-    };
+        assert_eq!(airport.weather.condition, WeatherCondition::Snow);
+        assert!("clear -> fog: 0.1".parse::<WeatherTransition>().is_err());
+        assert!("clear -> rain".parse::<WeatherTransition>().is_err());
+        // A table with a bad line is reported rather than played
+        std::fs::write(&path, "clear -> fog: 0.1\n").unwrap();
+        assert!(construct_airport_from(&a, "./src/airport.map").is_err());
+    }
 
-    let plane = Plane {
-        id: num_planes + 1,
-        name: plane_name,
-        current_action,
-        position,
-        runway: runways["1"].clone(),
-        out_of_map: false,
-    };
+    #[test]
+    fn medevac_lands_and_departs_first() {
+        let mut airport = construct_airport(&args());
+        spawn_landing_aircraft(&mut airport, false);
+        let first = airport.planes[0].id;
+        update_aircraft_position(&mut airport, Some(first));
+        update_aircraft_position(&mut airport, Some(first));
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].fuel_ticks = 500;
+        airport.planes[1].fuel_ticks = 500;
+        let (closer, farther) = (airport.planes[0].id, airport.planes[1].id);
+        assert_eq!(landing_sequence(&airport), vec![closer, farther]);
+        let name = airport.planes[1].name.clone();
+        assert!(airport.set_priority(&name, "medevac").is_ok());
+        assert!(airport.set_priority(&name, "urgent").is_err());
+        assert_eq!(landing_sequence(&airport), vec![farther, closer]);
+        // Low fuel still comes first
+        airport.planes[0].fuel_ticks = 60;
+        assert_eq!(landing_sequence(&airport), vec![closer, farther]);
+        // The autopilot clears the medevac flight for the free runway
+        airport.planes[0].fuel_ticks = 500;
+        assert_eq!(autopilot_commands(&airport), vec![format!("l {} 1", name)]);
 
-    airport.planes.push(plane);
-}
+        // Without the autopilot too, a medevac departure holding short goes first
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        for _ in 0..2 {
+            spawn_landing_aircraft(&mut airport, true);
+        }
+        for plane in airport.planes.iter_mut() {
+            plane.position = (3, 23);
+            plane.current_action = Action::HoldPosition;
+        }
+        let (normal, medevac) = (
+            airport.planes[0].name.clone(),
+            airport.planes[1].name.clone(),
+        );
+        assert!(airport.set_priority(&medevac, "medevac").is_ok());
+        let refused = parse_user_input(format!("tor {} 1", normal), &airport);
+        assert_eq!(
+            refused.err().map(|e| e.reason),
+            Some(format!("medevac flight {} departs runway 1 first", medevac))
+        );
+        assert!(parse_user_input(format!("tor {} 1", medevac), &airport).is_ok());
+    }
 
-fn user_input_thread(sender: std::sync::mpsc::Sender<String>) {
-    let stream = TcpStream::connect("localhost:8080").unwrap();
-    let mut reader = BufReader::new(stream);
-    loop {
-        let mut user_input = String::new();
-        reader
-            .read_line(&mut user_input)
-            .expect("Failed to read user input");
+    #[test]
+    fn clearance_waits_for_readback() {
+        let mut a = args();
+        a.readback_ratio = 1.0;
+        let mut airport = construct_airport(&a);
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        // Left unread, a clearance lapses and the plane asks again
+        assert!(airport.hold_for_readback(&format!("p {}", name)).is_ok());
+        airport.tick += READBACK_TICKS;
+        expire_readbacks(&mut airport);
+        assert!(airport.pending_readbacks.is_empty());
+        assert!(airport.requests[0].message.ends_with("say again."));
+        let (sender, receiver) = channel();
+        let mut spoken = Vec::<String>::new();
+        sender
+            .send(("test".to_string(), format!("p {}", name)))
+            .unwrap();
+        update_aircraft_from_user_input(&mut airport, &receiver, &mut spoken);
+        assert!(spoken[0].contains("pushback approved"), "{:?}", spoken);
+        assert!(matches!(
+            airport.planes[0].current_action,
+            Action::AtGate(_)
+        ));
+        assert_eq!(airport.pending_readbacks.len(), 1);
+        // A readback of another clearance is not enough
+        for command in [format!("rb hp {}", name), format!("rb P {}", name)] {
+            sender.send(("test".to_string(), command)).unwrap();
+        }
+        update_aircraft_from_user_input(&mut airport, &receiver, &mut spoken);
+        assert_eq!(airport.planes[0].current_action, Action::Pushback);
+        assert!(airport.pending_readbacks.is_empty());
+        // Scripted commands are carried out at once
+        spawn_landing_aircraft(&mut airport, true);
+        let scripted = airport.planes[1].name.clone();
+        sender
+            .send(("script".to_string(), format!("p {}", scripted)))
+            .unwrap();
+        update_aircraft_from_user_input(&mut airport, &receiver, &mut spoken);
+        assert_eq!(airport.planes[1].current_action, Action::Pushback);
+        assert!(airport.pending_readbacks.is_empty());
+    }
 
-        // Trim whitespace and newline characters from the input
-        user_input = user_input.trim().to_string();
+    #[test]
+    fn side_panel_beside_the_map() {
+        let mut a = args();
+        a.layout = "side".parse().unwrap();
+        let mut airport = construct_airport(&a);
+        render(&mut airport, &score(), &mut Vec::new());
+        let line = airport
+            .screen
+            .iter()
+            .position(|row| row.concat().contains("Airlines"))
+            .unwrap();
+        // The dashboard takes the first two lines, so the map rows follow them
+        let map_row = &airport.map.map[line - 2];
+        let glyphs = map_row
+            .iter()
+            .map(|point| point.glyph())
+            .collect::<Vec<_>>();
+        assert_eq!(airport.screen[line][..map_row.len()].to_vec(), glyphs);
+        assert!(airport.screen.len() <= airport.map.map.len() + 2);
+    }
 
-        // Send the user input to the main game loop through the channel
-        sender.send(user_input).expect("Failed to send user input");
+    #[test]
+    fn debug_command_forces_weather() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        let (sender, receiver) = channel();
+        let mut spoken = Vec::<String>::new();
+        // Without --debug the command is refused
+        sender
+            .send(("test".to_string(), "wx inclement".to_string()))
+            .unwrap();
+        update_aircraft_from_user_input(&mut airport, &receiver, &mut spoken);
+        assert_eq!(airport.weather.condition, WeatherCondition::Clear);
+
+        let mut a = args();
+        a.debug = true;
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[0].current_action = Action::HoldShort;
+        let name = airport.planes[0].name.clone();
+        let runway = airport.planes[0].runway.name;
+        for command in ["wx inclement".to_string(), format!("t {} {}", name, runway)] {
+            sender.send(("test".to_string(), command)).unwrap();
+        }
+        update_aircraft_from_user_input(&mut airport, &receiver, &mut spoken);
+        assert_eq!(
+            airport.weather.condition,
+            WeatherCondition::InclementWeather
+        );
+        assert_eq!(airport.planes[0].current_action, Action::HoldShort);
     }
-}
 
-fn tcp_listener() {
-    let listener = TcpListener::bind("localhost:8080").expect("Failed to bind address");
-    for stream in listener.incoming() {
-        let mut stream = stream.unwrap();
-        let stdin = io::stdin();
-        for line in stdin.lock().lines() {
-            let line = line.unwrap();
-            stream.write(line.as_bytes()).unwrap();
-            stream.write(b"\n").unwrap();
-            stream.flush().unwrap();
+    #[test]
+    fn commands_in_any_case_and_spacing() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, false);
+        let name = airport.planes[0].name.clone();
+        let runway = airport.planes[0].runway.name;
+        for command in [
+            format!("L {} {}", name, runway),
+            format!("l   {}\t {}  ", name.to_lowercase(), runway),
+        ] {
+            let plane = parse_user_input(command, &airport).unwrap();
+            assert!(plane.cleared_to_land);
         }
+        assert_eq!(airport.expand_alias("  LIST  "), "list".to_string());
+        // Each keyword still takes its own number of arguments
+        assert!(parse_user_input(format!("HP {} {}", name, runway), &airport).is_err());
+        assert!(parse_user_input(format!("L {}", name), &airport).is_err());
+        assert!(parse_user_input("   ".to_string(), &airport).is_err());
     }
-}
 
-// Main function to run the game
-fn main() {
-    let args = Args::parse();
-    if !args.sim {
-        tcp_listener();
+    #[test]
+    fn arrival_calls_final_once() {
+        let mut a = args();
+        a.final_fix = Some(8);
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].speed_kt = APPROACH_SPEED_KT;
+        airport.planes[0].cleared_to_land = true;
+        let mut calls = vec![];
+        for _ in 0..60 {
+            update_aircraft_position(&mut airport, None);
+            calls.extend(airport.requests.drain(..).map(|request| request.message));
+            if airport.planes[0].current_action != Action::InAir {
+                break;
+            }
+        }
+        assert!(matches!(airport.planes[0].current_action, Action::Land(_)));
+        assert_eq!(
+            calls,
+            vec![format!(
+                "{}, established on final, runway {}.",
+                airport.planes[0].name, airport.planes[0].runway.name
+            )]
+        );
     }
 
-    // Initialize and run your ATC game here
-    let mut airport = construct_airport();
-    let time: Time = Time { step_duration: 1 };
-    const LANDING_INTERVAL: usize = 60;
-    let mut score = Score {
-        takeoff: 0,
-        crash: 0,
-    };
+    #[test]
+    fn reloaded_map_picks_up_new_runways() {
+        let mut a = args();
+        a.debug = true;
+        let mut airport = construct_airport(&a);
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        assert!(airport.runways.contains_key("1"));
+        // The shipped map with runway 1 renamed to runway 5
+        let path = temp_path("reload_map.map");
+        let map = std::fs::read_to_string("./src/airport.map").unwrap();
+        std::fs::write(&path, map.replace("R1", "R5")).unwrap();
+        airport.map_path = path.to_str().unwrap().to_string();
+        assert!(airport.reload_map().is_ok());
+        assert!(airport.runways.contains_key("5"));
+        assert!(!airport.runways.contains_key("1"));
+        // The plane at the gate is still there, now expecting a runway that exists
+        assert_eq!(airport.planes[0].name, name);
+        assert!(airport
+            .runways
+            .contains_key(&airport.planes[0].runway.name.to_string()));
+        // A map with a typo, or without runways, is turned down and the running one kept
+        for broken in [map.replace("R1", "R%"), map.replace("R1", "...")] {
+            std::fs::write(&path, broken).unwrap();
+            assert!(airport.reload_map().is_err());
+            assert!(airport.runways.contains_key("5"));
+            assert_eq!(airport.planes[0].name, name);
+        }
+    }
 
-    // Channel for communication between threads
-    let (sender, receiver): (std::sync::mpsc::Sender<String>, Receiver<String>) = channel();
+    #[test]
+    fn reload_refuses_maps_that_do_not_fit() {
+        let mut a = args();
+        a.debug = true;
+        a.gate_runway = vec![("2".to_string(), "1".to_string())];
+        let mut airport = construct_airport(&a);
+        let path = temp_path("reload_refused.map");
+        airport.map_path = path.to_str().unwrap().to_string();
+        let map = std::fs::read_to_string("./src/airport.map").unwrap();
+        // A gate numbered twice, and a map without the runway --gate-runway sends gate 2 to
+        for (broken, problem) in [
+            (map.replace("G2X", "G1X"), "Gate 1 appears more than once"),
+            (
+                map.replace("R1", "R5"),
+                "--gate-runway 2=1 no longer fits the map",
+            ),
+        ] {
+            std::fs::write(&path, broken).unwrap();
+            let error = airport.reload_map().unwrap_err().reason;
+            assert!(error.contains(problem), "{}", error);
+            assert!(airport.runways.contains_key("1"));
+            assert_eq!(airport.gates.len(), 6);
+        }
+    }
 
-    // Separate thread for handling user input
-    std::thread::spawn(move || {
-        user_input_thread(sender);
-    });
+    #[test]
+    fn reload_drops_planes_whose_gate_is_gone() {
+        let mut a = args();
+        a.debug = true;
+        let mut airport = construct_airport(&a);
+        // One plane parked at gate 1, and one that has already diverted
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[0].position = airport.gates["1"].position;
+        airport.planes[0].current_action = Action::AtGate(("1".to_string(), AtGateAction::Standby));
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[1].out_of_map = true;
+        airport.planes[1].diverted = true;
+        airport.diversions = 1;
+        airport.closed_runways.push(1);
+        // Gate 1 is renumbered, and runway 1 with it gone
+        let path = temp_path("reload_gate_gone.map");
+        let map = std::fs::read_to_string("./src/airport.map").unwrap();
+        std::fs::write(&path, map.replace("G1X", "G7X").replace("R1", "R5")).unwrap();
+        airport.map_path = path.to_str().unwrap().to_string();
+        assert!(airport.reload_map().unwrap().contains("1 plane(s) removed"));
+        assert_eq!(airport.planes.len(), 1);
+        assert!(airport.planes[0].diverted);
+        assert!(!airport.gates["7"].is_occupied);
+        assert!(airport.closed_runways.is_empty());
+        let mut score = score();
+        update_score(&mut airport, &mut score);
+        assert!(summary_text(&airport, &score).contains("In the air:\t0"));
+    }
 
-    // TTS
-    let mut tts = Tts::default().expect("Could not initialize TTS");
+    #[test]
+    fn dashboard_shows_wind_on_each_runway() {
+        let mut a = args();
+        a.crosswind_limit = vec![("light".to_string(), 15.0)];
+        let mut airport = construct_airport(&a);
+        let heading = airport.runways["1"].heading as usize;
+        // Straight across runway 1, above the limit
+        airport.weather.wind_direction = (heading + 90) % 360;
+        airport.weather.wind_speed = 20.0;
+        let winds = runway_winds(&airport);
+        assert!(
+            winds.contains(&format!(
+                "1 cross {}20 kn{} tail 0 kn",
+                BOLD_RED_COLOR, RESET_COLOR
+            )),
+            "{}",
+            winds
+        );
+        // Straight down runway 1 from behind
+        airport.weather.wind_direction = (heading + 180) % 360;
+        airport.weather.wind_speed = 10.0;
+        assert!(runway_winds(&airport).contains("1 cross 0 kn tail 10 kn"));
+        // A second runway, heading south, gets a line of its own after runway 1
+        let south = Runway {
+            name: 2,
+            side: Direction::South,
+            noise_sensitive: false,
+            heading: 180,
+        };
+        airport.runways.insert("2".to_string(), south);
+        airport.weather.wind_direction = 360;
+        airport.weather.wind_speed = 10.0;
+        let winds = runway_winds(&airport);
+        assert!(winds.starts_with("Runway winds: 1 cross"), "{}", winds);
+        assert!(winds.ends_with("   2 cross 0 kn tail 10 kn"), "{}", winds);
+        airport.weather.wind_direction = 270;
+        airport.weather.wind_speed = 20.0;
+        let winds = runway_winds(&airport);
+        assert!(
+            winds.ends_with(&format!(
+                "   2 cross {}20 kn{} tail 0 kn",
+                BOLD_RED_COLOR, RESET_COLOR
+            )),
+            "{}",
+            winds
+        );
+    }
 
-    // Spawn the first aircraft at a gate
-    spawn_landing_aircraft(&mut airport, true);
+    #[test]
+    fn autopilot_departs_after_hold_short_timeout() {
+        let mut a = args();
+        a.hold_short_timeout = Some(10);
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, true);
+        spawn_landing_aircraft(&mut airport, true);
+        // The first plane taxis to the hold line of runway 1 while the second sits on it
+        airport.planes[0].current_action = Action::HoldShort;
+        airport.planes[0].position = (6, 23);
+        airport.planes[0].intention = "to runway 1".to_string();
+        let runway = airport.runways["1"].clone();
+        airport.planes[1].position = runway_threshold(&airport.map, &runway).unwrap();
+        airport.planes[1].current_action = Action::HoldPosition;
+        let departure = airport.planes[0].id;
+        let mut held_short = None;
+        let mut lined_up = None;
+        for tick in 0..40 {
+            airport.tick = tick;
+            // The runway frees up before the timeout runs out
+            if tick == 5 {
+                airport.planes[1].out_of_map = true;
+            }
+            for command in autopilot_commands(&airport) {
+                let _ = issue_command(&mut airport, command);
+            }
+            update_aircraft_position(&mut airport, Some(departure));
+            held_short = held_short.or(airport.planes[0].held_short_tick);
+            if lined_up.is_none()
+                && matches!(airport.planes[0].current_action, Action::TaxiOntoRunway(_))
+            {
+                lined_up = Some(tick);
+            }
+            if airport.planes[0].current_action == Action::Takeoff {
+                break;
+            }
+        }
+        let held_short = held_short.unwrap();
+        assert!(held_short < 5);
+        assert_eq!(lined_up, Some(held_short + 10));
+        assert_eq!(airport.planes[0].current_action, Action::Takeoff);
+        assert_eq!(airport.planes[0].held_short_tick, None);
+    }
 
-    let mut timer: usize = 0;
-    loop {
-        let spawn_plane = timer % LANDING_INTERVAL == 0;
-        update_game_state(&mut airport, spawn_plane, &mut score, &receiver, &mut tts);
-        // Sleep for a bit
-        thread::sleep(Duration::from_secs(time.step_duration as u64));
-        timer += 1;
-        if score.crash > 0 {
-            break;
+    #[test]
+    fn clearance_in_another_language() {
+        let path = temp_path("lang.txt");
+        std::fs::write(
+            &path,
+            "# Français\n\
+            pushback = {airline} {code}, repoussage approuvé, piste {runway} prévue.\n\
+            plane_not_found = Avion introuvable\n\
+            invalid_at_gate = Action impossible à la porte\n",
+        )
+        .unwrap();
+        let mut a = args();
+        a.lang = Some(path.to_str().unwrap().to_string());
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        let plane = parse_user_input(format!("p {}", name), &airport).unwrap();
+        let clearance = create_atc_clearance(&airport, &plane);
+        assert!(
+            clearance.ends_with(&format!(
+                ", repoussage approuvé, piste {} prévue.",
+                plane.runway.spoken_name()
+            )),
+            "{}",
+            clearance
+        );
+        // A translated rejection keeps its code
+        assert_eq!(
+            parse_user_input("p XX000".to_string(), &airport).unwrap_err(),
+            Rejection::new(RejectCode::UnknownAircraft, "Avion introuvable".to_string())
+        );
+        assert_eq!(
+            parse_user_input(format!("hp {}", name), &airport).unwrap_err(),
+            Rejection::from("Action impossible à la porte")
+        );
+        // Messages left out of the bundle keep their default wording
+        airport.planes[0].current_action = Action::HoldShort;
+        assert!(create_atc_clearance(&airport, &airport.planes[0]).contains("hold short of runway"));
+        // A bundle with a message id the simulator does not know is reported
+        std::fs::write(&path, "push_back = Repoussage\n").unwrap();
+        let built = construct_airport_from(&a, "./src/airport.map");
+        assert_eq!(
+            built.err(),
+            Some("Unknown message id push_back in message bundle".to_string())
+        );
+    }
+
+    #[test]
+    fn uncleared_arrival_diverts_after_limit() {
+        let mut a = args();
+        a.max_airborne = Some(5);
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, false);
+        let since = airport.planes[0].airborne_since.unwrap();
+        for tick in since..since + 5 {
+            airport.tick = tick;
+            update_aircraft_position(&mut airport, None);
+            divert_overdue_arrivals(&mut airport);
+            assert!(!airport.planes[0].out_of_map);
+        }
+        airport.tick = since + 5;
+        divert_overdue_arrivals(&mut airport);
+        assert!(airport.planes[0].out_of_map);
+        assert!(airport.planes[0].diverted);
+        assert_eq!(airport.diversions, 1);
+    }
+
+    #[test]
+    fn rejected_command_has_a_code() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        let result = run_command(&mut airport, format!("zz {} 1", name));
+        assert_eq!(
+            result,
+            CommandResult::Rejected {
+                reason: "Invalid command: zz".to_string(),
+                code: RejectCode::Syntax,
+            }
+        );
+        assert_eq!(result.response_line(), "ERR syntax Invalid command: zz");
+        for (command, code) in [
+            ("p XX000".to_string(), RejectCode::UnknownAircraft),
+            (format!("tor {} 9", name), RejectCode::UnknownTarget),
+            (format!("t {} 1", name), RejectCode::Refused),
+        ] {
+            match run_command(&mut airport, command) {
+                CommandResult::Rejected { code: got, .. } => assert_eq!(got, code),
+                accepted => panic!("{:?}", accepted),
+            }
+        }
+        let result = run_command(&mut airport, format!("p {}", name));
+        assert!(result.response_line().starts_with(&format!("OK {} ", name)));
+    }
+
+    #[test]
+    fn every_console_command_gets_one_reply() {
+        let mut a = args();
+        a.readback_ratio = 1.0;
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, true);
+        let name = airport.planes[0].name.clone();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || user_input_thread(listener, None, sender));
+        let mut console = TcpStream::connect(address).unwrap();
+        console
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let mut replies = BufReader::new(console.try_clone().unwrap());
+        for (command, expected) in [
+            ("list".to_string(), "OK".to_string()),
+            (
+                "rate 5".to_string(),
+                "OK Arrivals every 5 ticks.".to_string(),
+            ),
+            ("close 9".to_string(), "ERR unknown_target".to_string()),
+            ("tug XX000".to_string(), "ERR unknown_aircraft".to_string()),
+            (
+                "prio XX000 medevac".to_string(),
+                "ERR unknown_aircraft".to_string(),
+            ),
+            (
+                format!("cross {} 9", name),
+                "ERR unknown_target".to_string(),
+            ),
+            ("wx snow".to_string(), "ERR refused".to_string()),
+            ("reload".to_string(), "ERR refused".to_string()),
+            ("rb hp XX000".to_string(), "ERR refused".to_string()),
+            // Held until read back, with the clearance to read back as the reply
+            (format!("p {}", name), "OK ".to_string()),
+            (format!("rb p {}", name), format!("OK {} ", name)),
+        ] {
+            console
+                .write_all(format!("{}\n", command).as_bytes())
+                .unwrap();
+            let command = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+            let (forward, game) = channel();
+            forward.send(command).unwrap();
+            update_aircraft_from_user_input(&mut airport, &game, &mut Vec::<String>::new());
+            let mut reply = String::new();
+            replies.read_line(&mut reply).unwrap();
+            assert!(reply.starts_with(&expected), "{}", reply);
+        }
+    }
+
+    #[test]
+    fn reserved_name_never_generated() {
+        let mut a = args();
+        a.flight_numbers = AIRWAY_IDS
+            .keys()
+            .map(|airline| (airline.to_string(), (100, 101)))
+            .collect();
+        a.reserve_name = vec!["aa100".to_string()];
+        let mut airport = construct_airport(&a);
+        for _ in 0..500 {
+            assert_ne!(airport.unique_plane_name().unwrap(), "AA100");
+        }
+        for _ in 0..20 {
+            spawn_landing_aircraft(&mut airport, false);
+            assert!(airport.planes.iter().all(|plane| plane.name != "AA100"));
+            airport.planes.clear();
+        }
+    }
+
+    #[test]
+    fn taxi_route_previewed_on_the_map() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Clear;
+        spawn_landing_aircraft(&mut airport, false);
+        airport.planes[0].position = (2, 40);
+        airport.planes[0].altitude_ft = 0.0;
+        airport.planes[0].current_action = Action::HoldPosition;
+        let name = airport.planes[0].name.clone();
+        let (position, runway) = (airport.planes[0].position, airport.planes[0].runway.clone());
+        let route = trace_taxi_tiles(&airport.map, position, &runway, "3").unwrap();
+        assert!(route.len() > 5);
+        assert!(matches!(
+            run_command(&mut airport, format!("t2g {} 3", name)),
+            CommandResult::Accepted { .. }
+        ));
+        assert_eq!(airport.path_preview, route);
+        render(&mut airport, &score(), &mut Vec::new());
+        // The dashboard takes the first two lines of the frame
+        let highlighted = |airport: &Airport, (row, col): (usize, usize)| {
+            airport.screen[row + 2][col].starts_with(CYAN_COLOR)
+        };
+        // The plane itself is drawn in its airline's colour, which may be cyan too
+        let plane = airport.planes[0].footprint();
+        for tile in route.iter().filter(|tile| !plane.contains(tile)) {
+            assert!(highlighted(&airport, *tile), "{:?}", tile);
         }
+        // The highlight fades after a few ticks
+        airport.tick += PATH_PREVIEW_TICKS;
+        render(&mut airport, &score(), &mut Vec::new());
+        assert!(route
+            .iter()
+            .filter(|tile| !plane.contains(tile))
+            .all(|tile| !highlighted(&airport, *tile)));
+    }
+
+    #[test]
+    fn immediate_takeoff_waives_spacing() {
+        let mut a = args();
+        a.arrival_spacing = Some(30);
+        let mut airport = construct_airport(&a);
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.weather.wind_speed = 0.0;
+        spawn_landing_aircraft(&mut airport, true);
+        airport.planes[0].current_action = Action::HoldShort;
+        let name = airport.planes[0].name.clone();
+        // An arrival was just cleared to land on runway 1
+        airport.last_arrival_ticks.insert(1, airport.tick);
+        let error = parse_user_input(format!("t {} 1", name), &airport)
+            .unwrap_err()
+            .reason;
+        assert!(
+            error.starts_with("Departures on runway 1 must wait"),
+            "{}",
+            error
+        );
+        let plane = parse_user_input(format!("it {} 1", name), &airport).unwrap();
+        assert_eq!(plane.current_action, Action::Takeoff);
+        assert!(create_atc_clearance(&airport, &plane).contains("cleared for immediate takeoff"));
+        // Weather and closed runways still stop it
+        airport.weather.condition = WeatherCondition::InclementWeather;
+        assert!(parse_user_input(format!("it {} 1", name), &airport).is_err());
+        airport.weather.condition = WeatherCondition::Clear;
+        airport.closed_runways.push(1);
+        let error = parse_user_input(format!("it {} 1", name), &airport)
+            .unwrap_err()
+            .reason;
+        assert_eq!(error, "Runway 1 is closed");
+    }
+
+    #[test]
+    fn metar_sent_back_to_console() {
+        let mut airport = construct_airport(&args());
+        airport.weather.condition = WeatherCondition::Rain;
+        airport.weather.wind_direction = 268;
+        airport.weather.wind_speed = 9.6;
+        airport.weather.wind_gust = Some(21.0);
+        airport.tick = 5;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || user_input_thread(listener, None, sender));
+        let mut console = TcpStream::connect(address).unwrap();
+        console.write_all(b"metar\n").unwrap();
+        let command = receiver.recv_timeout(Duration::from_secs(2)).unwrap();
+        let (forward, game) = channel();
+        forward.send(command).unwrap();
+        let before = airport.planes.len();
+        update_aircraft_from_user_input(&mut airport, &game, &mut Vec::<String>::new());
+        assert_eq!(airport.planes.len(), before);
+
+        console
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let mut report = String::new();
+        BufReader::new(console).read_line(&mut report).unwrap();
+        let fields = report.split_whitespace().collect::<Vec<&str>>();
+        let [ok, kind, time, wind, visibility, weather] = fields[..] else {
+            panic!("{}", report);
+        };
+        assert_eq!(ok, "OK");
+        assert_eq!(kind, "METAR");
+        assert!(time.ends_with('Z') && time.starts_with("01"));
+        assert_eq!(&wind[..3], "270");
+        assert_eq!(&wind[3..5], "10");
+        assert_eq!(wind[6..8].parse::<usize>().unwrap(), 21);
+        assert!(wind.ends_with("KT"));
+        assert_eq!(visibility.parse::<usize>().unwrap(), 6000);
+        assert_eq!(weather, "RA");
     }
 }